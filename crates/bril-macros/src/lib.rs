@@ -1,19 +1,37 @@
 use proc_macro::TokenStream;
 use proc_macro2::{Ident, Span};
 use quote::{quote, ToTokens};
+use std::collections::{HashMap, HashSet};
 use std::str::FromStr;
+use syn::ext::IdentExt;
 use syn::parse::{Parse, ParseStream};
 use syn::punctuated::Punctuated;
-use syn::token::Const;
-use syn::{bracketed, parse_macro_input, LitInt, Token};
+use syn::spanned::Spanned;
+use syn::token::Paren;
+use syn::{
+    braced, bracketed, parenthesized, parse_macro_input, LitBool, LitFloat, LitInt, LitStr, Token,
+};
 
 /// The instruction macro takes the following values which need to
 /// be key value inputs:
-///     - op: The operation (mandatory)
+///     - op: The operation (mandatory). The operations of extensions
+///       which aren't known are written as strings, such as `"vecadd"`
 ///     - args: The arguments to the operations (optional)
 ///     - ty: The type of the input (optional)
 ///     - value: The value of the input (optional)
 ///     - dest: The variable destination of the operation (optional)
+///       Variables are identifiers, or strings such as "v.1" for the
+///       names which aren't identifiers
+///     - funcs: The functions called by the operation (optional). Calls
+///       can also be written `op = call @function(a, b)`
+///     - labels: The labels the operation refers to (optional)
+///
+/// The value, the destination and the arguments can be computed at runtime
+/// with `#ident` or `#(expression)`, the expression being converted with
+/// `Literal::from` or `Var::from`:
+///     instruction!(op = const, value = #n, dest = #(format!("v{i}")))
+/// A value which isn't a single literal is an integer expression, such
+/// as `value = N + 1`.
 #[proc_macro]
 pub fn instruction(input: TokenStream) -> TokenStream {
     let instruction = parse_macro_input!(input as Instruction);
@@ -24,6 +42,251 @@ pub fn instruction(input: TokenStream) -> TokenStream {
     output.into()
 }
 
+/// The instruction_unchecked macro takes the same inputs as the instruction
+/// macro, without verifying that the instruction is valid. It builds the
+/// malformed instructions used to test the error paths:
+///     instruction_unchecked!(op = add, args = [a], dest = b)
+#[proc_macro]
+pub fn instruction_unchecked(input: TokenStream) -> TokenStream {
+    let instruction = parse_macro_input!(input with Instruction::parse_fields);
+
+    let mut output = proc_macro2::TokenStream::new();
+    instruction.to_tokens(&mut output);
+
+    output.into()
+}
+
+/// The function macro builds a whole function. It takes the following
+/// key value inputs:
+///     - name: The name of the function (mandatory)
+///     - args: The typed arguments of the function, as `[a: int, b: bool]`
+///       (optional)
+///     - ty: The return type of the function (optional)
+///     - instrs: The body of the function, as a list of entries separated
+///       by `;`. An entry is either `label = name` or the inputs of the
+///       instruction macro (mandatory)
+#[proc_macro]
+pub fn function(input: TokenStream) -> TokenStream {
+    let function = parse_macro_input!(input as Function);
+
+    let mut output = proc_macro2::TokenStream::new();
+    function.to_tokens(&mut output);
+
+    output.into()
+}
+
+/// The label macro builds the label starting a block, to be placed
+/// among the instructions of a function body:
+///     label!(loop_start)
+#[proc_macro]
+pub fn label(input: TokenStream) -> TokenStream {
+    let label = parse_macro_input!(input with parse_name);
+
+    let mut output = proc_macro2::TokenStream::new();
+    Code::Label(label).to_tokens(&mut output);
+
+    output.into()
+}
+
+/// The block macro builds a block out of the inputs of several
+/// instructions separated by `;`:
+///     block![op = const, value = 1, dest = a; op = add, args = [a, a], dest = b]
+#[proc_macro]
+pub fn block(input: TokenStream) -> TokenStream {
+    let block = parse_macro_input!(input as Block);
+
+    let mut output = proc_macro2::TokenStream::new();
+    block.to_tokens(&mut output);
+
+    output.into()
+}
+
+/// The labeled_block macro builds a basic block with its label, as the
+/// entries of a function body. The label comes first, and the block must
+/// end with a terminator, no other instruction transferring control:
+///     labeled_block![label = body; op = add, args = [a, b], dest = c; op = jmp, labels = [end]]
+#[proc_macro]
+pub fn labeled_block(input: TokenStream) -> TokenStream {
+    let block = parse_macro_input!(input as LabeledBlock);
+
+    let mut output = proc_macro2::TokenStream::new();
+    block.to_tokens(&mut output);
+
+    output.into()
+}
+
+/// The instructions macro builds a block out of statements separated by
+/// `;`, in which the destination comes before the operation and its
+/// operands. The operands are the arguments, the value of a `const`,
+/// the functions as `@name` and the labels as `.name`:
+///     instructions! { a = const 1; sum: int = add a a; print sum; }
+#[proc_macro]
+pub fn instructions(input: TokenStream) -> TokenStream {
+    let block = parse_macro_input!(input with Block::parse_statements);
+
+    let mut output = proc_macro2::TokenStream::new();
+    block.to_tokens(&mut output);
+
+    output.into()
+}
+
+/// The program macro builds a program out of several functions, each one
+/// being the inputs of the function macro between braces:
+///     program![{ name = main, instrs = [...] }, { name = helper, ... }]
+/// The functions must have distinct names and the called functions
+/// must be defined.
+#[proc_macro]
+pub fn program(input: TokenStream) -> TokenStream {
+    let program = parse_macro_input!(input as Program);
+
+    let mut output = proc_macro2::TokenStream::new();
+    program.to_tokens(&mut output);
+
+    output.into()
+}
+
+/// The bril macro parses the Bril text format at compile time. The input
+/// is either functions, expanding to a `Function` if there is a single
+/// one and to a `BrilProgram` otherwise, or instructions without labels,
+/// expanding to a `Block`:
+///     bril! { a: int = const 1; b: int = add a a; print b; }
+///     bril! { @main { v.0: int = const 1; print v.0; } }
+/// Since the input is made of Rust tokens, it can't contain Bril comments.
+#[proc_macro]
+pub fn bril(input: TokenStream) -> TokenStream {
+    let input = source_text(input);
+
+    let parsed = if input.trim_start().starts_with('@') {
+        bril::text::parse_program(&input)
+            .map_err(|err| err.to_string())
+            .map(|mut program| {
+                if program.functions.len() == 1 {
+                    Function::from(program.functions.remove(0)).into_token_stream()
+                } else {
+                    Program(program.functions.into_iter().map(Function::from).collect())
+                        .into_token_stream()
+                }
+            })
+    } else {
+        // The block is wrapped on the first line to keep the line numbers
+        let block = bril::text::parse_program(&format!("@block {{ {input}\n}}"));
+        block.map_err(|err| err.to_string()).and_then(|mut program| {
+            let mut block = Vec::new();
+            for code in program.functions.remove(0).instrs {
+                match code {
+                    bril::types::Code::Instruction(inner) => block.push(Instruction {
+                        inner,
+                        ..Default::default()
+                    }),
+                    bril::types::Code::Label { label } => {
+                        return Err(format!(
+                            "blocks can't contain labels, got .{label}: wrap the block in a function"
+                        ))
+                    }
+                }
+            }
+            Ok(Block(block).into_token_stream())
+        })
+    };
+
+    parsed
+        .unwrap_or_else(|err| {
+            syn::Error::new(Span::call_site(), format!("invalid Bril: {err}")).to_compile_error()
+        })
+        .into()
+}
+
+/// The bril_json macro deserializes a JSON program at compile time and
+/// expands to the matching `BrilProgram`. The program is either a string
+/// literal, or a file relative to the directory of the crate's manifest:
+///     bril_json!(r#"{ "functions": [...] }"#)
+///     bril_json!(file = "tests/fixtures/add.json")
+/// The program must pass `verify_program`.
+#[proc_macro]
+pub fn bril_json(input: TokenStream) -> TokenStream {
+    let program = parse_macro_input!(input with LoadedProgram::parse_json);
+
+    let mut output = proc_macro2::TokenStream::new();
+    program.to_tokens(&mut output);
+
+    output.into()
+}
+
+/// The include_bril macro reads a program in the Bril text format at
+/// compile time, the path being relative to the directory of the crate's
+/// manifest. It expands to the matching `BrilProgram`, which must pass
+/// `verify_program`:
+///     include_bril!("tests/fixtures/fib.bril")
+#[proc_macro]
+pub fn include_bril(input: TokenStream) -> TokenStream {
+    let program = parse_macro_input!(input with LoadedProgram::parse_text_file);
+
+    let mut output = proc_macro2::TokenStream::new();
+    program.to_tokens(&mut output);
+
+    output.into()
+}
+
+/// The include_bril_json macro is the include_bril macro for JSON programs:
+///     include_bril_json!("tests/fixtures/fib.json")
+#[proc_macro]
+pub fn include_bril_json(input: TokenStream) -> TokenStream {
+    let program = parse_macro_input!(input with LoadedProgram::parse_json_file);
+
+    let mut output = proc_macro2::TokenStream::new();
+    program.to_tokens(&mut output);
+
+    output.into()
+}
+
+/// Returns the source of the tokens. The spacing of the source is kept,
+/// as Bril names such as `v.1` or `.label` are made of several Rust
+/// tokens, which the default formatting would separate.
+fn source_text(input: TokenStream) -> String {
+    // The end of the previous token is computed from its length, as the
+    // end of the spans isn't always known
+    type End = Option<(usize, usize)>;
+
+    fn push(text: &mut String, end: &mut End, span: proc_macro::Span, s: &str) {
+        if let Some((line, column)) = *end {
+            if line != span.line() {
+                text.push('\n');
+            } else if column != span.column() {
+                text.push(' ');
+            }
+        }
+        text.push_str(s);
+        *end = Some((span.line(), span.column() + s.chars().count()));
+    }
+
+    fn visit(text: &mut String, end: &mut End, input: TokenStream) {
+        for tree in input {
+            match tree {
+                proc_macro::TokenTree::Group(group) => {
+                    let (open, close) = match group.delimiter() {
+                        proc_macro::Delimiter::Brace => ("{", "}"),
+                        proc_macro::Delimiter::Parenthesis => ("(", ")"),
+                        proc_macro::Delimiter::Bracket => ("[", "]"),
+                        proc_macro::Delimiter::None => ("", ""),
+                    };
+                    push(text, end, group.span_open(), open);
+                    visit(text, end, group.stream());
+                    push(text, end, group.span_close(), close);
+                }
+                tree => {
+                    let span = tree.span();
+                    let source = span.source_text().unwrap_or_else(|| tree.to_string());
+                    push(text, end, span, &source);
+                }
+            }
+        }
+    }
+
+    let mut text = String::new();
+    visit(&mut text, &mut None, input);
+    text
+}
+
 /// Util macro for easy syn::Error generation
 macro_rules! error {
     ($span: expr, $msg: expr) => {
@@ -31,9 +294,167 @@ macro_rules! error {
     };
 }
 
+/// The Pass derive implements `bril::pass::Pass` for a type, so that it can
+/// be added to a `bril::pass::PassRegistry`. Unit structs are also registered
+/// in the inventory read by `bril::pass::PassRegistry::with_builtin`, the
+/// other types being added by hand. The `pass` attribute takes:
+///     - name: The name of the pass (optional, defaults to the type name
+///       in snake case)
+///     - block: The function running the pass on a block
+///     - function: The function running the pass on a function
+///
+/// One of block or function is mandatory, and they can return their result
+/// directly or in an `eyre::Result`:
+///     #[derive(Pass)]
+///     #[pass(name = "dce", block = multi_pass_dce)]
+///     struct DeadCodeElimination;
+#[proc_macro_derive(Pass, attributes(pass))]
+pub fn derive_pass(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as syn::DeriveInput);
+    derive_pass_impl(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn derive_pass_impl(input: syn::DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let mut name = None;
+    let mut block = None;
+    let mut function = None;
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("pass")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                name = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("block") {
+                block = Some(meta.value()?.parse::<syn::Path>()?);
+            } else if meta.path.is_ident("function") {
+                function = Some(meta.value()?.parse::<syn::Path>()?);
+            } else {
+                return Err(meta.error("expected name, block or function"));
+            }
+            Ok(())
+        })?;
+    }
+
+    let run = match (block, function) {
+        (Some(block), None) => quote! {
+            fn run_on_block(
+                &self,
+                block: bril::types::Block,
+            ) -> bril::pass::Result<bril::types::Block> {
+                bril::pass::PassOutput::into_result(#block(block))
+            }
+        },
+        (None, Some(function)) => quote! {
+            fn run_on_function(
+                &self,
+                function: &mut bril::types::Function,
+            ) -> bril::pass::Result<()> {
+                bril::pass::PassOutput::into_result(#function(function))
+            }
+        },
+        (Some(_), Some(function)) => {
+            return Err(error!(
+                function.span(),
+                "a pass runs on either blocks or functions"
+            ))
+        }
+        (None, None) => {
+            return Err(error!(
+                input.ident.span(),
+                "a pass requires #[pass(block = ...)] or #[pass(function = ...)]"
+            ))
+        }
+    };
+    let name = name.unwrap_or_else(|| snake_case(&input.ident.to_string()));
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    // Only the unit structs can be built without knowing their fields
+    let unit = match &input.data {
+        syn::Data::Struct(data) => matches!(data.fields, syn::Fields::Unit),
+        _ => false,
+    };
+    let registration = (unit && input.generics.params.is_empty()).then(|| {
+        quote! {
+            bril::pass::inventory::submit! {
+                bril::pass::Registration::new(|| ::std::boxed::Box::new(#ident))
+            }
+        }
+    });
+    Ok(quote! {
+        impl #impl_generics bril::pass::Pass for #ident #ty_generics #where_clause {
+            fn name(&self) -> &'static str {
+                #name
+            }
+
+            #run
+        }
+
+        #registration
+    })
+}
+
+/// Converts a camel case type name to snake case
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() && i != 0 {
+            snake.push('_');
+        }
+        snake.extend(c.to_lowercase());
+    }
+    snake
+}
+
 /// Wrapper around a bril Instruction. Used for parsing.
 #[derive(Default, Debug)]
-struct Instruction(bril::types::Instruction);
+struct Instruction {
+    inner: bril::types::Instruction,
+    splices: Splices,
+    spans: Spans,
+}
+
+/// The spans of the keys of an instruction, used to point
+/// the validation errors at the offending key
+#[derive(Default, Debug)]
+struct Spans {
+    op: Option<Span>,
+    args: Option<Span>,
+    value: Option<Span>,
+    ty: Option<Span>,
+    dest: Option<Span>,
+    funcs: Option<Span>,
+    labels: Option<Span>,
+}
+
+/// The runtime expressions spliced into an instruction. The instruction
+/// holds placeholders in their place, so that it can still be validated.
+#[derive(Default, Debug)]
+struct Splices {
+    value: Option<proc_macro2::TokenStream>,
+    dest: Option<proc_macro2::TokenStream>,
+    /// The spliced arguments, by position
+    args: HashMap<usize, proc_macro2::TokenStream>,
+}
+
+/// A value known at compile time or computed at runtime
+enum Spliced<T> {
+    Known(T),
+    Splice(proc_macro2::TokenStream),
+}
+
+/// Parses `#ident` or `#(expression)` if the input starts with a `#`
+fn parse_splice(input: ParseStream) -> syn::Result<Option<proc_macro2::TokenStream>> {
+    if input.parse::<Option<Token![#]>>()?.is_none() {
+        return Ok(None);
+    }
+    if input.peek(Paren) {
+        let content;
+        parenthesized!(content in input);
+        return Ok(Some(content.parse()?));
+    }
+    Ok(Some(input.parse::<Ident>()?.to_token_stream()))
+}
 
 mod kw {
     syn::custom_keyword!(op);
@@ -41,11 +462,24 @@ mod kw {
     syn::custom_keyword!(ty);
     syn::custom_keyword!(value);
     syn::custom_keyword!(dest);
+    syn::custom_keyword!(funcs);
+    syn::custom_keyword!(labels);
+    syn::custom_keyword!(label);
+    syn::custom_keyword!(name);
+    syn::custom_keyword!(instrs);
 }
 
 impl Parse for Instruction {
     fn parse(input: ParseStream) -> syn::Result<Self> {
-        if input.is_empty() {
+        Self::parse_fields(input)?.validate(input.span())
+    }
+}
+
+impl Instruction {
+    /// Parses the inputs of the instruction, up to the end of
+    /// the stream or to the `;` ending an entry of a body
+    fn parse_fields(input: ParseStream) -> syn::Result<Self> {
+        if input.is_empty() || input.peek(Token![;]) {
             return Err(error!(input.span(), "expected at least an 'op' field"));
         }
 
@@ -54,34 +488,75 @@ impl Parse for Instruction {
         let mut instruction = Instruction::default();
 
         // Keep parsing while there are values in the stream
-        while !input.is_empty() {
+        while !input.is_empty() && !input.peek(Token![;]) {
+            let key = Some(input.span());
             if input.peek(kw::op) {
                 if has_operation {
                     return Err(error!(input.span(), "operation already set"));
                 }
-                instruction.0.op = Operation::parse(input)?.0;
+                instruction.spans.op = key;
+                instruction.inner.op = Operation::parse(input)?.0;
                 has_operation = true;
+
+                // Calls can be written as `call @function(args)`
+                if instruction.inner.op == bril::types::Operation::Call && input.peek(Token![@]) {
+                    let _ = input.parse::<Token![@]>()?;
+                    instruction.spans.funcs = Some(input.span());
+                    instruction.inner.funcs = Some(vec![parse_name(input)?]);
+
+                    let content;
+                    parenthesized!(content in input);
+                    instruction.spans.args = Some(content.span());
+                    let args =
+                        Punctuated::<_, Token![,]>::parse_terminated_with(&content, parse_var)?;
+                    instruction.set_args(args);
+                    has_args = true;
+                }
             } else if input.peek(kw::args) {
                 if has_args {
                     return Err(error!(input.span(), "args already set"));
                 }
-                instruction.0.args = Args::parse(input)?.0;
+                instruction.spans.args = key;
+                instruction.set_args(Args::parse(input)?.0);
                 has_args = true;
             } else if input.peek(kw::value) {
-                if instruction.0.value.is_some() {
+                if instruction.inner.value.is_some() || instruction.splices.value.is_some() {
                     return Err(error!(input.span(), "value already set"));
                 }
-                instruction.0.value = Some(input.parse::<Value>()?.0);
+                instruction.spans.value = key;
+                instruction.set_value(input.parse()?);
             } else if input.peek(kw::ty) {
-                if instruction.0.r#type.is_some() {
+                if instruction.inner.r#type.is_some() {
                     return Err(error!(input.span(), "type already set"));
                 }
-                instruction.0.r#type = Some(input.parse::<Type>()?.0);
+                instruction.spans.ty = key;
+                instruction.inner.r#type = Some(input.parse::<Type>()?.0);
             } else if input.peek(kw::dest) {
-                if instruction.0.dest.is_some() {
+                if instruction.inner.dest.is_some() {
                     return Err(error!(input.span(), "dest already set"));
                 }
-                instruction.0.dest = Some(input.parse::<Dest>()?.0)
+                instruction.spans.dest = key;
+                instruction.inner.dest = Some(match input.parse::<Dest>()?.0 {
+                    Spliced::Known(dest) => dest,
+                    Spliced::Splice(splice) => {
+                        instruction.splices.dest = Some(splice);
+                        "#dest".into()
+                    }
+                });
+            } else if input.peek(kw::funcs) {
+                if instruction.inner.funcs.is_some() {
+                    return Err(error!(input.span(), "funcs already set"));
+                }
+                instruction.spans.funcs = key;
+                let _ = input.parse::<kw::funcs>()?;
+                instruction.inner.funcs = Some(parse_names(input)?);
+            } else if input.peek(kw::labels) {
+                if instruction.inner.labels.is_some() {
+                    return Err(error!(input.span(), "labels already set"));
+                }
+                instruction.spans.labels = key;
+                let _ = input.parse::<kw::labels>()?;
+                instruction.inner.labels = Some(parse_names(input)?);
             } else {
                 return Err(error!(
                     input.span(),
@@ -100,12 +575,195 @@ impl Parse for Instruction {
             return Err(error!(input.span(), "'op' attribute needs to be set"));
         }
 
-        // Before returning, we verify if the instruction is a valid instruction
-        if !instruction.0.is_valid() {
-            return Err(error!(input.span(), "invalid instruction"));
+        Ok(instruction)
+    }
+
+    /// Sets the value, the integer expressions being replaced by a placeholder
+    fn set_value(&mut self, value: Value) {
+        match value {
+            Value::Spliced(Spliced::Known(value)) => self.inner.value = Some(value),
+            Value::Spliced(Spliced::Splice(splice)) => self.splices.value = Some(splice),
+            Value::Int(expr) => {
+                self.splices.value = Some(quote!(bril::types::Literal::Int(#expr)));
+                self.inner.value = Some(bril::types::Literal::Int(0));
+            }
         }
+    }
 
-        Ok(instruction)
+    /// Sets the arguments, the spliced ones being replaced by placeholders
+    fn set_args(&mut self, args: impl IntoIterator<Item = Spliced<bril::types::Var>>) {
+        for (i, arg) in args.into_iter().enumerate() {
+            let arg = match arg {
+                Spliced::Known(arg) => arg,
+                Spliced::Splice(splice) => {
+                    self.splices.args.insert(i, splice);
+                    format!("#{i}").into()
+                }
+            };
+            self.inner.args.push(arg);
+        }
+    }
+
+    /// Verifies if the instruction is a valid instruction,
+    /// reporting the error at the provided span otherwise
+    fn validate(mut self, span: Span) -> syn::Result<Self> {
+        // A spliced value is only known at runtime, the placeholder
+        // matches the type so that the constant can be validated
+        if self.splices.value.is_some() && self.inner.value.is_none() {
+            self.inner.value = Some(match self.inner.r#type {
+                Some(bril::types::Type::Bool) => bril::types::Literal::Bool(false),
+                Some(bril::types::Type::Float) => bril::types::Literal::Float(0.0),
+                Some(bril::types::Type::Char) => bril::types::Literal::Char('a'),
+                _ => bril::types::Literal::Int(0),
+            });
+        }
+
+        if !self.inner.is_valid() {
+            let (key, hint) = self.diagnose();
+            let span = key.or(self.spans.op).unwrap_or(span);
+            return Err(error!(span, format!("invalid instruction: {hint}")));
+        }
+
+        Ok(self)
+    }
+
+    /// Returns the span of the key which makes the instruction invalid, if
+    /// it is known, and the rule it breaks. The rules aren't duplicated
+    /// from bril: the instruction is fixed in various ways, the first fix
+    /// making it valid telling what is wrong.
+    fn diagnose(&self) -> (Option<Span>, String) {
+        use bril::types::{Literal, Type};
+
+        let instruction = &self.inner;
+        let op = &instruction.op;
+        let valid_with = |fix: &dyn Fn(&mut bril::types::Instruction)| {
+            let mut fixed = instruction.clone();
+            fix(&mut fixed);
+            fixed.is_valid()
+        };
+        let types = [Type::Int, Type::Bool, Type::Float, Type::Char];
+
+        if let (Some(value), Some(ty)) = (instruction.value, &instruction.r#type) {
+            if !value.matches_type(ty) {
+                // The placeholder of an integer expression is only known to be an int
+                let value = match self.splices.value {
+                    Some(_) => "an integer expression".to_string(),
+                    None => value.to_string(),
+                };
+                return (
+                    self.spans.value,
+                    format!("{value} isn't a valid {ty} value"),
+                );
+            }
+        }
+        if let Some(ty) = &instruction.r#type {
+            let expected = types
+                .iter()
+                .find(|t| valid_with(&|i| i.r#type = Some((*t).clone())));
+            if let Some(expected) = expected {
+                return (self.spans.ty, format!("{op} produces {expected}, not {ty}"));
+            }
+        }
+
+        type Fix = fn(&mut bril::types::Instruction);
+        let unexpected: [(bool, Option<Span>, &str, Fix); 5] = [
+            (
+                instruction.value.is_some(),
+                self.spans.value,
+                "a value",
+                |i| i.value = None,
+            ),
+            (instruction.r#type.is_some(), self.spans.ty, "a type", |i| {
+                i.r#type = None
+            }),
+            (
+                instruction.dest.is_some(),
+                self.spans.dest,
+                "a destination",
+                |i| i.dest = None,
+            ),
+            (
+                instruction.funcs.is_some(),
+                self.spans.funcs,
+                "functions",
+                |i| i.funcs = None,
+            ),
+            (
+                instruction.labels.is_some(),
+                self.spans.labels,
+                "labels",
+                |i| i.labels = None,
+            ),
+        ];
+        for (present, span, what, fix) in unexpected {
+            if present && valid_with(&fix) {
+                return (span, format!("{op} doesn't take {what}"));
+            }
+        }
+
+        if instruction.value.is_none() && valid_with(&|i| i.value = Some(Literal::Int(0))) {
+            return (None, format!("{op} requires a value"));
+        }
+        if instruction.dest.is_none() && valid_with(&|i| i.dest = Some("dest".into())) {
+            return (None, format!("{op} requires a destination"));
+        }
+        if instruction.r#type.is_none()
+            && types
+                .iter()
+                .any(|t| valid_with(&|i| i.r#type = Some(t.clone())))
+        {
+            return (None, format!("{op} requires a type"));
+        }
+
+        // Fixing either count of a phi would hide that they must match
+        let len = |names: &Option<Vec<String>>| names.as_ref().map_or(0, Vec::len);
+        if *op == bril::types::Operation::Phi && len(&instruction.labels) != instruction.args.len()
+        {
+            return (
+                self.spans.labels,
+                format!(
+                    "phi takes a label per argument, got {} argument(s) and {} label(s)",
+                    instruction.args.len(),
+                    len(&instruction.labels)
+                ),
+            );
+        }
+
+        let counts = |len: usize,
+                      what: &str,
+                      span: Option<Span>,
+                      fix: fn(&mut bril::types::Instruction, usize)| {
+            (0..=3)
+                .filter(|&n| n != len)
+                .find(|&n| valid_with(&|i| fix(i, n)))
+                .map(|n| {
+                    let plural = if n == 1 { "" } else { "s" };
+                    (span, format!("{op} takes {n} {what}{plural}, got {len}"))
+                })
+        };
+        counts(
+            instruction.args.len(),
+            "argument",
+            self.spans.args,
+            |i, n| i.args = (0..n).map(|n| format!("arg{n}").into()).collect(),
+        )
+        .or_else(|| {
+            counts(
+                len(&instruction.labels),
+                "label",
+                self.spans.labels,
+                |i, n| i.labels = Some((0..n).map(|n| format!("label{n}")).collect()),
+            )
+        })
+        .or_else(|| {
+            counts(
+                len(&instruction.funcs),
+                "function",
+                self.spans.funcs,
+                |i, n| i.funcs = Some((0..n).map(|n| format!("f{n}")).collect()),
+            )
+        })
+        .unwrap_or_else(|| (None, format!("no {op} instruction has these inputs")))
     }
 }
 
@@ -113,42 +771,103 @@ impl ToTokens for Instruction {
     fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
         let none = quote!(None);
 
-        let op = Ident::new(&format!("{:?}", self.0.op), Span::call_site());
-        let op = quote!(bril::types::Operation::#op);
+        let op = match &self.inner.op {
+            bril::types::Operation::Other(op) => {
+                quote!(bril::types::Operation::Other(#op.to_string()))
+            }
+            op => {
+                let op = Ident::new(&format!("{op:?}"), Span::call_site());
+                quote!(bril::types::Operation::#op)
+            }
+        };
 
-        let args = self.0.args.iter().map(|arg| quote!(#arg.into()));
+        let args = self.inner.args.iter().enumerate().map(|(i, arg)| {
+            if let Some(splice) = self.splices.args.get(&i) {
+                return quote!(bril::types::Var::from(#splice));
+            }
+            let arg = arg.as_str();
+            quote!(#arg.into())
+        });
 
         let ty = self
-            .0
+            .inner
             .r#type
             .as_ref()
             .map(|t| {
-                let t = Ident::new(&format!("{t:?}"), Span::call_site());
-                quote!(Some(bril::types::Type::#t))
+                let t = type_tokens(t);
+                quote!(Some(#t))
             })
             .unwrap_or_else(|| none.clone());
 
         let value = self
-            .0
+            .splices
             .value
             .as_ref()
-            .map(|v| quote!(Some(#v)))
+            .map(|splice| quote!(Some(bril::types::Literal::from(#splice))));
+        let value = value
+            .or_else(|| {
+                self.inner.value.as_ref().map(|v| match v {
+                    bril::types::Literal::Bool(v) => quote!(Some(bril::types::Literal::Bool(#v))),
+                    bril::types::Literal::Int(v) => quote!(Some(bril::types::Literal::Int(#v))),
+                    bril::types::Literal::Float(v) => quote!(Some(bril::types::Literal::Float(#v))),
+                    bril::types::Literal::Char(v) => quote!(Some(bril::types::Literal::Char(#v))),
+                })
+            })
+            .unwrap_or_else(|| none.clone());
+
+        let dest = match (&self.splices.dest, &self.inner.dest) {
+            (Some(splice), _) => quote!(Some(bril::types::Var::from(#splice))),
+            (None, Some(d)) => {
+                let d = d.as_str();
+                quote!(Some(#d.into()))
+            }
+            (None, None) => none.clone(),
+        };
+
+        let funcs = self
+            .inner
+            .funcs
+            .as_ref()
+            .map(|f| {
+                let f = f.iter();
+                quote!(Some(vec![#(#f.to_string(),)*]))
+            })
             .unwrap_or_else(|| none.clone());
 
-        let dest = self
-            .0
-            .dest
+        let labels = self
+            .inner
+            .labels
             .as_ref()
-            .map(|d| quote!(Some(#d.to_string())))
+            .map(|l| {
+                let l = l.iter();
+                quote!(Some(vec![#(#l.to_string(),)*]))
+            })
+            .unwrap_or_else(|| none.clone());
+
+        let pos = self
+            .inner
+            .pos
+            .map(|bril::types::Position { row, col }| {
+                quote!(Some(bril::types::Position { row: #row, col: #col }))
+            })
             .unwrap_or_else(|| none.clone());
 
+        let attrs = self.inner.attrs.as_ref().and_then(json_tokens);
+        let attrs = attrs.map_or_else(|| none.clone(), |attrs| quote!(Some(#attrs)));
+        let extra = json_tokens(&self.inner.extra).unwrap_or_else(|| quote!(Default::default()));
+
         let instr = quote!(
             bril::types::Instruction {
                 op: #op,
                 args: vec![#(#args,)*],
                 value: #value,
                 dest: #dest,
-                r#type: #ty
+                r#type: #ty,
+                funcs: #funcs,
+                labels: #labels,
+                pos: #pos,
+                attrs: #attrs,
+                extra: #extra
             }
         );
 
@@ -156,39 +875,117 @@ impl ToTokens for Instruction {
     }
 }
 
+/// Returns the tokens building the provided type
+/// Returns the expression deserializing the JSON fields, none if there are
+/// no fields. They are only found in the programs read from JSON.
+fn json_tokens(
+    fields: &std::collections::BTreeMap<String, serde_json::Value>,
+) -> Option<proc_macro2::TokenStream> {
+    if fields.is_empty() {
+        return None;
+    }
+    let json = serde_json::to_string(fields).expect("fields are valid JSON");
+    Some(quote!(bril::serde_json::from_str(#json).expect("fields are valid JSON")))
+}
+
+fn type_tokens(ty: &bril::types::Type) -> proc_macro2::TokenStream {
+    match ty {
+        bril::types::Type::Ptr(t) => {
+            let t = type_tokens(t);
+            quote!(bril::types::Type::Ptr(Box::new(#t)))
+        }
+        t => {
+            let t = Ident::new(&format!("{t:?}"), Span::call_site());
+            quote!(bril::types::Type::#t)
+        }
+    }
+}
+
 struct Operation(bril::types::Operation);
 
 impl Parse for Operation {
     fn parse(input: ParseStream) -> syn::Result<Self> {
         let _ = input.parse::<kw::op>()?;
         let _ = input.parse::<Token![=]>()?;
-        let op = input.parse::<Ident>();
+        Ok(Self(parse_operation(input)?))
+    }
+}
 
-        // If the parsing failed, try to parse the `const` keyword.
-        // This is the only operation that needs special attention
-        // because `const` is a reserved keyword
-        if op.is_err() {
-            let _ = input.parse::<Const>()?;
-            return Ok(Self(bril::types::Operation::Const));
+/// Parses the name of an operation, or a string for the operations of
+/// the extensions which aren't known, such as `"vecadd"`
+fn parse_operation(input: ParseStream) -> syn::Result<bril::types::Operation> {
+    if input.peek(LitStr) {
+        let name = input.parse::<LitStr>()?.value();
+        if name.is_empty() {
+            return Err(error!(input.span(), "operations can't be empty"));
         }
-
-        let op = op?.to_string();
-        Ok(Self(bril::types::Operation::from_str(&op).map_err(
-            |_| error!(input.span(), format!("expected valid operation, got {op}")),
-        )?))
+        return Ok(
+            bril::types::Operation::from_str(&name).unwrap_or(bril::types::Operation::Other(name))
+        );
     }
+
+    // `const` and other keywords are valid operations
+    let op = Ident::parse_any(input)?;
+    bril::types::Operation::from_str(&op.to_string())
+        .map_err(|_| error!(op.span(), format!("expected valid operation, got {op}")))
 }
 
-struct Value(u32);
+/// The value of an instruction: a literal, a splice, or an integer
+/// expression such as `N + 1`, which is evaluated at runtime
+enum Value {
+    Spliced(Spliced<bril::types::Literal>),
+    Int(syn::Expr),
+}
 
 impl Parse for Value {
     fn parse(input: ParseStream) -> syn::Result<Self> {
         let _ = input.parse::<kw::value>()?;
         let _ = input.parse::<Token![=]>()?;
-        let value = input.parse::<LitInt>()?.base10_parse()?;
+        parse_value(input)
+    }
+}
 
-        Ok(Self(value))
+/// Parses a value, which is an expression unless it is a single literal
+fn parse_value(input: ParseStream) -> syn::Result<Value> {
+    let fork = input.fork();
+    let literal = parse_literal(&fork).is_ok()
+        && (fork.is_empty() || fork.peek(Token![,]) || fork.peek(Token![;]));
+    if literal {
+        return Ok(Value::Spliced(parse_literal(input)?));
     }
+    Ok(Value::Int(input.parse()?))
+}
+
+/// Parses a boolean, a number which can be negative, or a splice
+fn parse_literal(input: ParseStream) -> syn::Result<Spliced<bril::types::Literal>> {
+    if let Some(splice) = parse_splice(input)? {
+        return Ok(Spliced::Splice(splice));
+    }
+    if input.peek(LitBool) {
+        let value = input.parse::<LitBool>()?.value;
+        return Ok(Spliced::Known(bril::types::Literal::Bool(value)));
+    }
+
+    // Numbers can be negated, the sign being part of the parsed
+    // digits so that the smallest integer doesn't overflow
+    let sign = if input.parse::<Option<Token![-]>>()?.is_some() {
+        "-"
+    } else {
+        ""
+    };
+    if input.peek(LitFloat) {
+        let lit = input.parse::<LitFloat>()?;
+        let value = format!("{sign}{}", lit.base10_digits())
+            .parse()
+            .map_err(|_| error!(lit.span(), "invalid float value"))?;
+        return Ok(Spliced::Known(bril::types::Literal::Float(value)));
+    }
+    let lit = input.parse::<LitInt>()?;
+    let value = format!("{sign}{}", lit.base10_digits())
+        .parse()
+        .map_err(|_| error!(lit.span(), "integer value out of range"))?;
+
+    Ok(Spliced::Known(bril::types::Literal::Int(value)))
 }
 
 struct Type(bril::types::Type);
@@ -197,27 +994,37 @@ impl Parse for Type {
     fn parse(input: ParseStream) -> syn::Result<Self> {
         let _ = input.parse::<kw::ty>()?;
         let _ = input.parse::<Token![=]>()?;
-        let ty = input.parse::<Ident>()?.to_string();
-
-        Ok(Self(bril::types::Type::from_str(&ty).map_err(|_| {
-            error!(input.span(), format!("expected valid type, got {ty}"))
-        })?))
+        Ok(Self(parse_type(input)?))
     }
 }
 
-struct Dest(bril::types::Var);
+struct Dest(Spliced<bril::types::Var>);
 
 impl Parse for Dest {
     fn parse(input: ParseStream) -> syn::Result<Self> {
         let _ = input.parse::<kw::dest>()?;
         let _ = input.parse::<Token![=]>()?;
-        let ty = input.parse::<Ident>()?.to_string();
+        Ok(Self(parse_var(input)?))
+    }
+}
 
-        Ok(Self(ty))
+/// Parses a variable: a name, a string for the names which aren't
+/// identifiers such as `v.1`, or a splice
+fn parse_var(input: ParseStream) -> syn::Result<Spliced<bril::types::Var>> {
+    if let Some(splice) = parse_splice(input)? {
+        return Ok(Spliced::Splice(splice));
+    }
+    if input.peek(LitStr) {
+        let name = input.parse::<LitStr>()?;
+        if name.value().is_empty() {
+            return Err(error!(name.span(), "variable names can't be empty"));
+        }
+        return Ok(Spliced::Known(name.value().into()));
     }
+    Ok(Spliced::Known(parse_name(input)?.into()))
 }
 
-struct Args(bril::types::Args);
+struct Args(Vec<Spliced<bril::types::Var>>);
 
 impl Parse for Args {
     fn parse(input: ParseStream) -> syn::Result<Self> {
@@ -228,9 +1035,561 @@ impl Parse for Args {
         let content;
         bracketed!(content in input);
 
-        let args = Punctuated::<Ident, Token![,]>::parse_terminated(&content)?;
-        let args = args.into_iter().map(|i| i.to_string()).collect();
+        let args = Punctuated::<_, Token![,]>::parse_terminated_with(&content, parse_var)?;
+
+        Ok(Self(args.into_iter().collect()))
+    }
+}
+
+/// Parses `= [a, b, ...]`, the identifiers being returned as strings
+fn parse_names(input: ParseStream) -> syn::Result<Vec<String>> {
+    let _ = input.parse::<Token![=]>()?;
+
+    let content;
+    bracketed!(content in input);
+
+    let names = Punctuated::<_, Token![,]>::parse_terminated_with(&content, parse_name)?;
+    Ok(names.into_iter().collect())
+}
+
+/// Parses a name, which can be a keyword such as `type` or `loop` since
+/// these are valid in Bril. The prefix of raw identifiers is stripped.
+fn parse_name(input: ParseStream) -> syn::Result<String> {
+    Ok(Ident::parse_any(input)?.unraw().to_string())
+}
+
+/// Parses a type identifier, or a pointer type as `ptr<type>`
+fn parse_type(input: ParseStream) -> syn::Result<bril::types::Type> {
+    let ty = input.parse::<Ident>()?;
+    if ty == "ptr" {
+        let _ = input.parse::<Token![<]>()?;
+        let inner = parse_type(input)?;
+        let _ = input.parse::<Token![>]>()?;
+        return Ok(bril::types::Type::Ptr(Box::new(inner)));
+    }
+    bril::types::Type::from_str(&ty.to_string())
+        .map_err(|_| error!(ty.span(), format!("expected valid type, got {ty}")))
+}
+
+/// Wrapper around an entry of a function body. Used for parsing.
+enum Code {
+    Label(String),
+    Instruction(Box<Instruction>),
+}
+
+impl Parse for Code {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.peek(kw::label) {
+            let _ = input.parse::<kw::label>()?;
+            let _ = input.parse::<Token![=]>()?;
+            return Ok(Self::Label(parse_name(input)?));
+        }
+
+        Ok(Self::Instruction(Box::new(Instruction::parse_entry(
+            input,
+        )?)))
+    }
+}
+
+impl Instruction {
+    /// Parses an instruction ending with a `;` or the end of the stream,
+    /// the errors pointing at the start of the offending instruction
+    fn parse_entry(input: ParseStream) -> syn::Result<Self> {
+        let span = input.span();
+        Self::parse_fields(input)?.validate(span)
+    }
+
+    /// Parses a statement as `dest: type = op operands`, the destination
+    /// and the type being optional. The operands are variables, values
+    /// for `const`, `@function` and `.label`.
+    fn parse_statement(input: ParseStream) -> syn::Result<Self> {
+        let span = input.span();
+        if input.peek(Token![.]) {
+            return Err(error!(span, "blocks can't contain labels"));
+        }
+
+        let mut instruction = Instruction::default();
+        let fork = input.fork();
+        if parse_var(&fork).is_ok() && (fork.peek(Token![=]) || fork.peek(Token![:])) {
+            instruction.spans.dest = Some(input.span());
+            instruction.inner.dest = Some(match parse_var(input)? {
+                Spliced::Known(dest) => dest,
+                Spliced::Splice(splice) => {
+                    instruction.splices.dest = Some(splice);
+                    "#dest".into()
+                }
+            });
+            if input.parse::<Option<Token![:]>>()?.is_some() {
+                instruction.spans.ty = Some(input.span());
+                instruction.inner.r#type = Some(parse_type(input)?);
+            }
+            let _ = input.parse::<Token![=]>()?;
+        }
+
+        instruction.spans.op = Some(input.span());
+        instruction.inner.op = parse_operation(input)?;
+
+        while !input.is_empty() && !input.peek(Token![;]) {
+            let key = Some(input.span());
+            if input.parse::<Option<Token![@]>>()?.is_some() {
+                instruction.spans.funcs = instruction.spans.funcs.or(key);
+                let name = parse_name(input)?;
+                instruction
+                    .inner
+                    .funcs
+                    .get_or_insert_with(Vec::new)
+                    .push(name);
+            } else if input.parse::<Option<Token![.]>>()?.is_some() {
+                instruction.spans.labels = instruction.spans.labels.or(key);
+                let name = parse_name(input)?;
+                instruction
+                    .inner
+                    .labels
+                    .get_or_insert_with(Vec::new)
+                    .push(name);
+            } else if instruction.inner.op == bril::types::Operation::Const {
+                if instruction.inner.value.is_some() || instruction.splices.value.is_some() {
+                    return Err(error!(input.span(), "value already set"));
+                }
+                instruction.spans.value = key;
+                instruction.set_value(parse_value(input)?);
+            } else {
+                instruction.spans.args = instruction.spans.args.or(key);
+                let arg = match parse_var(input)? {
+                    Spliced::Known(arg) => arg,
+                    Spliced::Splice(splice) => {
+                        let i = instruction.inner.args.len();
+                        instruction.splices.args.insert(i, splice);
+                        format!("#{i}").into()
+                    }
+                };
+                instruction.inner.args.push(arg);
+            }
+        }
+
+        instruction.validate(span)
+    }
+}
+
+/// Wrapper around a bril Block. Used for parsing.
+struct Block(Vec<Instruction>);
+
+impl Parse for Block {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let block = Punctuated::<Instruction, Token![;]>::parse_terminated_with(input, |input| {
+            if input.peek(kw::label) {
+                return Err(error!(input.span(), "blocks can't contain labels"));
+            }
+            Instruction::parse_entry(input)
+        })?;
+
+        Ok(Self(block.into_iter().collect()))
+    }
+}
+
+impl Block {
+    /// Parses the statements of the instructions macro
+    fn parse_statements(input: ParseStream) -> syn::Result<Self> {
+        let block = Punctuated::<Instruction, Token![;]>::parse_terminated_with(
+            input,
+            Instruction::parse_statement,
+        )?;
+
+        Ok(Self(block.into_iter().collect()))
+    }
+}
+
+impl ToTokens for Block {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        let instructions = &self.0;
+        tokens.extend(quote!(<bril::types::Block>::from([#(#instructions,)*])));
+    }
+}
+
+/// A program checked at compile time and the file it was read from, if
+/// any. Used for parsing.
+struct LoadedProgram {
+    program: Program,
+    extra: bril::types::Extra,
+    file: Option<String>,
+}
+
+/// The input of the include macros: the path to the file of a program
+struct ProgramFile {
+    contents: String,
+    path: String,
+    span: Span,
+}
+
+impl Parse for ProgramFile {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let path = input.parse::<LitStr>()?;
+        let dir = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+        let full = std::path::Path::new(&dir).join(path.value());
+        let contents = std::fs::read_to_string(&full)
+            .map_err(|err| error!(path.span(), format!("can't read {}: {err}", full.display())))?;
+
+        Ok(Self {
+            contents,
+            path: full.display().to_string(),
+            span: path.span(),
+        })
+    }
+}
+
+impl LoadedProgram {
+    /// Verifies the program, reporting the first issue at the span
+    fn new(
+        program: bril::types::BrilProgram,
+        span: Span,
+        file: Option<String>,
+    ) -> syn::Result<Self> {
+        if let Some(diagnostic) = bril::verify::verify_program(&program).first() {
+            return Err(error!(span, format!("invalid program: {diagnostic}")));
+        }
+        let functions = program.functions.into_iter().map(Function::from);
+        Ok(Self {
+            program: Program(functions.collect()),
+            extra: program.extra,
+            file,
+        })
+    }
+
+    /// Parses a JSON program, or a JSON file as `file = "path"`
+    fn parse_json(input: ParseStream) -> syn::Result<Self> {
+        let (json, span, file) = if input.peek(LitStr) {
+            let json = input.parse::<LitStr>()?;
+            (json.value(), json.span(), None)
+        } else {
+            let key = input.parse::<Ident>()?;
+            if key != "file" {
+                return Err(error!(key.span(), "expected a string or `file = \"path\"`"));
+            }
+            let _ = input.parse::<Token![=]>()?;
+            let file = input.parse::<ProgramFile>()?;
+            (file.contents, file.span, Some(file.path))
+        };
+
+        let program = bril::migrate::from_str(&json)
+            .map_err(|err| error!(span, format!("invalid JSON program: {err}")))?;
+        Self::new(program, span, file)
+    }
+
+    /// Parses the path of a file in the Bril text format
+    fn parse_text_file(input: ParseStream) -> syn::Result<Self> {
+        let file = input.parse::<ProgramFile>()?;
+        let program = bril::text::parse_program(&file.contents)
+            .map_err(|err| error!(file.span, format!("invalid Bril: {err}")))?;
+        Self::new(program, file.span, Some(file.path))
+    }
 
-        Ok(Self(args))
+    /// Parses the path of a JSON file
+    fn parse_json_file(input: ParseStream) -> syn::Result<Self> {
+        let file = input.parse::<ProgramFile>()?;
+        let program = bril::migrate::from_str(&file.contents)
+            .map_err(|err| error!(file.span, format!("invalid JSON program: {err}")))?;
+        Self::new(program, file.span, Some(file.path))
+    }
+}
+
+impl ToTokens for LoadedProgram {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        let program = &self.program;
+        // Including the file rebuilds the crate when it changes
+        let file = self.file.as_ref().map(|file| {
+            quote!(
+                const _: &str = include_str!(#file);
+            )
+        });
+        let program = match json_tokens(&self.extra) {
+            Some(extra) => quote!({
+                let mut program = #program;
+                program.extra = #extra;
+                program
+            }),
+            None => quote!(#program),
+        };
+        tokens.extend(quote!({
+            #file
+            #program
+        }));
+    }
+}
+
+/// Wrapper around a label and the instructions of its basic block. Used for parsing.
+struct LabeledBlock {
+    label: String,
+    instrs: Vec<Instruction>,
+}
+
+impl Parse for LabeledBlock {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let span = input.span();
+        if !input.peek(kw::label) {
+            return Err(error!(
+                span,
+                "expected the block to start with `label = name`"
+            ));
+        }
+        let _ = input.parse::<kw::label>()?;
+        let _ = input.parse::<Token![=]>()?;
+        let label = parse_name(input)?;
+        let _ = input.parse::<Token![;]>()?;
+
+        let instrs = Block::parse(input)?.0;
+        let Some((terminator, body)) = instrs.split_last() else {
+            return Err(error!(span, "the block must end with a terminator"));
+        };
+        if !terminator.inner.is_terminator() {
+            return Err(error!(
+                terminator.spans.op.unwrap_or(span),
+                format!(
+                    "the block must end with a terminator, got {}",
+                    terminator.inner.op
+                )
+            ));
+        }
+        if let Some(instruction) = body.iter().find(|i| i.inner.is_control_flow()) {
+            return Err(error!(
+                instruction.spans.op.unwrap_or(span),
+                format!(
+                    "{} can only end the block, as it transfers control",
+                    instruction.inner.op
+                )
+            ));
+        }
+
+        Ok(Self { label, instrs })
+    }
+}
+
+impl ToTokens for LabeledBlock {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        let label = Code::Label(self.label.clone());
+        let instructions = &self.instrs;
+        tokens.extend(quote!(<Vec<bril::types::Code>>::from([
+            #label,
+            #(bril::types::Code::Instruction(#instructions),)*
+        ])));
+    }
+}
+
+impl ToTokens for Code {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        tokens.extend(match self {
+            Code::Label(label) => quote!(bril::types::Code::Label {
+                label: #label.to_string()
+            }),
+            Code::Instruction(instruction) => quote!(bril::types::Code::Instruction(#instruction)),
+        });
+    }
+}
+
+/// Parses the body of a function or a block: entries
+/// separated by `;` between square brackets
+fn parse_body(input: ParseStream) -> syn::Result<Vec<Code>> {
+    let content;
+    bracketed!(content in input);
+
+    let body = Punctuated::<Code, Token![;]>::parse_terminated(&content)?;
+    Ok(body.into_iter().collect())
+}
+
+/// Wrapper around a bril Function. Used for parsing.
+struct Function {
+    name: String,
+    name_span: Span,
+    args: Vec<(String, bril::types::Type)>,
+    ty: Option<bril::types::Type>,
+    instrs: Vec<Code>,
+    extra: bril::types::Extra,
+}
+
+impl Parse for Function {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut name = None;
+        let mut name_span = input.span();
+        let mut args = None;
+        let mut ty = None;
+        let mut instrs = None;
+
+        while !input.is_empty() {
+            if input.peek(kw::name) {
+                if name.is_some() {
+                    return Err(error!(input.span(), "name already set"));
+                }
+                let _ = input.parse::<kw::name>()?;
+                let _ = input.parse::<Token![=]>()?;
+                name_span = input.span();
+                name = Some(parse_name(input)?);
+            } else if input.peek(kw::args) {
+                if args.is_some() {
+                    return Err(error!(input.span(), "args already set"));
+                }
+                let _ = input.parse::<kw::args>()?;
+                let _ = input.parse::<Token![=]>()?;
+
+                let content;
+                bracketed!(content in input);
+                let typed = Punctuated::<FunctionArg, Token![,]>::parse_terminated(&content)?;
+                args = Some(typed.into_iter().map(|a| (a.0, a.1)).collect());
+            } else if input.peek(kw::ty) {
+                if ty.is_some() {
+                    return Err(error!(input.span(), "type already set"));
+                }
+                ty = Some(input.parse::<Type>()?.0);
+            } else if input.peek(kw::instrs) {
+                if instrs.is_some() {
+                    return Err(error!(input.span(), "instrs already set"));
+                }
+                let _ = input.parse::<kw::instrs>()?;
+                let _ = input.parse::<Token![=]>()?;
+                instrs = Some(parse_body(input)?);
+            } else {
+                return Err(error!(
+                    input.span(),
+                    format!("unexpected attribute {input}")
+                ));
+            }
+
+            let _ = input.parse::<Token![,]>();
+        }
+
+        let Some(name) = name else {
+            return Err(error!(input.span(), "'name' attribute needs to be set"));
+        };
+        let Some(instrs) = instrs else {
+            return Err(error!(input.span(), "'instrs' attribute needs to be set"));
+        };
+
+        Ok(Self {
+            name,
+            name_span,
+            args: args.unwrap_or_default(),
+            ty,
+            instrs,
+            extra: Default::default(),
+        })
+    }
+}
+
+impl ToTokens for Function {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        let name = &self.name;
+        let args = self.args.iter().map(|(name, ty)| {
+            let ty = type_tokens(ty);
+            quote!(bril::types::FunctionArg {
+                name: #name.into(),
+                r#type: #ty
+            })
+        });
+        let ty = self
+            .ty
+            .as_ref()
+            .map(|t| {
+                let t = type_tokens(t);
+                quote!(Some(#t))
+            })
+            .unwrap_or_else(|| quote!(None));
+        let instrs = &self.instrs;
+        let extra = json_tokens(&self.extra).unwrap_or_else(|| quote!(Default::default()));
+
+        tokens.extend(quote!(
+            bril::types::Function {
+                name: #name.to_string(),
+                args: vec![#(#args,)*],
+                r#type: #ty,
+                instrs: vec![#(#instrs,)*],
+                extra: #extra
+            }
+        ));
+    }
+}
+
+impl From<bril::types::Function> for Function {
+    fn from(function: bril::types::Function) -> Self {
+        Self {
+            name: function.name,
+            name_span: Span::call_site(),
+            args: function
+                .args
+                .into_iter()
+                .map(|a| (a.name.to_string(), a.r#type))
+                .collect(),
+            ty: function.r#type,
+            instrs: function
+                .instrs
+                .into_iter()
+                .map(|code| match code {
+                    bril::types::Code::Label { label } => Code::Label(label),
+                    bril::types::Code::Instruction(inner) => {
+                        Code::Instruction(Box::new(Instruction {
+                            inner,
+                            ..Default::default()
+                        }))
+                    }
+                })
+                .collect(),
+            extra: function.extra,
+        }
+    }
+}
+
+/// An argument of a function, as `name: type`
+struct FunctionArg(String, bril::types::Type);
+
+impl Parse for FunctionArg {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name = parse_name(input)?;
+        let _ = input.parse::<Token![:]>()?;
+        Ok(Self(name, parse_type(input)?))
+    }
+}
+
+/// Wrapper around a bril program. Used for parsing.
+struct Program(Vec<Function>);
+
+impl Parse for Program {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let functions = Punctuated::<Function, Token![,]>::parse_terminated_with(input, |input| {
+            let content;
+            braced!(content in input);
+            content.parse::<Function>()
+        })?;
+        let functions = functions.into_iter().collect::<Vec<_>>();
+
+        let mut names = HashSet::new();
+        for function in &functions {
+            if !names.insert(function.name.as_str()) {
+                return Err(error!(
+                    function.name_span,
+                    format!("function {} is defined twice", function.name)
+                ));
+            }
+        }
+        for function in &functions {
+            let called = function.instrs.iter().filter_map(|code| match code {
+                Code::Instruction(i) => i.inner.funcs.as_ref(),
+                Code::Label(_) => None,
+            });
+            if let Some(missing) = called.flatten().find(|f| !names.contains(f.as_str())) {
+                return Err(error!(
+                    function.name_span,
+                    format!(
+                        "function {} calls unknown function {missing}",
+                        function.name
+                    )
+                ));
+            }
+        }
+
+        Ok(Self(functions))
+    }
+}
+
+impl ToTokens for Program {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        let functions = &self.0;
+        tokens.extend(quote!(bril::types::BrilProgram::new(vec![#(#functions,)*])));
     }
 }
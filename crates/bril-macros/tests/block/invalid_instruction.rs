@@ -0,0 +1,9 @@
+use bril_macros::block;
+
+fn main() {
+    let _ = block![
+        op = const, value = 1, dest = a;
+        op = print, args = [a], dest = b;
+        op = print, args = [a];
+    ];
+}
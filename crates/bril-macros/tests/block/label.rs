@@ -0,0 +1,5 @@
+use bril_macros::block;
+
+fn main() {
+    let _ = block![op = const, value = 1, dest = a; label = end];
+}
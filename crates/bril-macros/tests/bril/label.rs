@@ -0,0 +1,5 @@
+use bril_macros::bril;
+
+fn main() {
+    let _ = bril! { a: int = const 1; .end: print a; };
+}
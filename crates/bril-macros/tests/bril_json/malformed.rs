@@ -0,0 +1,5 @@
+use bril_macros::bril_json;
+
+fn main() {
+    let _ = bril_json!(r#"{ "functions": [{ "name": "main" }] }"#);
+}
@@ -0,0 +1,5 @@
+use bril_macros::include_bril;
+
+fn main() {
+    let _ = include_bril!("tests/fixtures/missing.bril");
+}
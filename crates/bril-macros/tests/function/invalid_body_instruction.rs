@@ -0,0 +1,11 @@
+#![no_main]
+
+use bril_macros::function;
+
+function!(
+    name = main,
+    instrs = [
+        op = const, value = 1, dest = a;
+        op = add, args = [a], dest = b;
+    ]
+);
@@ -0,0 +1,5 @@
+#![no_main]
+
+use bril_macros::function;
+
+function!(instrs = [op = jmp, labels = [end]; label = end]);
@@ -0,0 +1,5 @@
+use bril_macros::instruction;
+
+fn main() {
+    let _ = instruction!(op = call @helper(a), args = [b]);
+}
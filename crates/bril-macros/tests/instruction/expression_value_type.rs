@@ -0,0 +1,7 @@
+use bril_macros::instruction;
+
+const N: i64 = 1;
+
+fn main() {
+    let _ = instruction!(op = const, value = N + 1, dest = a, ty = bool);
+}
@@ -0,0 +1,5 @@
+#![no_main]
+
+use bril_macros::instruction;
+
+instruction!(op = call, funcs = [fib, other], args = [n], dest = r);
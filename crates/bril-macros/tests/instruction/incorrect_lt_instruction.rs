@@ -0,0 +1,5 @@
+#![no_main]
+
+use bril_macros::instruction;
+
+instruction!(op = lt, args = [a, b], dest = lower, ty = int);
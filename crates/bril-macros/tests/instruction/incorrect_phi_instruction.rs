@@ -0,0 +1,5 @@
+use bril_macros::instruction;
+
+fn main() {
+    let _ = instruction!(op = phi, args = [a, b], labels = [then], dest = x, ty = int);
+}
@@ -0,0 +1,5 @@
+#![no_main]
+
+use bril_macros::instruction;
+
+instruction!(op = const, value = 9223372036854775808, dest = a);
@@ -0,0 +1,5 @@
+use bril_macros::instructions;
+
+fn main() {
+    let _ = instructions! { a = const 1; b = not a a; };
+}
@@ -0,0 +1,5 @@
+use bril_macros::labeled_block;
+
+fn main() {
+    let _ = labeled_block![label = body; op = const, value = 1, dest = a; op = print, args = [a]];
+}
@@ -1,5 +1,642 @@
+use bril::pass::{Pass, PassRegistry};
+use bril::text::parse_program;
+use bril::types::{Block, Code, Function, Instruction, Literal, Operation, Type};
+use bril_macros::{
+    block, bril, bril_json, function, include_bril, include_bril_json, instruction,
+    instruction_unchecked, instructions, label, labeled_block, program, Pass,
+};
+
 #[test]
 fn test_macro_compilation() {
     let cases = trybuild::TestCases::new();
     cases.compile_fail("tests/instruction/*.rs");
+    cases.compile_fail("tests/function/*.rs");
+    cases.compile_fail("tests/program/*.rs");
+    cases.compile_fail("tests/block/*.rs");
+    cases.compile_fail("tests/bril/*.rs");
+    cases.compile_fail("tests/pass/*.rs");
+    cases.compile_fail("tests/instructions/*.rs");
+    cases.compile_fail("tests/labeled_block/*.rs");
+    cases.compile_fail("tests/bril_json/*.rs");
+}
+
+#[test]
+fn test_function_macro() {
+    // Given
+    let expected = parse_program(
+        r#"
+        @count(n: int): int {
+          zero: int = const 0;
+          one: int = const 1;
+        .loop:
+          done: bool = eq n zero;
+          br done .else .body;
+        .body:
+          n: int = sub n one;
+          jmp .loop;
+        .else:
+          r: int = call @count zero;
+          ret n;
+        }
+        "#,
+    )
+    .unwrap()
+    .functions
+    .remove(0);
+
+    // When
+    let function = function!(
+        name = count,
+        args = [n: int],
+        ty = int,
+        instrs = [
+            op = const, value = 0, dest = zero, ty = int;
+            op = const, value = 1, dest = one, ty = int;
+            label = loop;
+            op = eq, args = [n, zero], dest = done, ty = bool;
+            op = br, args = [done], labels = [else, body];
+            label = body;
+            op = sub, args = [n, one], dest = n, ty = int;
+            op = jmp, labels = [loop];
+            label = else;
+            op = call, funcs = [count], args = [zero], dest = r, ty = int;
+            op = ret, args = [n];
+        ]
+    );
+
+    // Then
+    assert_eq!(function, expected);
+}
+
+#[test]
+fn test_program_macro() {
+    // Given
+    let expected = parse_program(
+        r#"
+        @main {
+          a: int = const 2;
+          b: int = call @double a;
+          print b;
+        }
+        @double(x: int): int {
+          r: int = add x x;
+          ret r;
+        }
+        "#,
+    )
+    .unwrap();
+
+    // When
+    let program = program![
+        {
+            name = main,
+            instrs = [
+                op = const, value = 2, dest = a, ty = int;
+                op = call, funcs = [double], args = [a], dest = b, ty = int;
+                op = print, args = [b];
+            ]
+        },
+        {
+            name = double,
+            args = [x: int],
+            ty = int,
+            instrs = [
+                op = add, args = [x, x], dest = r, ty = int;
+                op = ret, args = [r];
+            ]
+        },
+    ];
+
+    // Then
+    assert_eq!(program, expected);
+    assert!(program.resolve_calls().is_ok());
+}
+
+#[test]
+fn test_block_macro() {
+    // Given
+    let expected = vec![
+        instruction!(op = const, value = 1, dest = a),
+        instruction!(op = add, args = [a, a], dest = b),
+        instruction!(op = print, args = [b]),
+    ];
+
+    // When
+    let block = block![
+        op = const, value = 1, dest = a;
+        op = add, args = [a, a], dest = b;
+        op = print, args = [b];
+    ];
+
+    // Then
+    assert_eq!(block, expected);
+    assert_eq!(block![], Block::new());
+}
+
+#[test]
+fn test_label_macro() {
+    // Given
+    let expected = function!(
+        name = main,
+        instrs = [
+            label = loop;
+            op = jmp, labels = [loop];
+        ]
+    );
+
+    // When
+    let function = Function {
+        name: "main".to_string(),
+        instrs: vec![
+            label!(loop),
+            Code::Instruction(instruction!(op = jmp, labels = [loop])),
+        ],
+        ..Default::default()
+    };
+
+    // Then
+    assert_eq!(function, expected);
+    assert_eq!(
+        label!(end),
+        Code::Label {
+            label: "end".to_string()
+        }
+    );
+}
+
+#[test]
+fn test_call_instruction_macro() {
+    // Given
+    let expected = Instruction {
+        op: Operation::Call,
+        args: vec!["n".into()],
+        dest: Some("r".into()),
+        funcs: Some(vec!["fib".to_string()]),
+        ..Default::default()
+    };
+
+    // When
+    let call = instruction!(op = call, funcs = [fib], args = [n], dest = r);
+
+    // Then
+    assert_eq!(call, expected);
+}
+
+#[test]
+fn test_float_value_macro() {
+    // Given
+    let expected = Instruction {
+        op: Operation::Const,
+        value: Some(Literal::Float(1.5)),
+        dest: Some("f".into()),
+        r#type: Some(Type::Float),
+        ..Default::default()
+    };
+
+    // When
+    let constant = instruction!(op = const, value = 1.5, dest = f, ty = float);
+
+    // Then
+    assert_eq!(constant, expected);
+}
+
+#[test]
+fn test_bool_value_macro() {
+    // Given
+    let expected = Instruction {
+        op: Operation::Const,
+        value: Some(Literal::Bool(true)),
+        dest: Some("cond".into()),
+        r#type: Some(Type::Bool),
+        ..Default::default()
+    };
+
+    // When
+    let constant = instruction!(op = const, value = true, dest = cond, ty = bool);
+
+    // Then
+    assert_eq!(constant, expected);
+    assert_eq!(
+        instruction!(op = const, value = false, dest = cond).value,
+        Some(Literal::Bool(false))
+    );
+}
+
+#[test]
+fn test_negative_value_macro() {
+    // When
+    let int = instruction!(op = const, value = -3, dest = a);
+    let float = instruction!(op = const, value = -0.5, dest = f, ty = float);
+    let min = instruction!(op = const, value = -9223372036854775808, dest = m);
+
+    // Then
+    assert_eq!(int.value, Some(Literal::Int(-3)));
+    assert_eq!(float.value, Some(Literal::Float(-0.5)));
+    assert_eq!(min.value, Some(Literal::Int(i64::MIN)));
+}
+
+#[test]
+fn test_spliced_values_macro() {
+    // Given
+    let n = 7;
+    let cond = true;
+    let var = |i: usize| format!("v{i}");
+
+    // When
+    let constant = instruction!(op = const, value = #n, dest = #(var(0)));
+    let boolean = instruction!(op = const, value = #cond, dest = c, ty = bool);
+    let add = instruction!(op = add, args = [#(var(0)), b], dest = #(var(1)));
+
+    // Then
+    assert_eq!(constant, instruction!(op = const, value = 7, dest = v0));
+    assert_eq!(boolean.value, Some(Literal::Bool(true)));
+    assert_eq!(add, instruction!(op = add, args = [v0, b], dest = v1));
+}
+
+#[test]
+fn test_pointer_type_macro() {
+    // Given
+    let ptr = |ty| Type::Ptr(Box::new(ty));
+
+    // When
+    let alloc = instruction!(op = alloc, args = [n], dest = p, ty = ptr<int>);
+    let load = instruction!(op = load, args = [pp], dest = q, ty = ptr<ptr<float>>);
+    let function = function!(
+        name = f,
+        args = [p: ptr<bool>],
+        instrs = [op = free, args = [p]]
+    );
+
+    // Then
+    assert_eq!(alloc.r#type, Some(ptr(Type::Int)));
+    assert_eq!(load.r#type, Some(ptr(ptr(Type::Float))));
+    assert_eq!(function.args[0].r#type, ptr(Type::Bool));
+}
+
+#[test]
+fn test_string_variable_names_macro() {
+    // Given
+    let expected = Instruction {
+        op: Operation::Add,
+        args: vec!["a.0".into(), "b".into()],
+        dest: Some("v.1".into()),
+        ..Default::default()
+    };
+
+    // When
+    let add = instruction!(op = add, args = ["a.0", b], dest = "v.1");
+
+    // Then
+    assert_eq!(add, expected);
+}
+
+#[test]
+fn test_bril_macro() {
+    // Given
+    let source = r#"
+        @main(n: int) {
+          v.0: int = const -1;
+          c: char = const 'a';
+          p: ptr<int> = alloc n;
+        .loop.1:
+          b: bool = lt v.0 n;
+          br b .loop.1 .end;
+        .end:
+          free p;
+        }
+        @f: float {
+          x: float = const 1.5;
+          ret x;
+        }
+    "#;
+    let expected = parse_program(source).unwrap();
+
+    // When
+    let block: Block = bril! { a: int = const 1; b: int = add a a; print b; };
+    let function: Function = bril! {
+        @f: float {
+          x: float = const 1.5;
+          ret x;
+        }
+    };
+    let program = bril! {
+        @main(n: int) {
+          v.0: int = const -1;
+          c: char = const 'a';
+          p: ptr<int> = alloc n;
+        .loop.1:
+          b: bool = lt v.0 n;
+          br b .loop.1 .end;
+        .end:
+          free p;
+        }
+        @f: float {
+          x: float = const 1.5;
+          ret x;
+        }
+    };
+
+    // Then
+    assert_eq!(
+        block,
+        block![
+            op = const, value = 1, dest = a, ty = int;
+            op = add, args = [a, a], dest = b, ty = int;
+            op = print, args = [b]
+        ]
+    );
+    assert_eq!(function, expected.functions[1]);
+    assert_eq!(program, expected);
+}
+
+/// Removes the prints of a block
+fn remove_prints(mut block: Block) -> Block {
+    block.retain(|i| i.op != Operation::Print);
+    block
+}
+
+/// Removes the labels of a function
+fn remove_labels(function: &mut Function) -> eyre::Result<()> {
+    function
+        .instrs
+        .retain(|c| matches!(c, Code::Instruction(_)));
+    Ok(())
+}
+
+#[derive(Pass)]
+#[pass(block = remove_prints)]
+struct RemovePrints;
+
+#[derive(Pass)]
+#[pass(name = "labels", function = remove_labels)]
+struct RemoveLabels;
+
+#[test]
+fn test_pass_derive() {
+    // Given
+    let mut function: Function = bril! {
+        @main {
+          a: int = const 1;
+          print a;
+        .end:
+          print a;
+        }
+    };
+
+    // When
+    RemovePrints.run_on_function(&mut function).unwrap();
+    RemoveLabels.run_on_function(&mut function).unwrap();
+
+    // Then
+    assert_eq!(RemovePrints.name(), "remove_prints");
+    assert_eq!(RemoveLabels.name(), "labels");
+    assert_eq!(function, bril! { @main { a: int = const 1; } });
+}
+
+/// Generic passes can't be built by the registry, so they aren't registered
+#[derive(Pass)]
+#[pass(name = "generic", block = remove_prints)]
+struct Generic<T: Sync>(std::marker::PhantomData<T>);
+
+#[test]
+fn test_pass_derive_registry() {
+    // Given
+    let registry = PassRegistry::with_builtin().unwrap();
+    let mut function: Function = bril! { @main { a: int = const 1; print a; } };
+
+    // When
+    registry
+        .get("remove_prints")
+        .unwrap()
+        .run_on_function(&mut function)
+        .unwrap();
+
+    // Then
+    assert_eq!(
+        registry.names().collect::<Vec<_>>(),
+        ["labels", "remove_prints"]
+    );
+    assert_eq!(function, bril! { @main { a: int = const 1; } });
+    assert_eq!(Generic::<()>(std::marker::PhantomData).name(), "generic");
+}
+
+#[test]
+fn test_instructions_macro() {
+    // Given
+    let n = 4;
+    let expected = block![
+        op = const, value = 1, dest = a;
+        op = const, value = -2.5, dest = f, ty = float;
+        op = const, value = #n, dest = b, ty = int;
+        op = add, args = [a, b], dest = "v.1";
+        op = call, args = ["v.1"], funcs = [square], dest = s, ty = int;
+        op = br, args = [c], labels = [then, else];
+        op = id, args = [#(format!("v{n}"))], dest = t;
+        op = print, args = [s];
+        op = ret
+    ];
+
+    // When
+    let block = instructions! {
+        a = const 1;
+        f: float = const -2.5;
+        b: int = const #n;
+        "v.1" = add a b;
+        s: int = call @square "v.1";
+        br c .then .else;
+        t = id #(format!("v{n}"));
+        print s;
+        ret;
+    };
+
+    // Then
+    assert_eq!(block, expected);
+}
+
+#[test]
+fn test_instruction_unchecked_macro() {
+    // Given
+    let expected = Instruction {
+        op: Operation::Add,
+        args: vec!["a".into()],
+        value: Some(Literal::Bool(true)),
+        ..Default::default()
+    };
+
+    // When
+    let add = instruction_unchecked!(op = add, args = [a], value = true);
+
+    // Then
+    assert_eq!(add, expected);
+    assert!(!add.is_valid());
+}
+
+#[test]
+fn test_call_shorthand_macro() {
+    // Given
+    let expected = instruction!(
+        op = call,
+        funcs = [helper],
+        args = [a, b],
+        dest = r,
+        ty = int
+    );
+    let arg = "b";
+
+    // When
+    let call = instruction!(op = call @helper(a, #arg), dest = r, ty = int);
+    let no_args = instruction!(op = call @init());
+
+    // Then
+    assert_eq!(call, expected);
+    assert_eq!(no_args, instruction!(op = call, funcs = [init]));
+}
+
+#[test]
+fn test_labeled_block_macro() {
+    // Given
+    let expected = function!(
+        name = main,
+        instrs = [
+            label = entry;
+            op = const, value = 1, dest = a;
+            op = jmp, labels = [end];
+            label = end;
+            op = print, args = [a];
+            op = ret
+        ]
+    );
+
+    // When
+    let instrs = [
+        labeled_block![label = entry; op = const, value = 1, dest = a; op = jmp, labels = [end]],
+        labeled_block![label = end; op = print, args = [a]; op = ret],
+    ]
+    .concat();
+
+    // Then
+    assert_eq!(instrs, expected.instrs);
+}
+
+#[test]
+fn test_keyword_variable_names_macro() {
+    // Given
+    let expected = Instruction {
+        op: Operation::Add,
+        args: vec!["type".into(), "match".into()],
+        dest: Some("const".into()),
+        ..Default::default()
+    };
+
+    // When
+    let add = instruction!(op = add, args = [type, r#match], dest = const);
+    let statements = instructions! { r#const = add type match; };
+    let function = function!(name = f, args = [type: int], instrs = [op = print, args = [r#type]]);
+
+    // Then
+    assert_eq!(add, expected);
+    assert_eq!(statements, [expected]);
+    assert_eq!(function.args[0].name, "type");
+}
+
+#[test]
+fn test_phi_macro() {
+    // Given
+    let expected = Instruction {
+        op: Operation::Phi,
+        args: vec!["a".into(), "b".into()],
+        labels: Some(vec!["then".into(), "else".into()]),
+        dest: Some("x".into()),
+        r#type: Some(Type::Int),
+        ..Default::default()
+    };
+
+    // When
+    let phi = instruction!(op = phi, args = [a, b], labels = [then, else], dest = x, ty = int);
+
+    // Then
+    assert_eq!(phi, expected);
+}
+
+#[test]
+fn test_bril_json_macro() {
+    // Given
+    let expected =
+        bril::migrate::from_str(include_str!("../../bril/tests/fixtures/pos.json")).unwrap();
+
+    // When
+    let fixture = bril_json!(file = "../bril/tests/fixtures/pos.json");
+    let inline = bril_json!(
+        r#"{
+            "functions": [{
+                "name": "main",
+                "instrs": [
+                    { "op": "const", "dest": "a", "type": "int", "value": 1 },
+                    { "op": "print", "args": ["a"] }
+                ]
+            }]
+        }"#
+    );
+
+    // Then
+    assert_eq!(fixture, expected);
+    assert_eq!(
+        inline.functions,
+        [bril! { @main { a: int = const 1; print a; } }]
+    );
+}
+
+#[test]
+fn test_include_bril_macros() {
+    // Given
+    let text = parse_program(include_str!("fixtures/fib.bril")).unwrap();
+    let json =
+        bril::migrate::from_str(include_str!("../../bril/tests/fixtures/call.json")).unwrap();
+
+    // When
+    let included = include_bril!("tests/fixtures/fib.bril");
+    let included_json = include_bril_json!("../bril/tests/fixtures/call.json");
+
+    // Then
+    assert_eq!(included, text);
+    assert_eq!(included_json, json);
+}
+
+const BASE: i64 = 40;
+
+#[test]
+fn test_expression_value_macro() {
+    // Given
+    let expected = instruction!(op = const, value = 42, dest = a, ty = int);
+
+    // When
+    let constant = instruction!(op = const, value = BASE + 2, dest = a, ty = int);
+    let sum = instruction!(op = const, value = 40 + 2, dest = a, ty = int);
+    let statements = instructions! { a: int = const BASE + 2; };
+
+    // Then
+    assert_eq!(constant, expected);
+    assert_eq!(sum, expected);
+    assert_eq!(statements, [expected]);
+}
+
+#[test]
+fn test_extension_operation_macro() {
+    // Given
+    let expected = Instruction {
+        op: Operation::Other("vecadd".to_string()),
+        args: vec!["a".into(), "b".into()],
+        dest: Some("v".into()),
+        ..Default::default()
+    };
+
+    // When
+    let vecadd = instruction!(op = "vecadd", args = [a, b], dest = v);
+    let statements = instructions! { v = "vecadd" a b; };
+    let known = instruction!(op = "print", args = [v]);
+
+    // Then
+    assert_eq!(vecadd, expected);
+    assert_eq!(statements, [expected]);
+    assert_eq!(known, instruction!(op = print, args = [v]));
 }
@@ -0,0 +1,7 @@
+use bril_macros::Pass;
+
+#[derive(Pass)]
+#[pass(name = "noop")]
+struct Noop;
+
+fn main() {}
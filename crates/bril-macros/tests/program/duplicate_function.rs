@@ -0,0 +1,8 @@
+#![no_main]
+
+use bril_macros::program;
+
+program![
+    { name = main, instrs = [op = ret] },
+    { name = main, instrs = [op = ret] },
+];
@@ -0,0 +1,5 @@
+#![no_main]
+
+use bril_macros::program;
+
+program![{ name = main, instrs = [op = call, funcs = [missing]] }];
@@ -0,0 +1,439 @@
+//! Contains a compact binary serialization of programs, much faster to
+//! load than JSON when processing many programs.
+//!
+//! The encoding starts with a header made of the `BRIL` magic bytes and
+//! the version of the format. Integers are LEB128 varints and each string
+//! is written once: later occurrences refer to the first one by index.
+
+use crate::types::{
+    BrilProgram, Code, Extra, Function, FunctionArg, Instruction, Literal, Operation, Position,
+    Type,
+};
+use eyre::eyre;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// The magic bytes starting an encoded program
+const MAGIC: &[u8; 4] = b"BRIL";
+
+/// The version of the binary format, bumped on each incompatible change
+pub const FORMAT_VERSION: u8 = 2;
+
+/// Encodes the program in the binary format
+pub fn encode(program: &BrilProgram) -> Vec<u8> {
+    let mut encoder = Encoder::default();
+    encoder.bytes.extend_from_slice(MAGIC);
+    encoder.bytes.push(FORMAT_VERSION);
+    encoder.program(program);
+    encoder.bytes
+}
+
+/// Decodes a program encoded with [`encode`]
+pub fn decode(bytes: &[u8]) -> eyre::Result<BrilProgram> {
+    let header = bytes
+        .get(..MAGIC.len() + 1)
+        .ok_or_else(|| eyre!("missing binary header"))?;
+    if &header[..MAGIC.len()] != MAGIC {
+        return Err(eyre!("not a binary Bril program"));
+    }
+    let version = header[MAGIC.len()];
+    if version != FORMAT_VERSION {
+        return Err(eyre!(
+            "unsupported binary format version {version}, expected {FORMAT_VERSION}"
+        ));
+    }
+
+    let mut decoder = Decoder {
+        bytes: &bytes[header.len()..],
+        strings: Vec::new(),
+    };
+    let program = decoder.program()?;
+    if !decoder.bytes.is_empty() {
+        return Err(eyre!("{} trailing bytes", decoder.bytes.len()));
+    }
+    Ok(program)
+}
+
+#[derive(Default)]
+struct Encoder<'a> {
+    bytes: Vec<u8>,
+    strings: HashMap<&'a str, u64>,
+    /// The number of strings written, shared or not
+    count: u64,
+}
+
+impl<'a> Encoder<'a> {
+    fn program(&mut self, program: &'a BrilProgram) {
+        self.option(program.version.as_ref(), |e, v| e.varint((*v).into()));
+        self.list(&program.functions, Self::function);
+        self.extra(&program.extra);
+    }
+
+    fn function(&mut self, function: &'a Function) {
+        self.str(&function.name);
+        self.list(&function.args, |e, arg| {
+            e.str(arg.name.as_str());
+            e.ty(&arg.r#type);
+        });
+        self.option(function.r#type.as_ref(), Self::ty);
+        self.list(&function.instrs, |e, code| match code {
+            Code::Label { label } => {
+                e.bytes.push(0);
+                e.str(label);
+            }
+            Code::Instruction(i) => {
+                e.bytes.push(1);
+                e.instruction(i);
+            }
+        });
+        self.extra(&function.extra);
+    }
+
+    fn instruction(&mut self, i: &'a Instruction) {
+        match &i.op {
+            // Only the opcodes of unknown operations are borrowed, the others are formatted
+            Operation::Other(op) => self.str(op),
+            op => self.owned_str(op.to_string()),
+        }
+        self.list(&i.args, |e, arg| e.str(arg.as_str()));
+        self.option(i.r#type.as_ref(), Self::ty);
+        self.option(i.value.as_ref(), |e, value| match *value {
+            Literal::Bool(v) => e.bytes.extend([0, u8::from(v)]),
+            Literal::Int(v) => {
+                e.bytes.push(1);
+                // Zigzag encoding keeps small negative values short
+                e.varint(((v << 1) ^ (v >> 63)) as u64);
+            }
+            Literal::Float(v) => {
+                e.bytes.push(2);
+                e.bytes.extend(v.to_le_bytes());
+            }
+            Literal::Char(v) => {
+                e.bytes.push(3);
+                e.varint(u32::from(v).into());
+            }
+        });
+        self.option(i.dest.as_ref(), |e, dest| e.str(dest.as_str()));
+        self.option(i.funcs.as_ref(), |e, funcs| {
+            e.list(funcs, |e, f| e.str(f));
+        });
+        self.option(i.labels.as_ref(), |e, labels| {
+            e.list(labels, |e, l| e.str(l));
+        });
+        self.option(i.pos.as_ref(), |e, pos| {
+            e.varint(pos.row);
+            e.varint(pos.col);
+        });
+        self.option(i.attrs.as_ref(), Self::extra);
+        self.extra(&i.extra);
+    }
+
+    fn ty(&mut self, ty: &Type) {
+        match ty {
+            Type::Int => self.bytes.push(0),
+            Type::Bool => self.bytes.push(1),
+            Type::Float => self.bytes.push(2),
+            Type::Char => self.bytes.push(3),
+            Type::Ptr(inner) => {
+                self.bytes.push(4);
+                self.ty(inner);
+            }
+        }
+    }
+
+    /// Writes the extension fields or the attributes, their values being
+    /// saved as JSON
+    fn extra(&mut self, extra: &'a Extra) {
+        self.varint(extra.len() as u64);
+        for (key, value) in extra {
+            self.str(key);
+            self.owned_str(value.to_string());
+        }
+    }
+
+    fn option<T>(&mut self, value: Option<&'a T>, mut f: impl FnMut(&mut Self, &'a T)) {
+        match value {
+            None => self.bytes.push(0),
+            Some(value) => {
+                self.bytes.push(1);
+                f(self, value);
+            }
+        }
+    }
+
+    fn list<T>(&mut self, values: &'a [T], mut f: impl FnMut(&mut Self, &'a T)) {
+        self.varint(values.len() as u64);
+        for value in values {
+            f(self, value);
+        }
+    }
+
+    /// Writes the index of the string plus one if it was already written,
+    /// or zero followed by the string
+    fn str(&mut self, s: &'a str) {
+        match self.strings.get(s) {
+            Some(&index) => self.varint(index + 1),
+            None => {
+                self.strings.insert(s, self.count);
+                self.raw_str(s);
+            }
+        }
+    }
+
+    /// Writes a string which doesn't outlive the encoding, so it can't be
+    /// shared. It still takes an index, as the decoder doesn't know.
+    fn owned_str(&mut self, s: String) {
+        self.raw_str(&s);
+    }
+
+    fn raw_str(&mut self, s: &str) {
+        self.count += 1;
+        self.varint(0);
+        self.varint(s.len() as u64);
+        self.bytes.extend_from_slice(s.as_bytes());
+    }
+
+    fn varint(&mut self, mut value: u64) {
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                self.bytes.push(byte);
+                return;
+            }
+            self.bytes.push(byte | 0x80);
+        }
+    }
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    strings: Vec<String>,
+}
+
+impl Decoder<'_> {
+    fn program(&mut self) -> eyre::Result<BrilProgram> {
+        let version = self
+            .option(|d| u32::try_from(d.varint()?).map_err(|_| eyre!("IR version out of range")))?;
+        let mut program = BrilProgram::new(self.list(Self::function)?);
+        program.version = version;
+        program.extra = self.extra()?;
+        Ok(program)
+    }
+
+    fn function(&mut self) -> eyre::Result<Function> {
+        Ok(Function {
+            name: self.str()?,
+            args: self.list(|d| {
+                Ok(FunctionArg {
+                    name: d.str()?.into(),
+                    r#type: d.ty()?,
+                })
+            })?,
+            r#type: self.option(Self::ty)?,
+            instrs: self.list(|d| match d.byte()? {
+                0 => Ok(Code::Label { label: d.str()? }),
+                1 => Ok(Code::Instruction(d.instruction()?)),
+                tag => Err(eyre!("invalid code tag {tag}")),
+            })?,
+            extra: self.extra()?,
+        })
+    }
+
+    fn instruction(&mut self) -> eyre::Result<Instruction> {
+        let op = self.str()?;
+        Ok(Instruction {
+            op: Operation::from_str(&op).unwrap_or(Operation::Other(op)),
+            args: self.list(|d| Ok(d.str()?.into()))?,
+            r#type: self.option(Self::ty)?,
+            value: self.option(|d| match d.byte()? {
+                0 => Ok(Literal::Bool(d.byte()? != 0)),
+                1 => {
+                    let v = d.varint()?;
+                    Ok(Literal::Int((v >> 1) as i64 ^ -((v & 1) as i64)))
+                }
+                2 => {
+                    let bytes = d.take(8)?.try_into().expect("8 bytes were taken");
+                    Ok(Literal::Float(f64::from_le_bytes(bytes)))
+                }
+                3 => u32::try_from(d.varint()?)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .map(Literal::Char)
+                    .ok_or_else(|| eyre!("invalid char literal")),
+                tag => Err(eyre!("invalid literal tag {tag}")),
+            })?,
+            dest: self.option(|d| Ok(d.str()?.into()))?,
+            funcs: self.option(|d| d.list(Self::str))?,
+            labels: self.option(|d| d.list(Self::str))?,
+            pos: self.option(|d| {
+                Ok(Position {
+                    row: d.varint()?,
+                    col: d.varint()?,
+                })
+            })?,
+            attrs: self.option(Self::extra)?,
+            extra: self.extra()?,
+        })
+    }
+
+    fn ty(&mut self) -> eyre::Result<Type> {
+        match self.byte()? {
+            0 => Ok(Type::Int),
+            1 => Ok(Type::Bool),
+            2 => Ok(Type::Float),
+            3 => Ok(Type::Char),
+            4 => Ok(Type::Ptr(Box::new(self.ty()?))),
+            tag => Err(eyre!("invalid type tag {tag}")),
+        }
+    }
+
+    fn extra(&mut self) -> eyre::Result<Extra> {
+        let len = self.varint()?;
+        (0..len)
+            .map(|_| Ok((self.str()?, serde_json::from_str(&self.str()?)?)))
+            .collect()
+    }
+
+    fn option<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> eyre::Result<T>,
+    ) -> eyre::Result<Option<T>> {
+        match self.byte()? {
+            0 => Ok(None),
+            1 => f(self).map(Some),
+            tag => Err(eyre!("invalid option tag {tag}")),
+        }
+    }
+
+    fn list<T>(&mut self, mut f: impl FnMut(&mut Self) -> eyre::Result<T>) -> eyre::Result<Vec<T>> {
+        let len = self.varint()?;
+        // The length isn't trusted for the allocation, each element
+        // taking at least a byte
+        let mut values = Vec::with_capacity((len as usize).min(self.bytes.len()));
+        for _ in 0..len {
+            values.push(f(self)?);
+        }
+        Ok(values)
+    }
+
+    fn str(&mut self) -> eyre::Result<String> {
+        match self.varint()? {
+            0 => {
+                let len = usize::try_from(self.varint()?)?;
+                let s = String::from_utf8(self.take(len)?.to_vec())?;
+                self.strings.push(s.clone());
+                Ok(s)
+            }
+            index => self
+                .strings
+                .get(index as usize - 1)
+                .cloned()
+                .ok_or_else(|| eyre!("invalid string index {index}")),
+        }
+    }
+
+    fn varint(&mut self) -> eyre::Result<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(eyre!("varint overflow"))
+    }
+
+    fn byte(&mut self) -> eyre::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn take(&mut self, len: usize) -> eyre::Result<&[u8]> {
+        if self.bytes.len() < len {
+            return Err(eyre!("unexpected end of input"));
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, encode};
+    use crate::generate::{generate, Config};
+    use crate::text::parse_program;
+    use crate::types::{Code, Instruction, Operation};
+
+    #[test]
+    fn test_round_trip() {
+        // Given
+        let mut program = parse_program(
+            r#"
+            @main(p: ptr<float>) {
+              a: int = const -42;
+              b: bool = const true;
+              c: char = const 'é';
+              f: float = const 0.5;
+              r: int = call @helper a;
+              br b .left .right;
+            .left:
+              print a;
+            .right:
+              ret;
+            }
+            @helper(x: int): int {
+              ret x;
+            }
+            "#,
+        )
+        .unwrap();
+        program.functions[1].instrs.insert(
+            0,
+            Code::Instruction(Instruction {
+                op: Operation::Other("custom".into()),
+                args: vec!["x".into()],
+                ..Default::default()
+            }),
+        );
+        if let Code::Instruction(i) = &mut program.functions[0].instrs[0] {
+            i.set_attr("live", serde_json::json!(["a"]));
+        }
+        program.version = Some(1);
+        program.extra.insert("source".into(), "test".into());
+
+        // When
+        let decoded = decode(&encode(&program)).unwrap();
+
+        // Then
+        assert_eq!(decoded, program);
+        assert_eq!(decoded.to_string(), program.to_string());
+    }
+
+    #[test]
+    fn test_round_trip_generated() {
+        // Given
+        let program = generate(&Config::default());
+
+        // When
+        let encoded = encode(&program);
+
+        // Then
+        assert_eq!(decode(&encoded).unwrap(), program);
+        assert!(encoded.len() < serde_json::to_vec(&program).unwrap().len() / 2);
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        // Given
+        let encoded = encode(&parse_program("@main { ret; }").unwrap());
+        let mut newer = encoded.clone();
+        newer[4] += 1;
+
+        // When / Then
+        assert!(decode(b"JSON").is_err());
+        assert!(decode(&newer).is_err());
+        assert!(decode(&encoded[..encoded.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,358 @@
+//! Contains helpers to build instructions and functions programmatically.
+
+use crate::types::{Code, Function, FunctionArg, Instruction, Literal, Operation, Type, Var};
+use eyre::eyre;
+
+impl From<bool> for Literal {
+    fn from(value: bool) -> Self {
+        Literal::Bool(value)
+    }
+}
+
+impl From<i64> for Literal {
+    fn from(value: i64) -> Self {
+        Literal::Int(value)
+    }
+}
+
+impl From<f64> for Literal {
+    fn from(value: f64) -> Self {
+        Literal::Float(value)
+    }
+}
+
+impl From<char> for Literal {
+    fn from(value: char) -> Self {
+        Literal::Char(value)
+    }
+}
+
+/// Shorthand constructors for the most common instructions
+impl Instruction {
+    /// Returns a `dest = const value` instruction
+    pub fn constant(dest: impl Into<Var>, value: impl Into<Literal>) -> Self {
+        Self {
+            op: Operation::Const,
+            value: Some(value.into()),
+            dest: Some(dest.into()),
+            ..Default::default()
+        }
+    }
+
+    /// Returns a `dest = op a b` instruction
+    pub fn binary(
+        op: Operation,
+        dest: impl Into<Var>,
+        a: impl Into<Var>,
+        b: impl Into<Var>,
+    ) -> Self {
+        Self {
+            op,
+            args: vec![a.into(), b.into()],
+            dest: Some(dest.into()),
+            ..Default::default()
+        }
+    }
+
+    /// Returns a `dest = add a b` instruction
+    pub fn add(dest: impl Into<Var>, a: impl Into<Var>, b: impl Into<Var>) -> Self {
+        Self::binary(Operation::Add, dest, a, b)
+    }
+
+    /// Returns a `dest = mul a b` instruction
+    pub fn mul(dest: impl Into<Var>, a: impl Into<Var>, b: impl Into<Var>) -> Self {
+        Self::binary(Operation::Mul, dest, a, b)
+    }
+
+    /// Returns a `dest = sub a b` instruction
+    pub fn sub(dest: impl Into<Var>, a: impl Into<Var>, b: impl Into<Var>) -> Self {
+        Self::binary(Operation::Sub, dest, a, b)
+    }
+
+    /// Returns a `dest = div a b` instruction
+    pub fn div(dest: impl Into<Var>, a: impl Into<Var>, b: impl Into<Var>) -> Self {
+        Self::binary(Operation::Div, dest, a, b)
+    }
+
+    /// Returns a `dest = id a` instruction
+    pub fn id(dest: impl Into<Var>, a: impl Into<Var>) -> Self {
+        Self {
+            op: Operation::Id,
+            args: vec![a.into()],
+            dest: Some(dest.into()),
+            ..Default::default()
+        }
+    }
+
+    /// Returns a `print a` instruction
+    pub fn print(a: impl Into<Var>) -> Self {
+        Self {
+            op: Operation::Print,
+            args: vec![a.into()],
+            ..Default::default()
+        }
+    }
+
+    /// Returns a `jmp .label` instruction
+    pub fn jmp(label: impl Into<String>) -> Self {
+        Self {
+            op: Operation::Jmp,
+            labels: Some(vec![label.into()]),
+            ..Default::default()
+        }
+    }
+
+    /// Returns a `br cond .then .else` instruction
+    pub fn br(cond: impl Into<Var>, then: impl Into<String>, r#else: impl Into<String>) -> Self {
+        Self {
+            op: Operation::Br,
+            args: vec![cond.into()],
+            labels: Some(vec![then.into(), r#else.into()]),
+            ..Default::default()
+        }
+    }
+
+    /// Returns a `ret [a]` instruction
+    pub fn ret(a: Option<Var>) -> Self {
+        Self {
+            op: Operation::Ret,
+            args: a.into_iter().collect(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Builder for a single [`Instruction`], validated on [`InstructionBuilder::build`].
+#[derive(Debug, Clone)]
+pub struct InstructionBuilder(Instruction);
+
+impl InstructionBuilder {
+    pub fn new(op: Operation) -> Self {
+        Self(Instruction {
+            op,
+            ..Default::default()
+        })
+    }
+
+    pub fn dest(mut self, dest: impl Into<Var>) -> Self {
+        self.0.dest = Some(dest.into());
+        self
+    }
+
+    pub fn arg(mut self, arg: impl Into<Var>) -> Self {
+        self.0.args.push(arg.into());
+        self
+    }
+
+    pub fn args<S: Into<Var>>(mut self, args: impl IntoIterator<Item = S>) -> Self {
+        self.0.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn ty(mut self, ty: Type) -> Self {
+        self.0.r#type = Some(ty);
+        self
+    }
+
+    pub fn value(mut self, value: impl Into<Literal>) -> Self {
+        self.0.value = Some(value.into());
+        self
+    }
+
+    pub fn func(mut self, func: impl Into<String>) -> Self {
+        self.0.funcs.get_or_insert_with(Vec::new).push(func.into());
+        self
+    }
+
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.0
+            .labels
+            .get_or_insert_with(Vec::new)
+            .push(label.into());
+        self
+    }
+
+    /// Returns the instruction if it is valid
+    pub fn build(self) -> eyre::Result<Instruction> {
+        if !self.0.is_valid() {
+            return Err(eyre!("invalid instruction {:?}", self.0));
+        }
+        Ok(self.0)
+    }
+}
+
+/// Builder for a [`Function`], each instruction is validated on
+/// [`FunctionBuilder::build`].
+#[derive(Debug, Clone)]
+pub struct FunctionBuilder(Function);
+
+impl FunctionBuilder {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self(Function {
+            name: name.into(),
+            ..Default::default()
+        })
+    }
+
+    /// Adds an argument to the function
+    pub fn arg(mut self, name: impl Into<Var>, r#type: Type) -> Self {
+        self.0.args.push(FunctionArg {
+            name: name.into(),
+            r#type,
+        });
+        self
+    }
+
+    /// Sets the type of the value returned by the function
+    pub fn returns(mut self, r#type: Type) -> Self {
+        self.0.r#type = Some(r#type);
+        self
+    }
+
+    /// Adds a label to the body of the function
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.0.instrs.push(Code::Label {
+            label: label.into(),
+        });
+        self
+    }
+
+    /// Adds an instruction to the body of the function
+    pub fn instruction(mut self, instruction: Instruction) -> Self {
+        self.0.instrs.push(Code::Instruction(instruction));
+        self
+    }
+
+    pub fn constant(self, dest: impl Into<Var>, value: impl Into<Literal>) -> Self {
+        self.instruction(Instruction::constant(dest, value))
+    }
+
+    pub fn add(self, dest: impl Into<Var>, a: impl Into<Var>, b: impl Into<Var>) -> Self {
+        self.instruction(Instruction::add(dest, a, b))
+    }
+
+    pub fn mul(self, dest: impl Into<Var>, a: impl Into<Var>, b: impl Into<Var>) -> Self {
+        self.instruction(Instruction::mul(dest, a, b))
+    }
+
+    pub fn sub(self, dest: impl Into<Var>, a: impl Into<Var>, b: impl Into<Var>) -> Self {
+        self.instruction(Instruction::sub(dest, a, b))
+    }
+
+    pub fn div(self, dest: impl Into<Var>, a: impl Into<Var>, b: impl Into<Var>) -> Self {
+        self.instruction(Instruction::div(dest, a, b))
+    }
+
+    pub fn id(self, dest: impl Into<Var>, a: impl Into<Var>) -> Self {
+        self.instruction(Instruction::id(dest, a))
+    }
+
+    pub fn print(self, a: impl Into<Var>) -> Self {
+        self.instruction(Instruction::print(a))
+    }
+
+    pub fn jmp(self, label: impl Into<String>) -> Self {
+        self.instruction(Instruction::jmp(label))
+    }
+
+    pub fn br(
+        self,
+        cond: impl Into<Var>,
+        then: impl Into<String>,
+        r#else: impl Into<String>,
+    ) -> Self {
+        self.instruction(Instruction::br(cond, then, r#else))
+    }
+
+    pub fn ret(self, a: Option<Var>) -> Self {
+        self.instruction(Instruction::ret(a))
+    }
+
+    /// Returns the function if all of its instructions are valid
+    pub fn build(self) -> eyre::Result<Function> {
+        for (index, code) in self.0.instrs.iter().enumerate() {
+            if let Code::Instruction(instruction) = code {
+                if !instruction.is_valid() {
+                    return Err(eyre!(
+                        "invalid instruction {index} in function {}: {instruction:?}",
+                        self.0.name
+                    ));
+                }
+            }
+        }
+        Ok(self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FunctionBuilder, InstructionBuilder};
+    use crate::types::{Code, Instruction, Literal, Operation, Type};
+
+    #[test]
+    fn test_instruction_builder() {
+        let call = InstructionBuilder::new(Operation::Call)
+            .dest("r")
+            .ty(Type::Int)
+            .func("double")
+            .arg("a")
+            .build()
+            .unwrap();
+
+        assert_eq!(call.funcs, Some(vec!["double".to_string()]));
+        assert_eq!(call.args, vec!["a"]);
+
+        let invalid = InstructionBuilder::new(Operation::Add)
+            .dest("sum")
+            .arg("a")
+            .build();
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn test_function_builder() {
+        let function = FunctionBuilder::new("main")
+            .arg("n", Type::Int)
+            .constant("a", 1)
+            .add("s", "a", "n")
+            .br("s", "then", "end")
+            .label("then")
+            .print("s")
+            .label("end")
+            .ret(None)
+            .build()
+            .unwrap();
+
+        assert_eq!(function.args.len(), 1);
+        assert_eq!(function.instrs.len(), 7);
+        assert_eq!(
+            function.instrs[0],
+            Code::Instruction(Instruction::constant("a", 1))
+        );
+        assert_eq!(
+            function.instrs[1],
+            Code::Instruction(Instruction::add("s", "a", "n"))
+        );
+        assert_eq!(
+            function.instrs[3],
+            Code::Label {
+                label: "then".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_function_builder_invalid_instruction() {
+        let function = FunctionBuilder::new("main")
+            .instruction(Instruction {
+                op: Operation::Const,
+                value: Some(Literal::Bool(true)),
+                r#type: Some(Type::Int),
+                dest: Some("a".into()),
+                ..Default::default()
+            })
+            .build();
+
+        assert!(function.is_err());
+    }
+}
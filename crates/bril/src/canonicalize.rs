@@ -0,0 +1,157 @@
+//! Contains the canonicalization of programs, which makes two programs
+//! differing only in inconsequential details print identically.
+
+use crate::types::{BrilProgram, Code, Function, Literal, Operation, Type, Var};
+use crate::verify::signature;
+use std::collections::{HashMap, HashSet};
+
+/// Returns the canonical form of the program:
+///     - `main` comes first, followed by the other functions sorted by name
+///     - empty `funcs` and `labels` lists are removed
+///     - the `type` of an instruction is removed when it can be inferred
+///       from the operation or from the literal of a constant
+///     - the temporaries, named `base.N`, are renumbered in their order
+///       of appearance for each base
+pub fn canonicalize(mut program: BrilProgram) -> BrilProgram {
+    program
+        .functions
+        .sort_by(|a, b| (a.name != "main", &a.name).cmp(&(b.name != "main", &b.name)));
+
+    for function in &mut program.functions {
+        canonicalize_function(function);
+    }
+
+    program
+}
+
+fn canonicalize_function(function: &mut Function) {
+    for code in &mut function.instrs {
+        let Code::Instruction(i) = code else {
+            continue;
+        };
+
+        if i.funcs.as_ref().is_some_and(Vec::is_empty) {
+            i.funcs = None;
+        }
+        if i.labels.as_ref().is_some_and(Vec::is_empty) {
+            i.labels = None;
+        }
+
+        let inferred = match (&i.op, i.value) {
+            (Operation::Const, Some(value)) => Some(literal_type(value)),
+            (op, _) => signature(op).and_then(|(_, result)| result),
+        };
+        if inferred.is_some() && inferred == i.r#type {
+            i.r#type = None;
+        }
+    }
+
+    renumber_temporaries(function);
+}
+
+/// Returns the type a literal has when the constant isn't annotated
+fn literal_type(literal: Literal) -> Type {
+    match literal {
+        Literal::Bool(_) => Type::Bool,
+        Literal::Int(_) => Type::Int,
+        Literal::Float(_) => Type::Float,
+        Literal::Char(_) => Type::Char,
+    }
+}
+
+/// Returns the base of a `base.N` temporary
+fn temporary_base(var: &Var) -> Option<&'static str> {
+    let (base, n) = var.as_str().rsplit_once('.')?;
+    (!base.is_empty() && !n.is_empty() && n.chars().all(|c| c.is_ascii_digit())).then_some(base)
+}
+
+/// Renumbers the temporaries from zero for each base. As all the
+/// `base.N` names are renumbered, the new names can't clash.
+fn renumber_temporaries(function: &mut Function) {
+    let mut seen = HashSet::new();
+    let mut order = Vec::new();
+    let vars = function.args.iter().map(|a| a.name).chain(
+        function
+            .instrs
+            .iter()
+            .filter_map(|c| match c {
+                Code::Instruction(i) => Some(i),
+                Code::Label { .. } => None,
+            })
+            .flat_map(|i| i.uses().chain(i.defs()).copied().collect::<Vec<_>>()),
+    );
+    for var in vars {
+        if temporary_base(&var).is_some() && seen.insert(var) {
+            order.push(var);
+        }
+    }
+
+    let mut counters = HashMap::<&str, usize>::new();
+    let renames = order
+        .into_iter()
+        .map(|var| {
+            let base = temporary_base(&var).expect("only temporaries are renumbered");
+            let counter = counters.entry(base).or_default();
+            *counter += 1;
+            (var, Var::from(format!("{base}.{}", *counter - 1)))
+        })
+        .collect::<HashMap<_, _>>();
+
+    crate::rename::rename_vars(function, &renames);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::canonicalize;
+    use crate::text::parse_program;
+
+    #[test]
+    fn test_canonicalize() {
+        // Given
+        let program = parse_program(
+            r#"
+            @helper {
+              ret;
+            }
+            @main(n: int) {
+              lvn.7: int = const 1;
+              f: float = const 1;
+              t: bool = const true;
+              lvn.3: int = add lvn.7 n;
+              v.1: bool = lt lvn.3 n;
+              x: int = id lvn.3;
+              print lvn.7 v.1 x;
+            }
+            @aux {
+              ret;
+            }
+            "#,
+        )
+        .unwrap();
+
+        // When
+        let canonical = canonicalize(program);
+
+        // Then
+        assert_eq!(
+            canonical.to_string(),
+            r#"@main(n: int) {
+  lvn.0 = const 1;
+  f = const 1.0;
+  t = const true;
+  lvn.1 = add lvn.0 n;
+  v.0 = lt lvn.1 n;
+  x: int = id lvn.1;
+  print lvn.0 v.0 x;
+}
+@aux {
+  ret;
+}
+@helper {
+  ret;
+}
+"#
+        );
+        assert_eq!(canonicalize(canonical.clone()), canonical);
+    }
+}
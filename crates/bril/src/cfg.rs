@@ -0,0 +1,310 @@
+//! Contains the control flow graph of a function, made of its basic blocks
+//! and the edges between them.
+
+use crate::types::{Block, Code, Function, Instruction};
+use eyre::eyre;
+use std::collections::HashMap;
+
+/// A basic block, along with the label starting it if any
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BasicBlock {
+    pub label: Option<String>,
+    pub instrs: Block,
+}
+
+/// Splits the body of a function into its basic blocks, moving the
+/// instructions. A basic block starts at a label or after a terminator, and
+/// a label directly followed by another one starts an empty block.
+pub fn split_blocks(instrs: Vec<Code>) -> Vec<BasicBlock> {
+    let mut blocks = Vec::new();
+    let mut block = BasicBlock::default();
+    for code in instrs {
+        match code {
+            Code::Label { label } => {
+                if block.label.is_some() || !block.instrs.is_empty() {
+                    blocks.push(std::mem::take(&mut block));
+                }
+                block.label = Some(label);
+            }
+            Code::Instruction(instruction) => {
+                let is_terminator = instruction.is_terminator();
+                block.instrs.push(instruction);
+                if is_terminator {
+                    blocks.push(std::mem::take(&mut block));
+                }
+            }
+        }
+    }
+    if block.label.is_some() || !block.instrs.is_empty() {
+        blocks.push(block);
+    }
+    blocks
+}
+
+/// Returns the body of a function made of the blocks, in order, which
+/// reverts [`split_blocks`]
+pub fn join_blocks(blocks: Vec<BasicBlock>) -> Vec<Code> {
+    blocks
+        .into_iter()
+        .flat_map(|block| {
+            let label = block.label.map(|label| Code::Label { label });
+            label
+                .into_iter()
+                .chain(block.instrs.into_iter().map(Code::Instruction))
+        })
+        .collect()
+}
+
+/// The control flow graph of a function. The blocks are kept in the order
+/// of the function, the first one being the entry.
+///
+/// The instructions of the blocks can be modified in place, but not their
+/// control flow, as the edges are computed once when the graph is built.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cfg {
+    pub blocks: Vec<BasicBlock>,
+    successors: Vec<Vec<usize>>,
+    predecessors: Vec<Vec<usize>>,
+}
+
+impl Cfg {
+    /// Builds the graph of the function, see [`split_blocks`]. A basic block
+    /// falls through to the next one unless it ends with a terminator. Fails
+    /// if an instruction jumps to an unknown label.
+    pub fn new(function: &Function) -> eyre::Result<Self> {
+        let blocks = split_blocks(function.instrs.clone());
+
+        let indices = blocks
+            .iter()
+            .enumerate()
+            .filter_map(|(index, block)| Some((block.label.as_deref()?, index)))
+            .collect::<HashMap<_, _>>();
+        let mut successors = vec![Vec::new(); blocks.len()];
+        let mut predecessors = vec![Vec::new(); blocks.len()];
+        for (index, block) in blocks.iter().enumerate() {
+            let jumps = block
+                .instrs
+                .iter()
+                .filter(|i| i.is_control_flow())
+                .flat_map(|i| i.labels.iter().flatten());
+            for label in jumps {
+                let target = *indices
+                    .get(label.as_str())
+                    .ok_or_else(|| eyre!("unknown label {label} in function {}", function.name))?;
+                successors[index].push(target);
+            }
+            let falls_through = !block.instrs.last().is_some_and(Instruction::is_terminator);
+            if falls_through && index + 1 < blocks.len() {
+                successors[index].push(index + 1);
+            }
+            successors[index].dedup();
+            for &target in &successors[index] {
+                predecessors[target].push(index);
+            }
+        }
+
+        Ok(Self {
+            blocks,
+            successors,
+            predecessors,
+        })
+    }
+
+    /// Returns the blocks the block can transfer control to
+    pub fn successors(&self, block: usize) -> &[usize] {
+        &self.successors[block]
+    }
+
+    /// Returns the blocks which can transfer control to the block
+    pub fn predecessors(&self, block: usize) -> &[usize] {
+        &self.predecessors[block]
+    }
+
+    /// Returns the index of the block starting with the label, if any
+    pub fn block_of(&self, label: &str) -> Option<usize> {
+        self.blocks
+            .iter()
+            .position(|block| block.label.as_deref() == Some(label))
+    }
+
+    /// Returns the blocks reachable from the entry in reverse postorder, in
+    /// which a block comes before its successors unless they loop back to it
+    pub fn reverse_postorder(&self) -> Vec<usize> {
+        let mut postorder = Vec::with_capacity(self.blocks.len());
+        if self.blocks.is_empty() {
+            return postorder;
+        }
+
+        let mut visited = vec![false; self.blocks.len()];
+        visited[0] = true;
+        let mut stack = vec![(0, 0)];
+        while let Some((block, next)) = stack.last_mut() {
+            let block = *block;
+            match self.successors[block].get(*next) {
+                Some(&successor) => {
+                    *next += 1;
+                    if !visited[successor] {
+                        visited[successor] = true;
+                        stack.push((successor, 0));
+                    }
+                }
+                None => {
+                    postorder.push(block);
+                    stack.pop();
+                }
+            }
+        }
+
+        postorder.reverse();
+        postorder
+    }
+
+    /// Returns the immediate dominator of each block, which is the closest
+    /// block on all the paths from the entry to it. The entry and the
+    /// unreachable blocks have none.
+    pub fn immediate_dominators(&self) -> Vec<Option<usize>> {
+        // Computed with the iterative algorithm of Cooper, Harvey and Kennedy,
+        // walking up the dominators of the predecessors until they meet
+        let order = self.reverse_postorder();
+        let mut rank = vec![usize::MAX; self.blocks.len()];
+        for (index, &block) in order.iter().enumerate() {
+            rank[block] = index;
+        }
+        let mut idoms = vec![None; self.blocks.len()];
+        let Some(&entry) = order.first() else {
+            return idoms;
+        };
+        idoms[entry] = Some(entry);
+
+        let intersect = |idoms: &[Option<usize>], mut a: usize, mut b: usize| {
+            while a != b {
+                while rank[a] > rank[b] {
+                    a = idoms[a].expect("the block is processed");
+                }
+                while rank[b] > rank[a] {
+                    b = idoms[b].expect("the block is processed");
+                }
+            }
+            a
+        };
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &block in &order[1..] {
+                let idom = self.predecessors[block]
+                    .iter()
+                    .filter(|p| idoms[**p].is_some())
+                    .fold(None, |idom, &p| {
+                        Some(idom.map_or(p, |idom| intersect(&idoms, idom, p)))
+                    });
+                if idom != idoms[block] {
+                    idoms[block] = idom;
+                    changed = true;
+                }
+            }
+        }
+
+        idoms[entry] = None;
+        idoms
+    }
+
+    /// Returns the body of a function made of the blocks, in order
+    pub fn into_instrs(self) -> Vec<Code> {
+        join_blocks(self.blocks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Cfg;
+    use crate::text::parse_program;
+
+    #[test]
+    fn test_cfg() {
+        // Given
+        let program = parse_program(
+            r#"
+            @main(c: bool) {
+              a: int = const 1;
+              br c .then .else;
+            .then:
+              print a;
+              jmp .end;
+            .else:
+              a: int = const 2;
+            .end:
+              print a;
+            }
+            "#,
+        )
+        .unwrap();
+        let function = &program.functions[0];
+
+        // When
+        let cfg = Cfg::new(function).unwrap();
+
+        // Then
+        assert_eq!(cfg.blocks.len(), 4);
+        assert_eq!(cfg.successors(0), [1, 2]);
+        assert_eq!(cfg.successors(1), [3]);
+        assert_eq!(cfg.successors(2), [3]);
+        assert!(cfg.successors(3).is_empty());
+        assert_eq!(cfg.predecessors(3), [1, 2]);
+        assert_eq!(cfg.block_of("else"), Some(2));
+        assert_eq!(cfg.into_instrs(), function.instrs);
+    }
+
+    #[test]
+    fn test_immediate_dominators() {
+        // Given
+        let program = parse_program(
+            r#"
+            @main(c: bool) {
+            .header:
+              br c .body .exit;
+            .body:
+              br c .left .right;
+            .left:
+              jmp .latch;
+            .right:
+              jmp .latch;
+            .latch:
+              jmp .header;
+            .dead:
+              jmp .latch;
+            .exit:
+              ret;
+            }
+            "#,
+        )
+        .unwrap();
+        let cfg = Cfg::new(&program.functions[0]).unwrap();
+
+        // When
+        let order = cfg.reverse_postorder();
+        let idoms = cfg.immediate_dominators();
+
+        // Then
+        assert_eq!(order, [0, 6, 1, 3, 2, 4]);
+        assert_eq!(
+            idoms,
+            [None, Some(0), Some(1), Some(1), Some(1), None, Some(0)]
+        );
+    }
+
+    #[test]
+    fn test_cfg_unknown_label() {
+        // Given
+        let program = parse_program("@main { jmp .nowhere; }").unwrap();
+
+        // When
+        let cfg = Cfg::new(&program.functions[0]);
+
+        // Then
+        assert_eq!(
+            cfg.unwrap_err().to_string(),
+            "unknown label nowhere in function main"
+        );
+    }
+}
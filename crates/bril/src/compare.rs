@@ -0,0 +1,193 @@
+//! Contains the comparison of programs up to the renaming of their variables.
+
+use crate::types::{BrilProgram, Code, Function, Instruction, Var};
+use std::collections::HashMap;
+
+/// A node of the IR which can be compared up to a consistent
+/// renaming of its variables.
+pub trait AlphaEquivalent {
+    /// Returns true if both nodes are identical once the variables of
+    /// `other` are renamed with the mapping built along the way.
+    fn alpha_equivalent_with(&self, other: &Self, renaming: &mut Renaming) -> bool;
+}
+
+/// Returns true if both nodes are identical up to a consistent renaming
+/// of their variables. Label names, function names and positions are
+/// not renamed: labels and names have to match exactly, while positions
+/// and the attributes attached by analyses are ignored.
+///
+/// The renaming is a bijection: each variable of `a` corresponds to a
+/// single variable of `b` and the other way around.
+pub fn alpha_equivalent<T: AlphaEquivalent + ?Sized>(a: &T, b: &T) -> bool {
+    a.alpha_equivalent_with(b, &mut Renaming::default())
+}
+
+/// The bijection between the variables of the two compared nodes
+#[derive(Debug, Default)]
+pub struct Renaming {
+    forward: HashMap<Var, Var>,
+    backward: HashMap<Var, Var>,
+}
+
+impl Renaming {
+    /// Maps `a` to `b`, returning false if either variable
+    /// was already mapped to another variable
+    fn unify(&mut self, a: Var, b: Var) -> bool {
+        let forward = *self.forward.entry(a).or_insert(b);
+        let backward = *self.backward.entry(b).or_insert(a);
+        forward == b && backward == a
+    }
+}
+
+impl AlphaEquivalent for BrilProgram {
+    /// Each function is compared with its own renaming
+    fn alpha_equivalent_with(&self, other: &Self, _renaming: &mut Renaming) -> bool {
+        self.functions.len() == other.functions.len()
+            && self
+                .functions
+                .iter()
+                .zip(&other.functions)
+                .all(|(a, b)| alpha_equivalent(a, b))
+    }
+}
+
+impl AlphaEquivalent for Function {
+    fn alpha_equivalent_with(&self, other: &Self, renaming: &mut Renaming) -> bool {
+        self.name == other.name
+            && self.r#type == other.r#type
+            && self.args.len() == other.args.len()
+            && self.instrs.len() == other.instrs.len()
+            && self
+                .args
+                .iter()
+                .zip(&other.args)
+                .all(|(a, b)| a.r#type == b.r#type && renaming.unify(a.name, b.name))
+            && self
+                .instrs
+                .iter()
+                .zip(&other.instrs)
+                .all(|(a, b)| match (a, b) {
+                    (Code::Label { label: a }, Code::Label { label: b }) => a == b,
+                    (Code::Instruction(a), Code::Instruction(b)) => {
+                        a.alpha_equivalent_with(b, renaming)
+                    }
+                    _ => false,
+                })
+    }
+}
+
+impl AlphaEquivalent for [Instruction] {
+    fn alpha_equivalent_with(&self, other: &Self, renaming: &mut Renaming) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .zip(other)
+                .all(|(a, b)| a.alpha_equivalent_with(b, renaming))
+    }
+}
+
+impl AlphaEquivalent for Vec<Instruction> {
+    fn alpha_equivalent_with(&self, other: &Self, renaming: &mut Renaming) -> bool {
+        self.as_slice().alpha_equivalent_with(other, renaming)
+    }
+}
+
+impl AlphaEquivalent for Instruction {
+    fn alpha_equivalent_with(&self, other: &Self, renaming: &mut Renaming) -> bool {
+        let same_shape = self.op == other.op
+            && self.r#type == other.r#type
+            && self.value == other.value
+            && self.funcs == other.funcs
+            && self.labels == other.labels
+            && self.extra == other.extra
+            && self.args.len() == other.args.len()
+            && self.dest.is_some() == other.dest.is_some();
+
+        // The arguments are unified before the destination, in
+        // the order they are evaluated by the instruction
+        same_shape
+            && self
+                .args
+                .iter()
+                .zip(&other.args)
+                .all(|(a, b)| renaming.unify(*a, *b))
+            && self
+                .dest
+                .zip(other.dest)
+                .is_none_or(|(a, b)| renaming.unify(a, b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::alpha_equivalent;
+    use crate::text::parse_program;
+    use crate::types::Instruction;
+
+    #[test]
+    fn test_alpha_equivalent_blocks() {
+        // Given
+        let a = vec![
+            Instruction::constant("a", 1),
+            Instruction::add("b", "a", "a"),
+            Instruction::print("b"),
+        ];
+        let b = vec![
+            Instruction::constant("x", 1),
+            Instruction::add("lvn.0", "x", "x"),
+            Instruction::print("lvn.0"),
+        ];
+        let swapped = vec![
+            Instruction::constant("x", 1),
+            Instruction::add("y", "x", "x"),
+            Instruction::print("x"),
+        ];
+
+        // When / Then
+        assert!(alpha_equivalent(&a, &b));
+        assert!(!alpha_equivalent(&a, &swapped));
+        assert!(!alpha_equivalent(&a, &a[..2].to_vec()));
+    }
+
+    #[test]
+    fn test_alpha_equivalent_is_a_bijection() {
+        // Given
+        let a = vec![Instruction::add("c", "a", "b")];
+        let b = vec![Instruction::add("c", "a", "a")];
+
+        // When / Then
+        assert!(!alpha_equivalent(&a, &b));
+        assert!(!alpha_equivalent(&b, &a));
+    }
+
+    #[test]
+    fn test_alpha_equivalent_programs() {
+        // Given
+        let a = parse_program(
+            "@main(n: int) { one: int = const 1; s: int = add n one; .end: print s; }",
+        )
+        .unwrap();
+        let b = parse_program(
+            "@main(m: int) { v0: int = const 1; v1: int = add m v0; .end: print v1; }",
+        )
+        .unwrap();
+        let other_label =
+            parse_program("@main(m: int) { v0: int = const 1; v1: int = add m v0; .l: print v1; }")
+                .unwrap();
+
+        // When / Then
+        assert!(alpha_equivalent(&a, &b));
+        assert!(!alpha_equivalent(&a, &other_label));
+    }
+
+    #[test]
+    fn test_alpha_equivalent_return_types() {
+        // Given
+        let int = parse_program("@f: int { v: int = const 1; ret v; }").unwrap();
+        let bool = parse_program("@f: bool { v: int = const 1; ret v; }").unwrap();
+
+        // When / Then
+        assert!(alpha_equivalent(&int, &int.clone()));
+        assert!(!alpha_equivalent(&int, &bool));
+    }
+}
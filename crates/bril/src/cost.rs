@@ -0,0 +1,146 @@
+//! Contains the cost model of the instructions, a shared estimate of
+//! their execution time used by the passes and the reports.
+
+use crate::types::{BrilProgram, Code, Function, Instruction, Operation};
+
+/// Estimates the cost of executing instructions. Only [`CostModel::cost`]
+/// needs to be implemented, the costs of blocks, functions and programs
+/// are the sum of the costs of their instructions.
+pub trait CostModel {
+    /// Returns the cost of a single execution of the instruction
+    fn cost(&self, instruction: &Instruction) -> u64;
+
+    fn block_cost(&self, block: &[Instruction]) -> u64 {
+        block.iter().map(|i| self.cost(i)).sum()
+    }
+
+    fn function_cost(&self, function: &Function) -> u64 {
+        function
+            .instrs
+            .iter()
+            .map(|code| match code {
+                Code::Instruction(i) => self.cost(i),
+                Code::Label { .. } => 0,
+            })
+            .sum()
+    }
+
+    fn program_cost(&self, program: &BrilProgram) -> u64 {
+        program
+            .functions
+            .iter()
+            .map(|f| self.function_cost(f))
+            .sum()
+    }
+}
+
+/// Cost model assigning a fixed weight to each operation. Constants and
+/// SSA bookkeeping are free, simple arithmetic costs 1 and calls, memory
+/// management and unknown operations are the most expensive.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultCostModel;
+
+impl CostModel for DefaultCostModel {
+    fn cost(&self, instruction: &Instruction) -> u64 {
+        match instruction.op {
+            Operation::Const | Operation::Phi | Operation::Set | Operation::Get => 0,
+            Operation::Add
+            | Operation::Sub
+            | Operation::Eq
+            | Operation::Lt
+            | Operation::Gt
+            | Operation::Le
+            | Operation::Ge
+            | Operation::And
+            | Operation::Or
+            | Operation::Not
+            | Operation::Id
+            | Operation::Br
+            | Operation::Jmp
+            | Operation::Ret
+            | Operation::PtrAdd
+            | Operation::Speculate
+            | Operation::Commit
+            | Operation::Guard
+            | Operation::Ceq
+            | Operation::Clt
+            | Operation::Cgt
+            | Operation::Cle
+            | Operation::Cge
+            | Operation::Char2Int
+            | Operation::Int2Char => 1,
+            Operation::Fadd
+            | Operation::Fsub
+            | Operation::Feq
+            | Operation::Flt
+            | Operation::Fgt
+            | Operation::Fle
+            | Operation::Fge => 2,
+            Operation::Mul => 3,
+            Operation::Fmul | Operation::Load | Operation::Store => 4,
+            Operation::Free => 5,
+            Operation::Div => 8,
+            Operation::Fdiv
+            | Operation::Call
+            | Operation::Alloc
+            | Operation::Print
+            | Operation::Other(_) => 10,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CostModel, DefaultCostModel};
+    use crate::text::parse_program;
+    use crate::types::Instruction;
+
+    #[test]
+    fn test_default_cost_model() {
+        // Given
+        let program = parse_program(
+            r#"
+            @main {
+              a: int = const 1;
+              b: int = add a a;
+              c: int = mul b b;
+            .end:
+              r: int = call @f c;
+            }
+            @f(x: int): int {
+              ret x;
+            }
+            "#,
+        )
+        .unwrap();
+
+        // When
+        let model = DefaultCostModel;
+
+        // Then
+        assert_eq!(model.cost(&Instruction::constant("a", 1)), 0);
+        assert_eq!(model.function_cost(&program.functions[0]), 14);
+        assert_eq!(model.program_cost(&program), 15);
+        assert_eq!(
+            model.block_cost(&[Instruction::add("b", "a", "a"), Instruction::print("b")]),
+            11
+        );
+    }
+
+    #[test]
+    fn test_custom_cost_model() {
+        // Given
+        struct Count;
+        impl CostModel for Count {
+            fn cost(&self, _instruction: &Instruction) -> u64 {
+                1
+            }
+        }
+
+        // When
+        let cost = Count.block_cost(&[Instruction::constant("a", 1), Instruction::print("a")]);
+
+        // Then
+        assert_eq!(cost, 2);
+    }
+}
@@ -0,0 +1,102 @@
+//! Contains the diagnostic type used to report issues in a program,
+//! along with their location.
+
+use std::fmt::{Display, Formatter};
+
+/// An issue found in a program. The location is as precise as the
+/// reporter knows: a pass working on a single block only sets the
+/// instruction index, and its caller can add the rest of the context.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// The function containing the issue
+    pub function: Option<String>,
+    /// The index of the basic block in the function
+    pub block: Option<usize>,
+    /// The index of the instruction, in the function body
+    /// or in the block if the block is set
+    pub index: Option<usize>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            function: None,
+            block: None,
+            index: None,
+            message: message.into(),
+        }
+    }
+
+    pub fn in_function(mut self, function: impl Into<String>) -> Self {
+        self.function = Some(function.into());
+        self
+    }
+
+    pub fn in_block(mut self, block: usize) -> Self {
+        self.block = Some(block);
+        self
+    }
+
+    pub fn at_instruction(mut self, index: usize) -> Self {
+        self.index = Some(index);
+        self
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let location = [
+            self.function
+                .as_ref()
+                .map(|function| format!("function {function}")),
+            self.block.map(|block| format!("block {block}")),
+            self.index.map(|index| format!("instruction {index}")),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+
+        if location.is_empty() {
+            return f.write_str(&self.message);
+        }
+        write!(f, "{}: {}", location.join(", "), self.message)
+    }
+}
+
+/// Diagnostics can be returned as errors, the location can then be
+/// retrieved by downcasting the error.
+impl std::error::Error for Diagnostic {}
+
+#[cfg(test)]
+mod tests {
+    use super::Diagnostic;
+
+    #[test]
+    fn test_display_diagnostic() {
+        let diagnostic = Diagnostic::new("undefined variable a");
+
+        assert_eq!(diagnostic.to_string(), "undefined variable a");
+        assert_eq!(
+            diagnostic.clone().at_instruction(3).to_string(),
+            "instruction 3: undefined variable a"
+        );
+        assert_eq!(
+            diagnostic
+                .in_function("main")
+                .in_block(1)
+                .at_instruction(3)
+                .to_string(),
+            "function main, block 1, instruction 3: undefined variable a"
+        );
+    }
+
+    #[test]
+    fn test_diagnostic_as_error() {
+        let error = eyre::Report::new(Diagnostic::new("oops").at_instruction(2));
+
+        let diagnostic = error.downcast_ref::<Diagnostic>().unwrap();
+
+        assert_eq!(diagnostic.index, Some(2));
+    }
+}
@@ -0,0 +1,484 @@
+//! Contains the structural diff between two versions of a program, used
+//! to inspect the changes made by a pass.
+
+use crate::types::{BrilProgram, Code};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+/// The changes between two versions of a program. The human-readable
+/// form is given by the [`Display`] implementation and the machine-readable
+/// one by the serialization.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ProgramDiff {
+    /// The functions which changed, in the order of the
+    /// original program followed by the new functions
+    pub functions: Vec<FunctionDiff>,
+}
+
+/// The changes made to the body of a function
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct FunctionDiff {
+    pub name: String,
+    pub changes: Vec<Change>,
+}
+
+/// A change to the body of a function. `before` indices refer to the
+/// original function and `after` indices to the new one.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Change {
+    Inserted {
+        after: usize,
+        code: Code,
+    },
+    Removed {
+        before: usize,
+        code: Code,
+    },
+    /// An entry replaced by another one at the same place. The entries
+    /// are boxed to keep the other variants small.
+    Rewritten {
+        before: usize,
+        after: usize,
+        old: Box<Code>,
+        new: Box<Code>,
+    },
+}
+
+impl ProgramDiff {
+    /// Returns true if both programs are identical
+    pub fn is_empty(&self) -> bool {
+        self.functions.is_empty()
+    }
+}
+
+/// Returns the changes between the two programs. Functions are matched by
+/// name, and their bodies are compared with a longest common subsequence,
+/// found in space linear in their lengths and in a time proportional to the
+/// number of changes: removals directly followed by insertions are reported
+/// as rewrites.
+pub fn program_diff(before: &BrilProgram, after: &BrilProgram) -> ProgramDiff {
+    let removed_or_changed = before
+        .functions
+        .iter()
+        .map(|f| (f, find(after, &f.name).unwrap_or_default()));
+    let added = after
+        .functions
+        .iter()
+        .filter(|f| find(before, &f.name).is_none())
+        .map(|f| (f, &[][..]));
+
+    let functions = removed_or_changed
+        .map(|(f, new)| function_diff(&f.name, &f.instrs, new))
+        .chain(added.map(|(f, old)| function_diff(&f.name, old, &f.instrs)))
+        .filter(|d| !d.changes.is_empty())
+        .collect();
+
+    ProgramDiff { functions }
+}
+
+/// Returns the body of the function with the given name, if any
+fn find<'a>(program: &'a BrilProgram, name: &str) -> Option<&'a [Code]> {
+    program
+        .functions
+        .iter()
+        .find(|f| f.name == name)
+        .map(|f| f.instrs.as_slice())
+}
+
+/// Returns the changes between the function bodies
+fn function_diff(name: &str, old: &[Code], new: &[Code]) -> FunctionDiff {
+    let mut edits = Vec::with_capacity(old.len().max(new.len()));
+    myers(old, new, (0, 0), &mut edits);
+    slide(&mut edits, old, new);
+
+    let mut changes = Vec::new();
+    let mut removed = Vec::new();
+    let mut inserted = Vec::new();
+    for edit in edits {
+        match edit {
+            Edit::Kept(..) => flush(&mut changes, &mut removed, &mut inserted, old, new),
+            Edit::Removed(before) => removed.push(before),
+            Edit::Inserted(after) => inserted.push(after),
+        }
+    }
+    flush(&mut changes, &mut removed, &mut inserted, old, new);
+
+    FunctionDiff {
+        name: name.to_string(),
+        changes,
+    }
+}
+
+/// An edit turning the old body into the new one, with the indices of the
+/// entries in their bodies
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Edit {
+    Kept(usize, usize),
+    Removed(usize),
+    Inserted(usize),
+}
+
+/// Moves each insertion or removal after the kept entries equal to it, so
+/// that the changes are reported as late as possible, whichever of the equal
+/// entries the subsequence kept.
+/// Example: (+ print a, = print a -> = print a, + print a)
+fn slide(edits: &mut [Edit], old: &[Code], new: &[Code]) {
+    for start in (0..edits.len()).rev() {
+        for k in start..edits.len() - 1 {
+            let slid = match (edits[k], edits[k + 1]) {
+                (Edit::Inserted(x), Edit::Kept(i, j)) if new[x] == new[j] => {
+                    [Edit::Kept(i, x), Edit::Inserted(j)]
+                }
+                (Edit::Removed(x), Edit::Kept(i, j)) if old[x] == old[i] => {
+                    [Edit::Kept(x, j), Edit::Removed(i)]
+                }
+                _ => break,
+            };
+            edits[k..=k + 1].copy_from_slice(&slid);
+        }
+    }
+}
+
+/// Records the edits turning `old` into `new` along one of their longest
+/// common subsequences, with the linear space variant of Myers' algorithm.
+/// The entries shared at the start and at the end are kept, and the rest is
+/// split at the middle of a shortest edit script, see [`middle_snake`]. The
+/// offsets are the indices of the first entries in their bodies.
+fn myers(old: &[Code], new: &[Code], offsets: (usize, usize), edits: &mut Vec<Edit>) {
+    let (i, j) = offsets;
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    edits.extend((0..prefix).map(|k| Edit::Kept(i + k, j + k)));
+
+    let (i, j) = (i + prefix, j + prefix);
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+    match middle_snake(old_middle, new_middle) {
+        Some((x, y)) => {
+            myers(&old_middle[..x], &new_middle[..y], (i, j), edits);
+            myers(&old_middle[x..], &new_middle[y..], (i + x, j + y), edits);
+        }
+        None => {
+            edits.extend((i..i + old_middle.len()).map(Edit::Removed));
+            edits.extend((j..j + new_middle.len()).map(Edit::Inserted));
+        }
+    }
+
+    let (i, j) = (i + old_middle.len(), j + new_middle.len());
+    edits.extend((0..suffix).map(|k| Edit::Kept(i + k, j + k)));
+}
+
+/// Returns the point in the middle of a shortest edit script of the bodies,
+/// which don't start nor end with the same entry. The paths of furthest
+/// reach are followed from both ends, one edit at a time, until they
+/// overlap. Returns none if the bodies have nothing in common.
+fn middle_snake(old: &[Code], new: &[Code]) -> Option<(usize, usize)> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let max = (n + m + 1) / 2;
+    let offset = max;
+    let length = 2 * max + 2;
+    // The furthest x reached on each diagonal k = x - y, by the forward
+    // paths and by the backward paths counted from the end
+    let mut forward = vec![-1isize; length as usize];
+    let mut backward = vec![-1isize; length as usize];
+    forward[offset as usize + 1] = 0;
+    backward[offset as usize + 1] = 0;
+    let delta = n - m;
+    // The paths overlap on a forward step if the difference of the lengths is odd
+    let odd = delta % 2 != 0;
+    // The diagonals leaving the bodies are skipped
+    let (mut forward_start, mut forward_end) = (0, 0);
+    let (mut backward_start, mut backward_end) = (0, 0);
+
+    for d in 0..max {
+        for k in (-d + forward_start..=d - forward_end).step_by(2) {
+            let index = (offset + k) as usize;
+            let mut x = match k == -d || (k != d && forward[index - 1] < forward[index + 1]) {
+                true => forward[index + 1],
+                false => forward[index - 1] + 1,
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            forward[index] = x;
+            if x > n {
+                forward_end += 2;
+            } else if y > m {
+                forward_start += 2;
+            } else if odd {
+                let other = offset + delta - k;
+                let reached = (0..length).contains(&other) && backward[other as usize] != -1;
+                if reached && x >= n - backward[other as usize] {
+                    return Some((x as usize, y as usize));
+                }
+            }
+        }
+
+        for k in (-d + backward_start..=d - backward_end).step_by(2) {
+            let index = (offset + k) as usize;
+            let mut x = match k == -d || (k != d && backward[index - 1] < backward[index + 1]) {
+                true => backward[index + 1],
+                false => backward[index - 1] + 1,
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[(n - x - 1) as usize] == new[(m - y - 1) as usize] {
+                x += 1;
+                y += 1;
+            }
+            backward[index] = x;
+            if x > n {
+                backward_end += 2;
+            } else if y > m {
+                backward_start += 2;
+            } else if !odd {
+                let other = offset + delta - k;
+                if (0..length).contains(&other) && forward[other as usize] != -1 {
+                    let forward_x = forward[other as usize];
+                    let forward_y = forward_x - (other - offset);
+                    if forward_x >= n - x {
+                        return Some((forward_x as usize, forward_y as usize));
+                    }
+                }
+            }
+        }
+    }
+
+    None
+}
+
+/// Records the pending removals and insertions of a hunk, pairing
+/// them as rewrites in order
+fn flush(
+    changes: &mut Vec<Change>,
+    removed: &mut Vec<usize>,
+    inserted: &mut Vec<usize>,
+    old: &[Code],
+    new: &[Code],
+) {
+    let rewritten = removed.len().min(inserted.len());
+    for (&before, &after) in removed.iter().zip(inserted.iter()) {
+        changes.push(Change::Rewritten {
+            before,
+            after,
+            old: Box::new(old[before].clone()),
+            new: Box::new(new[after].clone()),
+        });
+    }
+    for &before in &removed[rewritten..] {
+        changes.push(Change::Removed {
+            before,
+            code: old[before].clone(),
+        });
+    }
+    for &after in &inserted[rewritten..] {
+        changes.push(Change::Inserted {
+            after,
+            code: new[after].clone(),
+        });
+    }
+    removed.clear();
+    inserted.clear();
+}
+
+impl Display for ProgramDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for function in &self.functions {
+            write!(f, "{function}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Prints each change on its own line, prefixed by `-` for removals,
+/// `+` for insertions and `~` for rewrites
+impl Display for FunctionDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "@{}", self.name)?;
+        for change in &self.changes {
+            match change {
+                Change::Inserted { after, code } => writeln!(f, "  + {after}: {code}")?,
+                Change::Removed { before, code } => writeln!(f, "  - {before}: {code}")?,
+                Change::Rewritten {
+                    before,
+                    after,
+                    old,
+                    new,
+                } => writeln!(f, "  ~ {before} -> {after}: {old} => {new}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{function_diff, program_diff, Change};
+    use crate::text::parse_program;
+    use crate::types::{Code, Instruction};
+
+    #[test]
+    fn test_program_diff() {
+        // Given
+        let before = parse_program(
+            r#"
+            @main {
+              a = const 1;
+              b = const 1;
+              c = add a b;
+              print c;
+            }
+            @unchanged {
+              ret;
+            }
+            "#,
+        )
+        .unwrap();
+        let after = parse_program(
+            r#"
+            @main {
+              a = const 1;
+              c = add a a;
+              print c;
+              print a;
+            }
+            @unchanged {
+              ret;
+            }
+            @added {
+              ret;
+            }
+            "#,
+        )
+        .unwrap();
+
+        // When
+        let diff = program_diff(&before, &after);
+
+        // Then
+        assert_eq!(diff.functions.len(), 2);
+        assert_eq!(diff.functions[0].name, "main");
+        assert_eq!(
+            diff.functions[0].changes,
+            vec![
+                Change::Rewritten {
+                    before: 1,
+                    after: 1,
+                    old: Box::new(Code::Instruction(Instruction::constant("b", 1))),
+                    new: Box::new(Code::Instruction(Instruction::add("c", "a", "a"))),
+                },
+                Change::Removed {
+                    before: 2,
+                    code: Code::Instruction(Instruction::add("c", "a", "b")),
+                },
+                Change::Inserted {
+                    after: 3,
+                    code: Code::Instruction(Instruction::print("a")),
+                },
+            ]
+        );
+        assert_eq!(diff.functions[1].name, "added");
+        assert!(program_diff(&before, &before).is_empty());
+    }
+
+    #[test]
+    fn test_display_diff() {
+        // Given
+        let before = parse_program("@main { a: int = const 1; print a; }").unwrap();
+        let after = parse_program("@main { a: int = const 2; print a; print a; }").unwrap();
+
+        // When
+        let diff = program_diff(&before, &after);
+
+        // Then
+        assert_eq!(
+            diff.to_string(),
+            "@main\n  ~ 0 -> 0: a: int = const 1; => a: int = const 2;\n  + 2: print a;\n"
+        );
+        assert_eq!(
+            serde_json::to_value(&diff.functions[0].changes[1]).unwrap(),
+            serde_json::json!({"kind": "inserted", "after": 2, "code": {"op": "print", "args": ["a"]}})
+        );
+    }
+
+    /// Returns the length of the longest common subsequence, with the whole table
+    fn lcs(old: &[Code], new: &[Code]) -> usize {
+        let mut lengths = vec![vec![0; new.len() + 1]; old.len() + 1];
+        for (i, a) in old.iter().enumerate() {
+            for (j, b) in new.iter().enumerate() {
+                lengths[i + 1][j + 1] = match a == b {
+                    true => lengths[i][j] + 1,
+                    false => lengths[i][j + 1].max(lengths[i + 1][j]),
+                };
+            }
+        }
+        lengths[old.len()][new.len()]
+    }
+
+    /// Returns the number of entries kept by the changes
+    fn kept(old: &[Code], changes: &[Change]) -> usize {
+        let changed = changes
+            .iter()
+            .filter(|c| !matches!(c, Change::Inserted { .. }))
+            .count();
+        old.len() - changed
+    }
+
+    #[test]
+    fn test_function_diff_is_minimal() {
+        // Given
+        let mut seed = 7u64;
+        let mut random = |n: u64| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) % n
+        };
+        let body = |random: &mut dyn FnMut(u64) -> u64| {
+            let len = random(12);
+            (0..len)
+                .map(|_| {
+                    let var = ["a", "b", "c"][random(3) as usize];
+                    Code::Instruction(Instruction::print(var))
+                })
+                .collect::<Vec<_>>()
+        };
+
+        for case in 0..500 {
+            let old = body(&mut random);
+            let new = body(&mut random);
+
+            // When
+            let diff = function_diff("main", &old, &new);
+
+            // Then
+            assert_eq!(kept(&old, &diff.changes), lcs(&old, &new), "case {case}");
+        }
+    }
+
+    #[test]
+    fn test_function_diff_large() {
+        // Given
+        let old = (0..10_000)
+            .map(|i| Code::Instruction(Instruction::constant(format!("v{i}").as_str(), i)))
+            .collect::<Vec<_>>();
+        let mut new = old.clone();
+        new[10] = Code::Instruction(Instruction::print("v0"));
+        new.remove(5_000);
+        new.insert(9_000, Code::Instruction(Instruction::print("v1")));
+
+        // When
+        let diff = function_diff("main", &old, &new);
+
+        // Then
+        assert_eq!(diff.changes.len(), 3);
+        assert_eq!(kept(&old, &diff.changes), old.len() - 2);
+    }
+}
@@ -0,0 +1,223 @@
+//! Contains the helpers running passes over the functions of a program.
+
+use crate::cfg::{join_blocks, split_blocks};
+use crate::diagnostic::Diagnostic;
+use crate::types::{Block, BrilProgram, Function, FunctionArg};
+use std::num::NonZeroUsize;
+use std::thread;
+
+/// Runs the pass on each function of the program, spreading the functions
+/// over as many threads as the machine has cores. The pass must only look
+/// at the function it is given, which makes the order of the runs
+/// irrelevant.
+///
+/// All the functions are processed even if some fail, and the error of
+/// the first function which failed is returned.
+pub fn for_each_function_parallel<F>(program: &mut BrilProgram, pass: F) -> eyre::Result<()>
+where
+    F: Fn(&mut Function) -> eyre::Result<()> + Sync,
+{
+    let threads = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+    let chunk_size = program.functions.len().div_ceil(threads).max(1);
+
+    let pass = &pass;
+    let results = thread::scope(|scope| {
+        let handles = program
+            .functions
+            .chunks_mut(chunk_size)
+            .map(|chunk| scope.spawn(move || chunk.iter_mut().map(pass).collect::<Vec<_>>()))
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().expect("pass panicked"))
+            .collect::<Vec<_>>()
+    });
+
+    results.into_iter().collect()
+}
+
+/// Runs the pass on each basic block of the program, spreading the blocks
+/// of all the functions over as many threads as the machine has cores, so
+/// that a program made of a few large functions is spread too. The pass is
+/// given the arguments of the function of the block, and must only look at
+/// the block it is given.
+///
+/// All the blocks are processed even if some fail, and the error of the
+/// first block which failed is returned, located in its function and block.
+pub fn for_each_block_parallel<F>(program: &mut BrilProgram, pass: F) -> eyre::Result<()>
+where
+    F: Fn(&[FunctionArg], &mut Block) -> eyre::Result<()> + Sync,
+{
+    let mut blocks = program
+        .functions
+        .iter_mut()
+        .map(|function| split_blocks(std::mem::take(&mut function.instrs)))
+        .collect::<Vec<_>>();
+    let mut work = program
+        .functions
+        .iter()
+        .zip(&mut blocks)
+        .flat_map(|(function, blocks)| {
+            blocks
+                .iter_mut()
+                .enumerate()
+                .map(move |(index, block)| (function, index, &mut block.instrs))
+        })
+        .filter(|(_, _, instrs)| !instrs.is_empty())
+        .collect::<Vec<_>>();
+
+    let threads = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+    let chunk_size = work.len().div_ceil(threads).max(1);
+
+    let pass = &pass;
+    let run = |(function, index, block): &mut (&Function, usize, &mut Block)| {
+        pass(&function.args, block).map_err(|error| match error.downcast::<Diagnostic>() {
+            Ok(diagnostic) => {
+                eyre::Report::new(diagnostic.in_function(&function.name).in_block(*index))
+            }
+            Err(error) => error,
+        })
+    };
+    let run = &run;
+    let results = thread::scope(|scope| {
+        let handles = work
+            .chunks_mut(chunk_size)
+            .map(|chunk| scope.spawn(move || chunk.iter_mut().map(run).collect::<Vec<_>>()))
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().expect("pass panicked"))
+            .collect::<Vec<_>>()
+    });
+
+    for (function, blocks) in program.functions.iter_mut().zip(blocks) {
+        function.instrs = join_blocks(blocks);
+    }
+    results.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{for_each_block_parallel, for_each_function_parallel};
+    use crate::diagnostic::Diagnostic;
+    use crate::generate::{generate, Config};
+    use crate::pass::for_each_block;
+    use crate::text::parse_program;
+    use crate::types::{Code, Operation};
+    use eyre::eyre;
+
+    #[test]
+    fn test_for_each_function_parallel() {
+        // Given
+        let mut program = generate(&Config {
+            functions: 50,
+            ..Default::default()
+        });
+        let mut expected = program.clone();
+        for function in &mut expected.functions {
+            function
+                .instrs
+                .retain(|c| matches!(c, Code::Instruction(_)));
+        }
+
+        // When
+        for_each_function_parallel(&mut program, |function| {
+            function
+                .instrs
+                .retain(|c| matches!(c, Code::Instruction(_)));
+            Ok(())
+        })
+        .unwrap();
+
+        // Then
+        assert_eq!(program, expected);
+    }
+
+    #[test]
+    fn test_for_each_function_parallel_error() {
+        // Given
+        let mut program = generate(&Config {
+            functions: 20,
+            ..Default::default()
+        });
+
+        // When
+        let result = for_each_function_parallel(&mut program, |function| {
+            function.instrs.clear();
+            match function.name.as_str() {
+                "f3" | "f7" => Err(eyre!("failed on {}", function.name)),
+                _ => Ok(()),
+            }
+        });
+
+        // Then
+        assert_eq!(result.unwrap_err().to_string(), "failed on f3");
+        assert!(program.functions.iter().all(|f| f.instrs.is_empty()));
+    }
+
+    #[test]
+    fn test_for_each_block_parallel() {
+        // Given
+        let mut program = generate(&Config {
+            functions: 20,
+            ..Default::default()
+        });
+        let mut expected = program.clone();
+        for function in &mut expected.functions {
+            let arity = function.args.len();
+            for_each_block(function, |block| {
+                block.truncate(arity);
+                Ok::<_, ()>(())
+            })
+            .unwrap();
+        }
+
+        // When
+        for_each_block_parallel(&mut program, |args, block| {
+            block.truncate(args.len());
+            Ok(())
+        })
+        .unwrap();
+
+        // Then
+        assert_eq!(program, expected);
+    }
+
+    #[test]
+    fn test_for_each_block_parallel_error() {
+        // Given
+        let mut program = parse_program(
+            r#"
+            @main {
+              print a;
+            .next:
+              ret;
+            }
+            @f {
+              jmp .next;
+            .next:
+              print b;
+            }
+            "#,
+        )
+        .unwrap();
+        let expected = program.clone();
+
+        // When
+        let result = for_each_block_parallel(&mut program, |_, block| {
+            match block.iter().position(|i| i.op == Operation::Print) {
+                Some(index) => Err(eyre::Report::new(
+                    Diagnostic::new("unexpected print").at_instruction(index),
+                )),
+                None => Ok(()),
+            }
+        });
+
+        // Then
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "function main, block 0, instruction 0: unexpected print"
+        );
+        assert_eq!(program, expected);
+    }
+}
@@ -0,0 +1,346 @@
+//! Contains a generator of random well-formed programs, used to
+//! fuzz the passes.
+//!
+//! Every variable is defined in the block using it, so each block can be
+//! optimized on its own, and the generated programs pass [`verify_program`].
+//!
+//! [`verify_program`]: crate::verify::verify_program
+
+use crate::types::{BrilProgram, Code, Function, FunctionArg, Instruction, Operation, Type, Var};
+
+/// The shape of the control flow graph of the generated functions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CfgShape {
+    /// Each block jumps to the next one
+    #[default]
+    Linear,
+    /// Blocks branch to any later block, the graph is acyclic
+    Forward,
+    /// Blocks branch to any block, which creates loops
+    Any,
+}
+
+/// The configuration of the generator
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// The seed of the generator, the same seed always
+    /// generates the same program
+    pub seed: u64,
+    /// The number of functions, the first one is `main`
+    pub functions: usize,
+    /// The number of blocks of each function
+    pub blocks: usize,
+    /// The number of instructions of each block, terminators excluded
+    pub instructions: usize,
+    /// The operations to pick from, uniformly. Repeating an operation
+    /// makes it more frequent. Only `const`, `id`, `print` and the
+    /// integer and boolean operations are supported, others are ignored.
+    pub operations: Vec<Operation>,
+    pub shape: CfgShape,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            functions: 1,
+            blocks: 4,
+            instructions: 8,
+            operations: vec![
+                Operation::Const,
+                Operation::Add,
+                Operation::Mul,
+                Operation::Sub,
+                Operation::Div,
+                Operation::Eq,
+                Operation::Lt,
+                Operation::Gt,
+                Operation::Le,
+                Operation::Ge,
+                Operation::And,
+                Operation::Or,
+                Operation::Not,
+                Operation::Id,
+                Operation::Print,
+            ],
+            shape: CfgShape::default(),
+        }
+    }
+}
+
+/// Generates a random program following the configuration
+pub fn generate(config: &Config) -> BrilProgram {
+    let mut generator = Generator {
+        config,
+        rng: Rng(config.seed),
+        next_var: 0,
+    };
+
+    let functions = (0..config.functions)
+        .map(|index| generator.function(index))
+        .collect();
+
+    BrilProgram::new(functions)
+}
+
+/// SplitMix64, which is enough for test input generation
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number in `0..n`, `n` has to be positive
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len())]
+    }
+}
+
+struct Generator<'a> {
+    config: &'a Config,
+    rng: Rng,
+    next_var: usize,
+}
+
+/// The variables defined so far in a block, by type
+#[derive(Default, Clone)]
+struct Pool {
+    ints: Vec<Var>,
+    bools: Vec<Var>,
+}
+
+impl Generator<'_> {
+    fn fresh(&mut self) -> Var {
+        self.next_var += 1;
+        format!("v{}", self.next_var - 1).into()
+    }
+
+    fn function(&mut self, index: usize) -> Function {
+        let (name, args) = match index {
+            0 => ("main".to_string(), vec![]),
+            i => (
+                format!("f{i}"),
+                vec![
+                    FunctionArg {
+                        name: "x".into(),
+                        r#type: Type::Int,
+                    },
+                    FunctionArg {
+                        name: "c".into(),
+                        r#type: Type::Bool,
+                    },
+                ],
+            ),
+        };
+        let args_pool = Pool {
+            ints: args
+                .iter()
+                .filter(|a| a.r#type == Type::Int)
+                .map(|a| a.name)
+                .collect(),
+            bools: args
+                .iter()
+                .filter(|a| a.r#type == Type::Bool)
+                .map(|a| a.name)
+                .collect(),
+        };
+
+        let blocks = self.config.blocks.max(1);
+        let mut instrs = Vec::new();
+        for block in 0..blocks {
+            instrs.push(Code::Label {
+                label: format!("b{block}"),
+            });
+            let mut pool = args_pool.clone();
+
+            // Seed the pool so that every operation has operands
+            let int = self.fresh();
+            let value = self.rng.below(16) as i64;
+            instrs.push(Code::Instruction(typed(
+                Instruction::constant(int, value),
+                Type::Int,
+            )));
+            pool.ints.push(int);
+            let bool = self.fresh();
+            let value = self.rng.below(2) == 0;
+            instrs.push(Code::Instruction(typed(
+                Instruction::constant(bool, value),
+                Type::Bool,
+            )));
+            pool.bools.push(bool);
+
+            for _ in 0..self.config.instructions {
+                if let Some(instruction) = self.instruction(&mut pool) {
+                    instrs.push(Code::Instruction(instruction));
+                }
+            }
+
+            instrs.push(Code::Instruction(self.terminator(block, blocks, &pool)));
+        }
+
+        Function {
+            name,
+            args,
+            instrs,
+            ..Default::default()
+        }
+    }
+
+    /// Returns a random instruction over the variables of the pool,
+    /// or none if the picked operation isn't supported
+    fn instruction(&mut self, pool: &mut Pool) -> Option<Instruction> {
+        if self.config.operations.is_empty() {
+            return None;
+        }
+        let op = self.rng.pick(&self.config.operations).clone();
+
+        let (args, ty) = match op {
+            Operation::Const => {
+                let dest = self.fresh();
+                let value = self.rng.below(16) as i64;
+                pool.ints.push(dest);
+                return Some(typed(Instruction::constant(dest, value), Type::Int));
+            }
+            Operation::Print => {
+                let arg = *self.rng.pick(&pool.ints);
+                return Some(Instruction::print(arg));
+            }
+            Operation::Add | Operation::Mul | Operation::Sub | Operation::Div => (
+                vec![*self.rng.pick(&pool.ints), *self.rng.pick(&pool.ints)],
+                Type::Int,
+            ),
+            Operation::Eq | Operation::Lt | Operation::Gt | Operation::Le | Operation::Ge => (
+                vec![*self.rng.pick(&pool.ints), *self.rng.pick(&pool.ints)],
+                Type::Bool,
+            ),
+            Operation::And | Operation::Or => (
+                vec![*self.rng.pick(&pool.bools), *self.rng.pick(&pool.bools)],
+                Type::Bool,
+            ),
+            Operation::Not => (vec![*self.rng.pick(&pool.bools)], Type::Bool),
+            Operation::Id => (vec![*self.rng.pick(&pool.ints)], Type::Int),
+            _ => return None,
+        };
+
+        let dest = self.fresh();
+        match ty {
+            Type::Bool => pool.bools.push(dest),
+            _ => pool.ints.push(dest),
+        }
+        Some(Instruction {
+            op,
+            args,
+            r#type: Some(ty),
+            dest: Some(dest),
+            ..Default::default()
+        })
+    }
+
+    /// Returns the instruction ending the block
+    fn terminator(&mut self, block: usize, blocks: usize, pool: &Pool) -> Instruction {
+        if block + 1 == blocks {
+            return Instruction::ret(None);
+        }
+
+        let next = format!("b{}", block + 1);
+        let target = match self.config.shape {
+            CfgShape::Linear => return Instruction::jmp(next),
+            CfgShape::Forward => block + 1 + self.rng.below(blocks - block - 1),
+            CfgShape::Any => self.rng.below(blocks),
+        };
+        let cond = *self.rng.pick(&pool.bools);
+        Instruction::br(cond, format!("b{target}"), next)
+    }
+}
+
+fn typed(mut instruction: Instruction, ty: Type) -> Instruction {
+    instruction.r#type = Some(ty);
+    instruction
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate, CfgShape, Config};
+    use crate::types::{Code, Operation};
+    use crate::verify::verify_program;
+
+    #[test]
+    fn test_generate_well_formed_programs() {
+        for shape in [CfgShape::Linear, CfgShape::Forward, CfgShape::Any] {
+            for seed in 0..20 {
+                // Given
+                let config = Config {
+                    seed,
+                    functions: 2,
+                    shape,
+                    ..Default::default()
+                };
+
+                // When
+                let program = generate(&config);
+
+                // Then
+                assert_eq!(verify_program(&program), vec![], "seed {seed}");
+                assert_eq!(program.functions.len(), 2);
+                assert_eq!(program.functions[0].name, "main");
+            }
+        }
+    }
+
+    #[test]
+    fn test_generate_is_deterministic() {
+        // Given
+        let config = Config {
+            seed: 42,
+            shape: CfgShape::Any,
+            ..Default::default()
+        };
+
+        // When / Then
+        assert_eq!(generate(&config), generate(&config));
+        assert_ne!(
+            generate(&config),
+            generate(&Config {
+                seed: 43,
+                ..config.clone()
+            })
+        );
+    }
+
+    #[test]
+    fn test_generate_operation_mix() {
+        // Given
+        let config = Config {
+            blocks: 1,
+            instructions: 10,
+            operations: vec![Operation::Add, Operation::Call],
+            ..Default::default()
+        };
+
+        // When
+        let program = generate(&config);
+
+        // Then
+        let ops = program.functions[0]
+            .instrs
+            .iter()
+            .filter_map(|c| match c {
+                Code::Instruction(i) => Some(i.op.clone()),
+                Code::Label { .. } => None,
+            })
+            .collect::<Vec<_>>();
+        // The calls are not supported and skipped
+        assert!(ops.len() <= 13);
+        assert!(ops[2..ops.len() - 1].iter().all(|op| *op == Operation::Add));
+        assert_eq!(ops.last(), Some(&Operation::Ret));
+    }
+}
@@ -0,0 +1,215 @@
+//! Contains the inference of the types missing from instructions.
+
+use crate::types::{Code, Function, Instruction, Literal, Operation, Type, Var};
+use crate::verify::signature;
+use eyre::eyre;
+use std::collections::HashMap;
+
+/// Fills in the missing `type` of the instructions of the function.
+///
+/// Types are propagated from the arguments of the function, the constants
+/// and the operations with a fixed result type, through copies, phis and
+/// memory operations. Instructions whose type can't be inferred, such as
+/// calls, are left untouched. An error is returned if a variable would be
+/// given two different types.
+pub fn infer_types(function: &mut Function) -> eyre::Result<()> {
+    let mut types = function
+        .args
+        .iter()
+        .map(|a| (a.name, a.r#type.clone()))
+        .collect::<HashMap<Var, Type>>();
+
+    // Variables can be used before their definition in a loop, so the
+    // propagation is repeated until no new type is found
+    loop {
+        let mut changed = false;
+        for code in &function.instrs {
+            let Code::Instruction(i) = code else {
+                continue;
+            };
+            let Some(dest) = i.dest else {
+                continue;
+            };
+
+            let inferred = infer(i, &types);
+            let ty = match (inferred, i.r#type.as_ref()) {
+                (Some(inferred), Some(declared)) if inferred != *declared => {
+                    return Err(eyre!(
+                        "variable {dest} is declared as {declared} but computes {inferred}"
+                    ))
+                }
+                (_, Some(declared)) => declared.clone(),
+                (Some(inferred), None) => inferred,
+                (None, None) => continue,
+            };
+
+            match types.get(&dest) {
+                Some(t) if *t != ty => {
+                    return Err(eyre!("conflicting types for variable {dest}: {t} and {ty}"))
+                }
+                Some(_) => (),
+                None => {
+                    types.insert(dest, ty);
+                    changed = true;
+                }
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    for code in &mut function.instrs {
+        let Code::Instruction(i) = code else {
+            continue;
+        };
+        if i.r#type.is_none() {
+            i.r#type = i.dest.and_then(|dest| types.get(&dest).cloned());
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the type of the result of the instruction, if it can be
+/// inferred from the operation and the known types of the arguments
+fn infer(i: &Instruction, types: &HashMap<Var, Type>) -> Option<Type> {
+    let arg = |n: usize| i.args.get(n).and_then(|a| types.get(a));
+
+    match &i.op {
+        // An integer literal is a valid float, so the declared type is kept
+        // when it matches the literal
+        Operation::Const => match i.value? {
+            value if i.r#type.as_ref().is_some_and(|ty| value.matches_type(ty)) => i.r#type.clone(),
+            Literal::Bool(_) => Some(Type::Bool),
+            Literal::Int(_) => Some(Type::Int),
+            Literal::Float(_) => Some(Type::Float),
+            Literal::Char(_) => Some(Type::Char),
+        },
+        Operation::Id | Operation::PtrAdd => arg(0).cloned(),
+        Operation::Load => match arg(0)? {
+            Type::Ptr(t) => Some(*t.clone()),
+            _ => None,
+        },
+        Operation::Phi => i.args.iter().find_map(|a| types.get(a)).cloned(),
+        op => signature(op).and_then(|(_, result)| result),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::infer_types;
+    use crate::text::parse_program;
+    use crate::types::Function;
+
+    #[test]
+    fn test_infer_types() {
+        // Given
+        let mut function = parse_program(
+            r#"
+            @main(p: ptr<float>, n: int) {
+              a = const 1;
+              b = add a n;
+              c = id b;
+              d = lt c a;
+              e = load p;
+              f = id e;
+              r = call @other;
+            }
+            "#,
+        )
+        .unwrap()
+        .functions
+        .remove(0);
+
+        // When
+        infer_types(&mut function).unwrap();
+
+        // Then
+        assert_eq!(
+            function.to_string(),
+            r#"@main(p: ptr<float>, n: int) {
+  a: int = const 1;
+  b: int = add a n;
+  c: int = id b;
+  d: bool = lt c a;
+  e: float = load p;
+  f: float = id e;
+  r = call @other;
+}
+"#
+        );
+    }
+
+    #[test]
+    fn test_infer_types_loop() {
+        // Given
+        let mut function = parse_program(
+            r#"
+            @main {
+            .loop:
+              y = id x;
+              x = const true;
+              jmp .loop;
+            }
+            "#,
+        )
+        .unwrap()
+        .functions
+        .remove(0);
+
+        // When
+        infer_types(&mut function).unwrap();
+
+        // Then
+        assert!(function.to_string().contains("y: bool = id x;"));
+    }
+
+    #[test]
+    fn test_infer_types_conflict() {
+        // Given
+        let mut redefined = parse_program("@main { a = const 1; a = const true; }")
+            .unwrap()
+            .functions
+            .remove(0);
+        let mut declared = parse_program("@main(n: int) { b: bool = add n n; }")
+            .unwrap()
+            .functions
+            .remove(0);
+
+        // When / Then
+        assert!(infer_types(&mut redefined).is_err());
+        assert!(infer_types(&mut declared).is_err());
+    }
+
+    #[test]
+    fn test_infer_types_int_literal_float() {
+        // Given
+        let mut function = serde_json::from_str::<Function>(
+            r#"{
+                "name": "main",
+                "instrs": [
+                    { "op": "const", "dest": "f", "type": "float", "value": 1 },
+                    { "op": "const", "dest": "b", "type": "bool", "value": true },
+                    { "op": "fadd", "dest": "g", "args": ["f", "f"] }
+                ]
+            }"#,
+        )
+        .unwrap();
+        let mut mismatched = serde_json::from_str::<Function>(
+            r#"{
+                "name": "main",
+                "instrs": [{ "op": "const", "dest": "b", "type": "bool", "value": 1 }]
+            }"#,
+        )
+        .unwrap();
+
+        // When
+        let result = infer_types(&mut function);
+
+        // Then
+        assert!(result.is_ok(), "{result:?}");
+        assert!(function.to_string().contains("g: float = fadd f f;"));
+        assert!(infer_types(&mut mismatched).is_err());
+    }
+}
@@ -0,0 +1,165 @@
+//! Contains the lenient parsing of JSON programs, which reports all the
+//! malformed entries instead of stopping at the first one.
+
+use crate::diagnostic::Diagnostic;
+use crate::types::{BrilProgram, Code, Function, Instruction, Operation};
+use serde_json::Value;
+
+impl BrilProgram {
+    /// Parses a JSON program, replacing each malformed instruction with an
+    /// [`Operation::Other`] placeholder and reporting it as a diagnostic.
+    /// Unknown operations are reported too, as they are usually typos in
+    /// hand-written programs. Malformed functions are reported and skipped.
+    ///
+    /// Only invalid JSON and a malformed program object are errors.
+    pub fn from_json_lenient(json: &str) -> eyre::Result<(BrilProgram, Vec<Diagnostic>)> {
+        let mut value: Value = serde_json::from_str(json)?;
+        let functions = match value.get_mut("functions").map(take_array) {
+            Some(Value::Array(functions)) => functions,
+            _ => Vec::new(),
+        };
+        let mut program: BrilProgram = serde_json::from_value(value)?;
+
+        let mut diagnostics = Vec::new();
+        for (index, function) in functions.into_iter().enumerate() {
+            match parse_function(function, &mut diagnostics) {
+                Ok(function) => program.functions.push(function),
+                Err(err) => diagnostics.push(Diagnostic::new(format!(
+                    "function {index} is malformed: {err}"
+                ))),
+            }
+        }
+
+        Ok((program, diagnostics))
+    }
+}
+
+/// Parses the function, each of its instructions on its own
+fn parse_function(
+    mut value: Value,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> serde_json::Result<Function> {
+    let instrs = match value.get_mut("instrs").map(take_array) {
+        Some(Value::Array(instrs)) => instrs,
+        _ => Vec::new(),
+    };
+    let mut function: Function = serde_json::from_value(value)?;
+
+    for (index, code) in instrs.into_iter().enumerate() {
+        let diagnostic = |message| {
+            Diagnostic::new(message)
+                .in_function(&function.name)
+                .at_instruction(index)
+        };
+        // Instructions are parsed on their own rather than as a `Code`, so
+        // that the error says what is wrong instead of matching no variant
+        let parsed = if code.get("label").is_some() {
+            serde_json::from_value::<Code>(code.clone())
+        } else {
+            serde_json::from_value::<Instruction>(code.clone()).map(Code::Instruction)
+        };
+        match parsed {
+            Ok(code) => {
+                if let Code::Instruction(Instruction {
+                    op: Operation::Other(op),
+                    ..
+                }) = &code
+                {
+                    diagnostics.push(diagnostic(format!("unknown operation {op}")));
+                }
+                function.instrs.push(code);
+            }
+            Err(err) => {
+                diagnostics.push(diagnostic(format!("malformed instruction: {err}")));
+                function.instrs.push(Code::Instruction(placeholder(&code)));
+            }
+        }
+    }
+
+    Ok(function)
+}
+
+/// Takes the value, leaving an empty array in its place
+fn take_array(value: &mut Value) -> Value {
+    std::mem::replace(value, Value::Array(Vec::new()))
+}
+
+/// Returns the placeholder of a malformed instruction, keeping its opcode,
+/// arguments and destination when they are readable
+fn placeholder(code: &Value) -> Instruction {
+    let op = code.get("op").and_then(Value::as_str).unwrap_or("invalid");
+    Instruction {
+        op: Operation::Other(op.to_string()),
+        args: code
+            .get("args")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .map(Into::into)
+            .collect(),
+        dest: code.get("dest").and_then(Value::as_str).map(Into::into),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::{BrilProgram, Code, Operation};
+
+    #[test]
+    fn test_from_json_lenient() {
+        // Given
+        let json = r#"{
+            "functions": [
+                {
+                    "name": "main",
+                    "instrs": [
+                        { "op": "const", "dest": "a", "type": "int", "value": 1 },
+                        { "op": "addd", "dest": "b", "type": "int", "args": ["a", "a"] },
+                        { "op": "add", "dest": "c", "type": "integer", "args": ["a", "b"] },
+                        { "label": "end" },
+                        { "op": "print", "args": ["c"] }
+                    ]
+                },
+                { "name": 3, "instrs": [] }
+            ]
+        }"#;
+
+        // When
+        let (program, diagnostics) = BrilProgram::from_json_lenient(json).unwrap();
+
+        // Then
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|d| d.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "function main, instruction 1: unknown operation addd",
+                "function main, instruction 2: malformed instruction: unknown variant `integer`, expected one of `int`, `bool`, `float`, `char`",
+                "function 1 is malformed: invalid type: integer `3`, expected a string",
+            ]
+        );
+        assert_eq!(program.functions.len(), 1);
+        let instrs = &program.functions[0].instrs;
+        assert_eq!(instrs.len(), 5);
+        let Code::Instruction(placeholder) = &instrs[2] else {
+            panic!("expected an instruction");
+        };
+        assert_eq!(placeholder.op, Operation::Other("add".to_string()));
+        assert_eq!(placeholder.args, ["a", "b"]);
+    }
+
+    #[test]
+    fn test_from_json_lenient_invalid_json() {
+        // Given
+        let json = r#"{ "functions": [ "#;
+
+        // When
+        let result = BrilProgram::from_json_lenient(json);
+
+        // Then
+        assert!(result.is_err());
+    }
+}
@@ -1,4 +1,35 @@
+#[cfg(feature = "binary")]
+pub mod binary;
+pub mod builder;
+pub mod canonicalize;
+pub mod cfg;
+pub mod compare;
+pub mod cost;
+pub mod diagnostic;
+pub mod diff;
+pub mod driver;
+pub mod generate;
+pub mod infer;
+pub mod lenient;
+pub mod link;
+pub mod migrate;
+pub mod pass;
+pub mod rename;
+pub mod stats;
+pub mod stream;
+pub mod symbol;
+pub mod testing;
+pub mod text;
 pub mod types;
+pub mod value;
+pub mod verify;
+pub mod visit;
+
+pub use infer::infer_types;
+
+/// Re-exported for the JSON fields built by the macros
+#[doc(hidden)]
+pub use serde_json;
 
 /// Util macro in under to check if all value are none
 #[macro_export]
@@ -0,0 +1,153 @@
+//! Contains the linking of several programs into a single one.
+
+use crate::types::{BrilProgram, Code, Function};
+use eyre::eyre;
+use std::collections::{HashMap, HashSet};
+
+impl BrilProgram {
+    /// Links the programs into a single one, keeping the order of the
+    /// functions.
+    ///
+    /// A function defined in several programs is a helper internal to each
+    /// of them: the later definitions are renamed to `name.N` and the calls
+    /// of their program are updated. Calls to a function the program doesn't
+    /// define are resolved to the first definition. Only one program can
+    /// define `main`, and a program can't define a function twice.
+    pub fn link(programs: impl IntoIterator<Item = BrilProgram>) -> eyre::Result<BrilProgram> {
+        let mut programs = programs.into_iter().collect::<Vec<_>>();
+
+        let mains = programs
+            .iter()
+            .filter(|p| p.functions.iter().any(|f| f.name == "main"))
+            .count();
+        if mains > 1 {
+            return Err(eyre!("main is defined in {mains} programs"));
+        }
+
+        let mut taken = programs
+            .iter()
+            .flat_map(|p| p.functions.iter().map(|f| f.name.clone()))
+            .collect::<HashSet<_>>();
+        let mut defined = HashSet::new();
+        for program in &mut programs {
+            let mut renames = HashMap::new();
+            let mut names = HashSet::new();
+            for function in &program.functions {
+                if !names.insert(function.name.as_str()) {
+                    return Err(eyre!("function {} is defined twice", function.name));
+                }
+                if !defined.insert(function.name.clone()) {
+                    let name = fresh_name(&function.name, &taken);
+                    taken.insert(name.clone());
+                    renames.insert(function.name.clone(), name);
+                }
+            }
+            for function in &mut program.functions {
+                rename_function(function, &renames);
+            }
+        }
+
+        let mut linked = BrilProgram::new(Vec::new());
+        for program in programs {
+            linked.functions.extend(program.functions);
+            linked.extra.extend(program.extra);
+        }
+
+        Ok(linked)
+    }
+}
+
+/// Returns the first `name.N` which isn't taken
+fn fresh_name(name: &str, taken: &HashSet<String>) -> String {
+    (1..)
+        .map(|n| format!("{name}.{n}"))
+        .find(|n| !taken.contains(n))
+        .expect("unbounded range")
+}
+
+/// Renames the function and the calls it makes
+fn rename_function(function: &mut Function, renames: &HashMap<String, String>) {
+    if let Some(name) = renames.get(&function.name) {
+        function.name = name.clone();
+    }
+    for code in &mut function.instrs {
+        let Code::Instruction(i) = code else {
+            continue;
+        };
+        for func in i.funcs.iter_mut().flatten() {
+            if let Some(name) = renames.get(func) {
+                *func = name.clone();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::text::parse_program;
+    use crate::types::BrilProgram;
+
+    #[test]
+    fn test_link() {
+        // Given
+        let main = parse_program(
+            r#"
+            @main {
+              a: int = call @helper;
+              b: int = call @util;
+            }
+            @helper {
+              ret;
+            }
+            "#,
+        )
+        .unwrap();
+        let utils = parse_program(
+            r#"
+            @util {
+              r: int = call @helper;
+              ret r;
+            }
+            @helper {
+              ret;
+            }
+            "#,
+        )
+        .unwrap();
+
+        // When
+        let linked = BrilProgram::link([main, utils]).unwrap();
+
+        // Then
+        let names = linked
+            .functions
+            .iter()
+            .map(|f| f.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["main", "helper", "util", "helper.1"]);
+        assert_eq!(
+            linked.function("util").unwrap().to_string(),
+            "@util {\n  r: int = call @helper.1;\n  ret r;\n}\n"
+        );
+        assert_eq!(
+            linked.function("main").unwrap().to_string(),
+            "@main {\n  a: int = call @helper;\n  b: int = call @util;\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_link_duplicates() {
+        // Given
+        let a = parse_program("@main { ret; }").unwrap();
+        let b = parse_program("@main { ret; }").unwrap();
+        let twice = parse_program("@f { ret; } @f { ret; }").unwrap();
+
+        // When
+        let linked = BrilProgram::link([a.clone(), b]);
+        let linked_twice = BrilProgram::link([a, twice]);
+
+        // Then
+        assert!(linked.is_err());
+        assert!(linked_twice.is_err());
+    }
+}
@@ -0,0 +1,238 @@
+//! Contains the upgrade of programs saved with an older version of the IR.
+//!
+//! The versions are:
+//!     - 0: the first form of the IR, where constants are unsigned integers
+//!       (booleans being saved as 0 or 1) and the labels of `br` and `jmp`
+//!       are saved in their `args`
+//!     - 1: the current form, matching the Bril JSON format
+
+use crate::types::BrilProgram;
+use eyre::eyre;
+use serde_json::{Map, Value};
+
+/// The current version of the IR
+pub const IR_VERSION: u32 = 1;
+
+/// A migration upgrades a serialized program to the next version
+type Migration = fn(&mut Value) -> eyre::Result<()>;
+
+/// The migrations, the one at index N upgrading from version N to N + 1
+const MIGRATIONS: [Migration; IR_VERSION as usize] = [literals_and_labels];
+
+/// Deserializes a program, upgrading it from the version it declares.
+/// The first version didn't save a version, so the programs without one are
+/// in the first version if they have its shape, see [`is_first_version`],
+/// and in the current version otherwise.
+pub fn from_value(mut value: Value) -> eyre::Result<BrilProgram> {
+    let version = match value.get("version") {
+        None if is_first_version(&value) => 0,
+        None => IR_VERSION,
+        Some(v) => v
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or_else(|| eyre!("invalid IR version {v}"))?,
+    };
+    migrate(&mut value, version)?;
+    Ok(serde_json::from_value(value)?)
+}
+
+/// Deserializes a program from a JSON string, see [`from_value`]
+pub fn from_str(s: &str) -> eyre::Result<BrilProgram> {
+    from_value(serde_json::from_str(s)?)
+}
+
+/// Upgrades a serialized program from the provided version to the current
+/// one. This allows loading artifacts saved without a version by an older
+/// version of the crate.
+pub fn migrate(value: &mut Value, from: u32) -> eyre::Result<()> {
+    if from > IR_VERSION {
+        return Err(eyre!(
+            "IR version {from} is newer than the supported version {IR_VERSION}"
+        ));
+    }
+    for migration in &MIGRATIONS[from as usize..] {
+        migration(value)?;
+    }
+
+    let program = value
+        .as_object_mut()
+        .ok_or_else(|| eyre!("expected a program object"))?;
+    if from != IR_VERSION || program.contains_key("version") {
+        program.insert("version".to_string(), IR_VERSION.into());
+    }
+
+    Ok(())
+}
+
+/// Returns the instructions of the serialized program
+fn instructions(value: &mut Value) -> impl Iterator<Item = &mut Map<String, Value>> {
+    value
+        .get_mut("functions")
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten()
+        .filter_map(|f| f.get_mut("instrs").and_then(Value::as_array_mut))
+        .flatten()
+        .filter_map(Value::as_object_mut)
+        // Labels are the only entries with a `label` key
+        .filter(|i| !i.contains_key("label"))
+}
+
+/// Returns whether the serialized program has an instruction which is only
+/// valid in version 0: a `br` or `jmp` with its labels in its `args`, or a
+/// boolean constant with an integer value
+fn is_first_version(value: &Value) -> bool {
+    value
+        .get("functions")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|f| f.get("instrs").and_then(Value::as_array))
+        .flatten()
+        .filter_map(Value::as_object)
+        .any(|i| match i.get("op").and_then(Value::as_str) {
+            Some("br" | "jmp") => !i.contains_key("labels") && i.contains_key("args"),
+            Some("const") => {
+                i.get("type").and_then(Value::as_str) == Some("bool")
+                    && i.get("value").is_some_and(Value::is_u64)
+            }
+            _ => false,
+        })
+}
+
+/// Migrates from version 0 to 1: boolean constants get a boolean value and
+/// the labels of `br` and `jmp` move from their `args` to their `labels`
+fn literals_and_labels(value: &mut Value) -> eyre::Result<()> {
+    for instruction in instructions(value) {
+        let op = instruction
+            .get("op")
+            .and_then(Value::as_str)
+            .map(str::to_string);
+        match op.as_deref() {
+            Some("const") if instruction.get("type").and_then(Value::as_str) == Some("bool") => {
+                let Some(v) = instruction.get("value").and_then(Value::as_u64) else {
+                    continue;
+                };
+                instruction.insert("value".to_string(), Value::Bool(v != 0));
+            }
+            Some(op @ ("br" | "jmp")) if !instruction.contains_key("labels") => {
+                let skip = usize::from(op == "br");
+                let Some(Value::Array(args)) = instruction.get_mut("args") else {
+                    return Err(eyre!("{op} without labels nor args"));
+                };
+                if args.len() < skip {
+                    return Err(eyre!("br without condition"));
+                }
+                let labels = args.split_off(skip);
+                instruction.insert("labels".to_string(), Value::Array(labels));
+            }
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_str, from_value, migrate, IR_VERSION};
+    use crate::types::{BrilProgram, Code, Literal};
+    use serde_json::json;
+
+    #[test]
+    fn test_migrate_from_first_version() {
+        // Given
+        let mut value = json!({
+            "functions": [{
+                "name": "main",
+                "instrs": [
+                    { "op": "const", "dest": "c", "type": "bool", "value": 1 },
+                    { "op": "const", "dest": "n", "type": "int", "value": 2 },
+                    { "op": "br", "args": ["c", "left", "right"] },
+                    { "label": "left" },
+                    { "op": "jmp", "args": ["right"] },
+                    { "label": "right" },
+                    { "op": "print", "args": ["n"] }
+                ]
+            }]
+        });
+
+        // When
+        migrate(&mut value, 0).unwrap();
+        let program = from_value(value).unwrap();
+
+        // Then
+        assert_eq!(program.version, Some(IR_VERSION));
+        assert_eq!(
+            program.to_string(),
+            r#"@main {
+  c: bool = const true;
+  n: int = const 2;
+  br c .left .right;
+.left:
+  jmp .right;
+.right:
+  print n;
+}
+"#
+        );
+    }
+
+    /// Returns the value of the first instruction of the program
+    fn value(program: &BrilProgram) -> Option<Literal> {
+        match &program.functions[0].instrs[0] {
+            Code::Instruction(i) => i.value,
+            Code::Label { .. } => None,
+        }
+    }
+
+    #[test]
+    fn test_from_unversioned_first_version() {
+        // Given
+        let old = r#"{ "functions": [{ "name": "main", "instrs": [
+            { "op": "const", "dest": "c", "type": "bool", "value": 1 },
+            { "op": "br", "args": ["c", "left", "right"] },
+            { "label": "left" },
+            { "op": "jmp", "args": ["right"] },
+            { "label": "right" },
+            { "op": "ret" }
+        ] }] }"#;
+        let labels_only = r#"{ "functions": [{ "name": "main", "instrs": [
+            { "op": "jmp", "args": ["end"] },
+            { "label": "end" }
+        ] }] }"#;
+
+        // When
+        let old = from_str(old).unwrap();
+        let labels_only = from_str(labels_only).unwrap();
+
+        // Then
+        assert_eq!(old.version, Some(IR_VERSION));
+        assert_eq!(value(&old), Some(Literal::Bool(true)));
+        assert!(old.to_string().contains("br c .left .right;"));
+        assert!(old.to_string().contains("jmp .right;"));
+        assert_eq!(labels_only.to_string(), "@main {\n  jmp .end;\n.end:\n}\n");
+    }
+
+    #[test]
+    fn test_from_versioned() {
+        // Given
+        let old = r#"{ "version": 0, "functions": [{ "name": "main", "instrs": [
+            { "op": "const", "dest": "b", "type": "bool", "value": 0 }
+        ] }] }"#;
+        let current = r#"{ "functions": [{ "name": "main", "instrs": [
+            { "op": "const", "dest": "b", "type": "int", "value": 0 }
+        ] }] }"#;
+        let future = r#"{ "version": 99, "functions": [] }"#;
+
+        // When
+        let old = from_str(old).unwrap();
+        let current = from_str(current).unwrap();
+        let future = from_str(future);
+
+        // Then
+        assert_eq!(value(&old), Some(Literal::Bool(false)));
+        assert_eq!(current.version, None);
+        assert_eq!(value(&current), Some(Literal::Int(0)));
+        assert!(future.is_err());
+    }
+}
@@ -0,0 +1,243 @@
+//! Contains the [`Pass`] trait implemented by the optimisations, and the
+//! [`PassRegistry`] from which a pipeline looks them up by name.
+//!
+//! Passes are usually implemented with `#[derive(Pass)]` from `bril-macros`,
+//! which only needs the function running the pass on a block or a function.
+//! The derived passes are also registered in an inventory, from which
+//! [`PassRegistry::with_builtin`] collects them.
+
+use crate::cfg::{join_blocks, split_blocks};
+use crate::types::{Block, BrilProgram, Function};
+use eyre::eyre;
+
+/// Re-exported for the implementations generated by `#[derive(Pass)]`
+pub use eyre::Result;
+/// Re-exported for the registrations generated by `#[derive(Pass)]`
+pub use inventory;
+
+/// An optimisation pass. Block passes only implement [`Pass::run_on_block`],
+/// which the default [`Pass::run_on_function`] calls on each basic block.
+pub trait Pass: Sync {
+    /// The name under which the pass is registered
+    fn name(&self) -> &'static str;
+
+    /// Runs the pass on a basic block, which isn't modified by default
+    fn run_on_block(&self, block: Block) -> eyre::Result<Block> {
+        Ok(block)
+    }
+
+    /// Runs the pass on each basic block of the function, see [`for_each_block`]
+    fn run_on_function(&self, function: &mut Function) -> eyre::Result<()> {
+        for_each_block(function, |block| {
+            *block = self.run_on_block(std::mem::take(block))?;
+            Ok(())
+        })
+    }
+
+    /// Runs the pass on each function of the program
+    fn run_on_program(&self, program: &mut BrilProgram) -> eyre::Result<()> {
+        program
+            .functions
+            .iter_mut()
+            .try_for_each(|function| self.run_on_function(function))
+    }
+}
+
+/// Applies the function on each basic block of the function, which is
+/// modified in place, see [`split_blocks`]. The labels are kept, and the
+/// instructions are moved in and out of the blocks, so that none is cloned.
+pub fn for_each_block<E>(
+    function: &mut Function,
+    f: impl FnMut(&mut Block) -> Result<(), E>,
+) -> Result<(), E> {
+    let mut blocks = split_blocks(std::mem::take(&mut function.instrs));
+    let result = blocks
+        .iter_mut()
+        .map(|block| &mut block.instrs)
+        .filter(|instrs| !instrs.is_empty())
+        .try_for_each(f);
+    function.instrs = join_blocks(blocks);
+    result
+}
+
+/// Converts the return values of the functions wrapped by `#[derive(Pass)]`,
+/// so that both fallible and infallible functions can be used
+pub trait PassOutput<T> {
+    fn into_result(self) -> eyre::Result<T>;
+}
+
+impl<T> PassOutput<T> for T {
+    fn into_result(self) -> eyre::Result<T> {
+        Ok(self)
+    }
+}
+
+impl<T> PassOutput<T> for eyre::Result<T> {
+    fn into_result(self) -> eyre::Result<T> {
+        self
+    }
+}
+
+/// A pass registered by `#[derive(Pass)]`, built by
+/// [`PassRegistry::with_builtin`]
+pub struct Registration {
+    new: fn() -> Box<dyn Pass>,
+}
+
+impl Registration {
+    pub const fn new(new: fn() -> Box<dyn Pass>) -> Self {
+        Self { new }
+    }
+}
+
+inventory::collect!(Registration);
+
+/// The passes available to a pipeline, in their registration order
+#[derive(Default)]
+pub struct PassRegistry {
+    passes: Vec<Box<dyn Pass>>,
+}
+
+impl PassRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the registry holding the passes derived with `#[derive(Pass)]`
+    /// in the crates linked in the program, ordered by name. Fails if two of
+    /// them have the same name.
+    pub fn with_builtin() -> eyre::Result<Self> {
+        let mut passes = inventory::iter::<Registration>
+            .into_iter()
+            .map(|registration| (registration.new)())
+            .collect::<Vec<_>>();
+        passes.sort_by_key(|pass| pass.name());
+
+        let mut registry = Self::new();
+        for pass in passes {
+            registry.register_boxed(pass)?;
+        }
+        Ok(registry)
+    }
+
+    /// Registers the pass, failing if a pass with the same name already is
+    pub fn register<P: Pass + 'static>(&mut self, pass: P) -> eyre::Result<()> {
+        self.register_boxed(Box::new(pass))
+    }
+
+    fn register_boxed(&mut self, pass: Box<dyn Pass>) -> eyre::Result<()> {
+        if self.get(pass.name()).is_some() {
+            return Err(eyre!("pass {} is already registered", pass.name()));
+        }
+        self.passes.push(pass);
+        Ok(())
+    }
+
+    /// Returns the pass registered under the name
+    pub fn get(&self, name: &str) -> Option<&dyn Pass> {
+        self.passes
+            .iter()
+            .find(|pass| pass.name() == name)
+            .map(Box::as_ref)
+    }
+
+    /// Returns the names of the registered passes
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.passes.iter().map(|pass| pass.name())
+    }
+
+    /// Runs the passes with the given names on the program, in order
+    pub fn run(&self, names: &[&str], program: &mut BrilProgram) -> eyre::Result<()> {
+        for name in names {
+            let pass = self.get(name).ok_or_else(|| eyre!("unknown pass {name}"))?;
+            pass.run_on_program(program)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Pass, PassRegistry};
+    use crate::text::parse_program;
+    use crate::types::{Block, Operation};
+
+    /// Removes the instructions following a print in its block
+    struct Truncate;
+
+    impl Pass for Truncate {
+        fn name(&self) -> &'static str {
+            "truncate"
+        }
+
+        fn run_on_block(&self, mut block: Block) -> eyre::Result<Block> {
+            if let Some(index) = block.iter().position(|i| i.op == Operation::Print) {
+                block.truncate(index + 1);
+            }
+            Ok(block)
+        }
+    }
+
+    #[test]
+    fn test_run_on_function() {
+        // Given
+        let mut program = parse_program(
+            r#"
+            @main {
+              a: int = const 1;
+              print a;
+              b: int = const 2;
+            .next:
+              print a;
+              jmp .end;
+              c: int = const 3;
+            .end:
+              ret;
+            }
+            "#,
+        )
+        .unwrap();
+
+        // When
+        Truncate.run_on_function(&mut program.functions[0]).unwrap();
+
+        // Then
+        let expected = parse_program(
+            r#"
+            @main {
+              a: int = const 1;
+              print a;
+            .next:
+              print a;
+              c: int = const 3;
+            .end:
+              ret;
+            }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(program, expected);
+    }
+
+    #[test]
+    fn test_pass_registry() {
+        // Given
+        let mut registry = PassRegistry::new();
+        registry.register(Truncate).unwrap();
+        let mut program = parse_program("@main { a: int = const 1; print a; print a; }").unwrap();
+
+        // When
+        let duplicate = registry.register(Truncate);
+        registry.run(&["truncate"], &mut program).unwrap();
+        let unknown = registry.run(&["dce"], &mut program);
+
+        // Then
+        assert_eq!(
+            duplicate.unwrap_err().to_string(),
+            "pass truncate is already registered"
+        );
+        assert_eq!(unknown.unwrap_err().to_string(), "unknown pass dce");
+        assert_eq!(registry.names().collect::<Vec<_>>(), ["truncate"]);
+        assert_eq!(program.functions[0].instrs.len(), 2);
+    }
+}
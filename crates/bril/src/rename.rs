@@ -0,0 +1,206 @@
+//! Contains the helpers to introduce new variables and rename
+//! existing ones in a function.
+
+use crate::types::{Code, Function, Var};
+use std::collections::{HashMap, HashSet};
+
+/// Generator of variable names which don't clash with the names
+/// already used in a function, or with the names it handed out.
+#[derive(Debug, Clone, Default)]
+pub struct FreshNames {
+    taken: HashSet<Var>,
+    counters: HashMap<String, usize>,
+}
+
+impl FreshNames {
+    /// Returns a generator avoiding the arguments and the
+    /// variables of the function
+    pub fn new(function: &Function) -> Self {
+        let mut names = Self::default();
+        names.taken.extend(function.args.iter().map(|a| a.name));
+        for code in &function.instrs {
+            if let Code::Instruction(i) = code {
+                names.taken.extend(i.defs().copied());
+                names.taken.extend(i.uses().copied());
+            }
+        }
+        names
+    }
+
+    /// Returns a generator avoiding the provided names
+    pub fn with_names(names: impl IntoIterator<Item = Var>) -> Self {
+        Self {
+            taken: names.into_iter().collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Returns a new `v.N` variable
+    pub fn next_var(&mut self) -> Var {
+        self.fresh("v")
+    }
+
+    /// Returns a new `base.N` variable, `N` counting up from zero
+    /// for each base
+    pub fn fresh(&mut self, base: &str) -> Var {
+        let counter = self.counters.entry(base.to_string()).or_default();
+        loop {
+            let name = Var::from(format!("{base}.{counter}"));
+            *counter += 1;
+            if self.taken.insert(name) {
+                return name;
+            }
+        }
+    }
+}
+
+/// Renames the variables of the function, arguments included. The
+/// variables missing from the mapping are left untouched.
+pub fn rename_vars(function: &mut Function, map: &HashMap<Var, Var>) {
+    let rename = |var: &mut Var| {
+        if let Some(name) = map.get(var) {
+            *var = *name;
+        }
+    };
+
+    for arg in &mut function.args {
+        rename(&mut arg.name);
+    }
+    for code in &mut function.instrs {
+        let Code::Instruction(i) = code else {
+            continue;
+        };
+        if let Some(dest) = i.dest.as_mut() {
+            rename(dest);
+        }
+        i.args_mut().iter_mut().for_each(rename);
+    }
+}
+
+impl Function {
+    /// Returns a copy of the function where the arguments, the defined
+    /// variables and the labels are prefixed with `prefix`, along with
+    /// their uses. The variables used without being defined in the
+    /// function are left untouched.
+    pub fn clone_with_prefix(&self, prefix: &str) -> Function {
+        let mut function = self.clone();
+
+        let mut vars = function.args.iter().map(|a| a.name).collect::<HashSet<_>>();
+        let mut labels = HashSet::new();
+        for code in &function.instrs {
+            match code {
+                Code::Label { label } => {
+                    labels.insert(label.clone());
+                }
+                Code::Instruction(i) => vars.extend(i.defs().copied()),
+            }
+        }
+
+        let renames = vars
+            .into_iter()
+            .map(|var| (var, Var::from(format!("{prefix}{var}"))))
+            .collect::<HashMap<_, _>>();
+        rename_vars(&mut function, &renames);
+
+        let rename_label = |label: &mut String| {
+            if labels.contains(label) {
+                *label = format!("{prefix}{label}");
+            }
+        };
+        for code in &mut function.instrs {
+            match code {
+                Code::Label { label } => rename_label(label),
+                Code::Instruction(i) => i.labels.iter_mut().flatten().for_each(rename_label),
+            }
+        }
+
+        function
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rename_vars, FreshNames};
+    use crate::text::parse_program;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_fresh_names() {
+        // Given
+        let program =
+            parse_program("@main(v.0: int) { v.2: int = add v.0 v.0; x.0: int = id v.2; }")
+                .unwrap();
+        let mut names = FreshNames::new(&program.functions[0]);
+
+        // When
+        let fresh = [
+            names.next_var(),
+            names.next_var(),
+            names.fresh("x"),
+            names.next_var(),
+        ];
+
+        // Then
+        assert_eq!(fresh, ["v.1", "v.3", "x.1", "v.4"]);
+    }
+
+    #[test]
+    fn test_rename_vars() {
+        // Given
+        let mut function = parse_program("@main(a: int) { b: int = add a a; print b c; }")
+            .unwrap()
+            .functions
+            .remove(0);
+        let map = HashMap::from([("a".into(), "x".into()), ("b".into(), "y".into())]);
+
+        // When
+        rename_vars(&mut function, &map);
+
+        // Then
+        assert_eq!(
+            function.to_string(),
+            "@main(x: int) {\n  y: int = add x x;\n  print y c;\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_clone_with_prefix() {
+        // Given
+        let function = parse_program(
+            r#"
+            @f(n: int): int {
+              one: int = const 1;
+              c: bool = lt n one;
+              br c .done .else;
+            .else:
+              r: int = add n g;
+              jmp .done;
+            .done:
+              ret n;
+            }
+            "#,
+        )
+        .unwrap()
+        .functions
+        .remove(0);
+
+        // When
+        let copy = function.clone_with_prefix("f.0.");
+
+        // Then
+        assert_eq!(
+            copy.to_string(),
+            r#"@f(f.0.n: int): int {
+  f.0.one: int = const 1;
+  f.0.c: bool = lt f.0.n f.0.one;
+  br f.0.c .f.0.done .f.0.else;
+.f.0.else:
+  f.0.r: int = add f.0.n g;
+  jmp .f.0.done;
+.f.0.done:
+  ret f.0.n;
+}
+"#
+        );
+    }
+}
@@ -0,0 +1,143 @@
+//! Contains the statistics of a program, used to compare
+//! a program before and after optimization.
+
+use crate::types::{BrilProgram, Code, Function, Operation};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::{Display, Formatter};
+
+/// Statistics of a whole program
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Stats {
+    /// The total number of instructions, labels excluded
+    pub instructions: usize,
+    /// The number of instructions of each operation
+    pub operations: BTreeMap<String, usize>,
+    /// The number of `const` instructions of each literal
+    pub constants: BTreeMap<String, usize>,
+    pub functions: Vec<FunctionStats>,
+}
+
+/// Statistics of a single function
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct FunctionStats {
+    pub name: String,
+    pub instructions: usize,
+    pub labels: usize,
+    /// The number of distinct variables, arguments included
+    pub variables: usize,
+}
+
+/// Collects the statistics of the program
+pub fn collect(program: &BrilProgram) -> Stats {
+    let mut stats = Stats::default();
+
+    for function in &program.functions {
+        for code in &function.instrs {
+            let Code::Instruction(i) = code else {
+                continue;
+            };
+            stats.instructions += 1;
+            *stats.operations.entry(i.op.to_string()).or_default() += 1;
+            if let (Operation::Const, Some(value)) = (&i.op, i.value) {
+                *stats.constants.entry(value.to_string()).or_default() += 1;
+            }
+        }
+        stats.functions.push(function_stats(function));
+    }
+
+    stats
+}
+
+fn function_stats(function: &Function) -> FunctionStats {
+    let mut stats = FunctionStats {
+        name: function.name.clone(),
+        ..Default::default()
+    };
+
+    let mut variables = function.args.iter().map(|a| a.name).collect::<HashSet<_>>();
+    for code in &function.instrs {
+        match code {
+            Code::Label { .. } => stats.labels += 1,
+            Code::Instruction(i) => {
+                stats.instructions += 1;
+                variables.extend(i.defs().copied());
+                variables.extend(i.uses().copied());
+            }
+        }
+    }
+    stats.variables = variables.len();
+
+    stats
+}
+
+impl Display for Stats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "instructions: {}", self.instructions)?;
+        for function in &self.functions {
+            writeln!(
+                f,
+                "  @{}: {} instructions, {} labels, {} variables",
+                function.name, function.instructions, function.labels, function.variables
+            )?;
+        }
+        writeln!(f, "operations:")?;
+        for (op, count) in &self.operations {
+            writeln!(f, "  {op}: {count}")?;
+        }
+        writeln!(f, "constants:")?;
+        for (value, count) in &self.constants {
+            writeln!(f, "  {value}: {count}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{collect, FunctionStats};
+    use crate::text::parse_program;
+
+    #[test]
+    fn test_collect() {
+        // Given
+        let program = parse_program(
+            r#"
+            @main(n: int) {
+              a: int = const 1;
+              b: int = const 1;
+              c: int = add a b;
+            .end:
+              print c n;
+            }
+            @other {
+              t: bool = const true;
+              ret;
+            }
+            "#,
+        )
+        .unwrap();
+
+        // When
+        let stats = collect(&program);
+
+        // Then
+        assert_eq!(stats.instructions, 6);
+        assert_eq!(stats.operations["const"], 3);
+        assert_eq!(stats.operations["add"], 1);
+        assert_eq!(stats.constants["1"], 2);
+        assert_eq!(stats.constants["true"], 1);
+        assert_eq!(
+            stats.functions[0],
+            FunctionStats {
+                name: "main".to_string(),
+                instructions: 4,
+                labels: 1,
+                variables: 4,
+            }
+        );
+        assert!(stats
+            .to_string()
+            .starts_with("instructions: 6\n  @main: 4 instructions, 1 labels, 4 variables\n"));
+    }
+}
@@ -0,0 +1,229 @@
+//! Contains the lazy reading of the functions of a JSON program, so that
+//! very large programs can be processed without being loaded at once.
+
+use crate::types::Function;
+use eyre::eyre;
+use serde::Deserialize;
+use std::io::{BufRead, BufReader, Read};
+
+/// Returns an iterator over the functions of the JSON program read from
+/// the reader. Each function is parsed when the iterator reaches it, and
+/// the iteration stops after the first error.
+///
+/// The other fields of the program are skipped, and anything following
+/// the `functions` array isn't read.
+pub fn read_functions<R: Read>(reader: R) -> impl Iterator<Item = eyre::Result<Function>> {
+    Functions {
+        reader: BufReader::new(reader),
+        state: State::Program,
+    }
+}
+
+/// The position of the iterator in the program
+enum State {
+    /// Before the `functions` array
+    Program,
+    /// In the `functions` array, before its first element
+    First,
+    /// In the `functions` array, after an element
+    Next,
+    /// After the `functions` array or an error
+    Done,
+}
+
+struct Functions<R> {
+    reader: BufReader<R>,
+    state: State,
+}
+
+impl<R: Read> Iterator for Functions<R> {
+    type Item = eyre::Result<Function>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let next = match self.state {
+            State::Done => return None,
+            State::Program => self.find_functions().and_then(|found| {
+                if !found {
+                    return Ok(None);
+                }
+                self.state = State::First;
+                self.next_function()
+            }),
+            State::First | State::Next => self.next_function(),
+        };
+
+        match next {
+            Ok(Some(function)) => {
+                self.state = State::Next;
+                Some(Ok(function))
+            }
+            Ok(None) => {
+                self.state = State::Done;
+                None
+            }
+            Err(err) => {
+                self.state = State::Done;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+impl<R: Read> Functions<R> {
+    /// Moves the reader into the `functions` array, skipping the fields
+    /// before it. Returns false if the program has no functions.
+    fn find_functions(&mut self) -> eyre::Result<bool> {
+        self.expect(b'{')?;
+        if self.peek()? == Some(b'}') {
+            return Ok(false);
+        }
+        loop {
+            let key = self.parse::<String>()?;
+            self.expect(b':')?;
+            if key == "functions" {
+                self.expect(b'[')?;
+                return Ok(true);
+            }
+            self.skip_value()?;
+            match self.peek()? {
+                Some(b',') => self.reader.consume(1),
+                Some(b'}') => return Ok(false),
+                _ => return Err(eyre!("expected , or }} after the value of {key}")),
+            }
+        }
+    }
+
+    /// Parses the next function of the array, none at its end
+    fn next_function(&mut self) -> eyre::Result<Option<Function>> {
+        let first = matches!(self.state, State::First);
+        match (first, self.peek()?) {
+            (_, Some(b']')) => {
+                self.reader.consume(1);
+                return Ok(None);
+            }
+            (false, Some(b',')) => self.reader.consume(1),
+            (false, _) => return Err(eyre!("expected , or ] after a function")),
+            _ => (),
+        }
+        self.parse().map(Some)
+    }
+
+    /// Skips the next JSON value. It isn't deserialized, as the reader
+    /// looks one byte past the numbers which would then be lost.
+    fn skip_value(&mut self) -> eyre::Result<()> {
+        self.peek()?;
+        let mut depth = 0usize;
+        let mut in_string = false;
+        let mut escaped = false;
+        loop {
+            let buffer = self.reader.fill_buf()?;
+            let Some(&byte) = buffer.first() else {
+                return Err(eyre!("unexpected end of input"));
+            };
+            if in_string {
+                match byte {
+                    _ if escaped => escaped = false,
+                    b'\\' => escaped = true,
+                    b'"' => in_string = false,
+                    _ => (),
+                }
+            } else {
+                match byte {
+                    b'"' => in_string = true,
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' if depth == 0 => return Ok(()),
+                    b'}' | b']' => depth -= 1,
+                    b',' if depth == 0 => return Ok(()),
+                    _ if byte.is_ascii_whitespace() && depth == 0 => return Ok(()),
+                    _ => (),
+                }
+            }
+            self.reader.consume(1);
+            if depth == 0 && !in_string && matches!(byte, b'"' | b'}' | b']') {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Parses the next JSON value, leaving the reader right after it. Only
+    /// used for strings and objects, which end without looking ahead.
+    fn parse<T: for<'de> Deserialize<'de>>(&mut self) -> eyre::Result<T> {
+        let mut deserializer = serde_json::Deserializer::from_reader(&mut self.reader);
+        Ok(T::deserialize(&mut deserializer)?)
+    }
+
+    /// Consumes the next non whitespace byte, which must be the expected one
+    fn expect(&mut self, expected: u8) -> eyre::Result<()> {
+        match self.peek()? {
+            Some(byte) if byte == expected => {
+                self.reader.consume(1);
+                Ok(())
+            }
+            Some(byte) => Err(eyre!("expected {}, got {}", expected as char, byte as char)),
+            None => Err(eyre!("expected {}, got end of input", expected as char)),
+        }
+    }
+
+    /// Skips the whitespaces and returns the next byte without consuming it
+    fn peek(&mut self) -> eyre::Result<Option<u8>> {
+        loop {
+            let buffer = self.reader.fill_buf()?;
+            let Some(&byte) = buffer.first() else {
+                return Ok(None);
+            };
+            if !byte.is_ascii_whitespace() {
+                return Ok(Some(byte));
+            }
+            self.reader.consume(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::read_functions;
+    use crate::generate::{generate, Config};
+
+    #[test]
+    fn test_read_functions() {
+        // Given
+        let program = generate(&Config {
+            functions: 4,
+            ..Default::default()
+        });
+        let mut json = serde_json::to_value(&program).unwrap();
+        // The fields sorted before the functions must be skipped
+        json["count"] = 4.into();
+        json["extension"] = serde_json::json!({ "functions": [], "note": "\"}" });
+        let json = serde_json::to_string_pretty(&json).unwrap();
+
+        // When
+        let functions = read_functions(json.as_bytes())
+            .collect::<eyre::Result<Vec<_>>>()
+            .unwrap();
+
+        // Then
+        assert_eq!(functions, program.functions);
+    }
+
+    #[test]
+    fn test_read_functions_errors() {
+        // Given
+        let empty = r#"{ "other": 1 }"#;
+        let truncated = r#"{ "functions": [{ "name": "main", "instrs": [] }, { "name": "#;
+        let missing_comma =
+            r#"{ "functions": [{ "name": "a", "instrs": [] } { "name": "b", "instrs": [] }] }"#;
+
+        // When
+        let empty = read_functions(empty.as_bytes()).count();
+        let truncated = read_functions(truncated.as_bytes()).collect::<Vec<_>>();
+        let missing_comma = read_functions(missing_comma.as_bytes()).collect::<Vec<_>>();
+
+        // Then
+        assert_eq!(empty, 0);
+        assert_eq!(truncated.len(), 2);
+        assert!(truncated[0].is_ok() && truncated[1].is_err());
+        assert_eq!(missing_comma.len(), 2);
+        assert!(missing_comma[1].is_err());
+    }
+}
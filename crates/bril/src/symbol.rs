@@ -0,0 +1,228 @@
+//! Contains the interned symbol used for the names in the program.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+use std::fmt::{Debug, Display, Formatter};
+use std::ops::Deref;
+use std::sync::{Mutex, OnceLock};
+
+/// An interned string. Symbols are cheap to copy, hash and compare,
+/// which avoids cloning strings each time a name is used by a pass.
+///
+/// The interned strings are never freed, the interner is expected
+/// to only contain the names of the processed programs.
+#[derive(Clone, Copy, Hash, PartialEq, Eq)]
+pub struct Symbol(u32);
+
+/// The global string table, mapping each interned string to its index.
+/// It is only locked to intern a string, the strings being read from
+/// [`STRINGS`].
+#[derive(Default)]
+struct Interner {
+    indices: HashMap<&'static str, u32>,
+}
+
+fn interner() -> &'static Mutex<Interner> {
+    static INTERNER: OnceLock<Mutex<Interner>> = OnceLock::new();
+    INTERNER.get_or_init(Default::default)
+}
+
+/// The interned strings by index, which are read without locking. They are
+/// stored in buckets doubling in size, each allocated once and never moved:
+/// the bucket `b` holds the `2^b` indices starting from `2^b - 1`.
+static STRINGS: [OnceLock<Box<[OnceLock<&'static str>]>>; 33] = [const { OnceLock::new() }; 33];
+
+/// Returns the bucket of [`STRINGS`] holding the index, and the position in it
+fn location(index: u32) -> (usize, usize) {
+    let n = u64::from(index) + 1;
+    let bucket = n.ilog2();
+    (bucket as usize, (n - (1 << bucket)) as usize)
+}
+
+impl Symbol {
+    /// Interns the string, returning the existing symbol if already interned
+    pub fn new(s: &str) -> Self {
+        let mut interner = interner().lock().expect("interner lock poisoned");
+        if let Some(index) = interner.indices.get(s) {
+            return Self(*index);
+        }
+
+        let s: &'static str = Box::leak(s.to_string().into_boxed_str());
+        let index = u32::try_from(interner.indices.len()).expect("too many interned strings");
+        let (bucket, position) = location(index);
+        let slots =
+            STRINGS[bucket].get_or_init(|| (0..1 << bucket).map(|_| OnceLock::new()).collect());
+        slots[position]
+            .set(s)
+            .expect("each index is only interned once");
+        interner.indices.insert(s, index);
+
+        Self(index)
+    }
+
+    /// Returns the interned string, without locking the interner
+    pub fn as_str(&self) -> &'static str {
+        let (bucket, position) = location(self.0);
+        STRINGS[bucket]
+            .get()
+            .and_then(|slots| slots[position].get())
+            .expect("the symbol is interned")
+    }
+}
+
+impl Default for Symbol {
+    fn default() -> Self {
+        Self::new("")
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for Symbol {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(s: &str) -> Self {
+        Self::new(s)
+    }
+}
+
+impl From<String> for Symbol {
+    fn from(s: String) -> Self {
+        Self::new(&s)
+    }
+}
+
+impl From<&String> for Symbol {
+    fn from(s: &String) -> Self {
+        Self::new(s)
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+/// Symbols are ordered by their string, which keeps the ordering
+/// independent of the interning order
+impl PartialOrd for Symbol {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Symbol {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        if self == other {
+            return std::cmp::Ordering::Equal;
+        }
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl Display for Symbol {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Debug for Symbol {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl Serialize for Symbol {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Symbol {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        Ok(Self::new(&s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{location, Symbol};
+
+    #[test]
+    fn test_interning() {
+        let a = Symbol::new("a");
+        let b = Symbol::from("b".to_string());
+
+        assert_eq!(a, Symbol::new("a"));
+        assert_ne!(a, b);
+        assert_eq!(a, "a");
+        assert_eq!(b.as_str(), "b");
+        assert_eq!(format!("{a} {b:?}"), "a \"b\"");
+        assert!(a < b);
+    }
+
+    #[test]
+    fn test_location() {
+        assert_eq!(location(0), (0, 0));
+        assert_eq!(location(1), (1, 0));
+        assert_eq!(location(2), (1, 1));
+        assert_eq!(location(3), (2, 0));
+        assert_eq!(location(6), (2, 3));
+        assert_eq!(location(u32::MAX - 1), (31, (1 << 31) - 1));
+        assert_eq!(location(u32::MAX), (32, 0));
+    }
+
+    #[test]
+    fn test_interning_threads() {
+        let symbols = std::thread::scope(|scope| {
+            let threads = (0..4)
+                .map(|thread| {
+                    scope.spawn(move || {
+                        (0..1000)
+                            .map(|i| Symbol::new(&format!("t{}", (thread * 500 + i) % 2000)))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect::<Vec<_>>();
+            threads
+                .into_iter()
+                .flat_map(|t| t.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+
+        for (index, symbol) in symbols.iter().enumerate() {
+            let (thread, i) = (index / 1000, index % 1000);
+            assert_eq!(symbol.as_str(), format!("t{}", (thread * 500 + i) % 2000));
+            assert_eq!(*symbol, Symbol::new(symbol.as_str()));
+        }
+    }
+
+    #[test]
+    fn test_serde() {
+        let symbols: Vec<Symbol> = serde_json::from_str(r#"["x", "v.1", "x"]"#).unwrap();
+
+        assert_eq!(symbols[0], symbols[2]);
+        assert_eq!(
+            serde_json::to_string(&symbols).unwrap(),
+            r#"["x","v.1","x"]"#
+        );
+    }
+}
@@ -0,0 +1,119 @@
+//! Contains the helpers used by the tests of the passes, such as the
+//! [`assert_block_eq`](crate::assert_block_eq) macro.
+
+use crate::types::Instruction;
+
+/// Returns the side-by-side diff of the blocks in the Bril text format, none
+/// if they are equal. The differing instructions are marked with `>`, and
+/// the first one is given in the header.
+///
+/// Instructions which only differ in fields that aren't printed, such as
+/// their position, are marked too.
+pub fn block_diff(left: &[Instruction], right: &[Instruction]) -> Option<String> {
+    if left == right {
+        return None;
+    }
+
+    let print = |block: &[Instruction], i: usize| block.get(i).map(ToString::to_string);
+    let len = left.len().max(right.len());
+    let rows = (0..len)
+        .map(|i| (left.get(i) != right.get(i), print(left, i), print(right, i)))
+        .collect::<Vec<_>>();
+    let width = rows
+        .iter()
+        .filter_map(|(_, left, _)| left.as_ref().map(|l| l.chars().count()))
+        .max()
+        .unwrap_or(0)
+        .max("left".len());
+    let first = rows
+        .iter()
+        .position(|(differs, _, _)| *differs)
+        .expect("the blocks differ");
+
+    let mut diff = format!(
+        "blocks differ at instruction {first}\n  {:width$} | right\n",
+        "left"
+    );
+    for (differs, left, right) in rows {
+        let marker = if differs { '>' } else { ' ' };
+        let left = left.unwrap_or_default();
+        let right = right.unwrap_or_default();
+        diff.push_str(format!("{marker} {left:width$} | {right}").trim_end());
+        diff.push('\n');
+    }
+
+    Some(diff)
+}
+
+/// Asserts that two blocks are equal, printing their side-by-side diff in
+/// the Bril text format otherwise, see [`block_diff`].
+#[macro_export]
+macro_rules! assert_block_eq {
+    ($left: expr, $right: expr $(,)?) => {
+        if let Some(diff) = $crate::testing::block_diff(&$left, &$right) {
+            panic!("assertion `left == right` failed: {diff}");
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::block_diff;
+    use crate::text::parse_program;
+    use crate::types::{Block, Code, Position};
+
+    fn block(text: &str) -> Block {
+        let program = parse_program(&format!("@main {{ {text} }}")).unwrap();
+        program.functions[0]
+            .instrs
+            .iter()
+            .filter_map(|code| match code {
+                Code::Instruction(instruction) => Some(instruction.clone()),
+                Code::Label { .. } => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_block_diff() {
+        // Given
+        let left = block("a: int = const 1; b: int = add a a; print b;");
+        let right = block("a: int = const 1; b: int = mul a a; print b; print a;");
+
+        // When
+        let diff = block_diff(&left, &right).unwrap();
+
+        // Then
+        assert_eq!(
+            diff,
+            "blocks differ at instruction 1\n\
+            \x20 left              | right\n\
+            \x20 a: int = const 1; | a: int = const 1;\n\
+            > b: int = add a a; | b: int = mul a a;\n\
+            \x20 print b;          | print b;\n\
+            >                   | print a;\n"
+        );
+    }
+
+    #[test]
+    fn test_block_diff_hidden_fields() {
+        // Given
+        let left = block("print a;");
+        let mut right = left.clone();
+        right[0].pos = Some(Position { row: 1, col: 1 });
+
+        // When
+        let equal = block_diff(&left, &left);
+        let diff = block_diff(&left, &right).unwrap();
+
+        // Then
+        assert_eq!(equal, None);
+        assert!(diff.contains("> print a; | print a;"));
+    }
+
+    #[test]
+    #[should_panic(expected = "blocks differ at instruction 0")]
+    fn test_assert_block_eq() {
+        assert_block_eq!(block("print a;"), block("print b;"));
+    }
+}
@@ -0,0 +1,479 @@
+//! Contains the parser and the printer for the human-readable Bril text format.
+//! The printer is exposed through the [`Display`] implementations of the IR.
+
+use crate::types::{
+    BrilProgram, Code, Function, FunctionArg, Instruction, Literal, Operation, Type,
+};
+use eyre::eyre;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// Parses a program written in the Bril text format into a [`BrilProgram`].
+pub fn parse_program(input: &str) -> eyre::Result<BrilProgram> {
+    let mut parser = Parser::new(lex(input));
+
+    let mut functions = Vec::new();
+    while !parser.is_empty() {
+        functions.push(parser.parse_function()?);
+    }
+
+    Ok(BrilProgram::new(functions))
+}
+
+/// Prints the program in the canonical Bril text format.
+pub fn emit_program(program: &BrilProgram) -> String {
+    program.to_string()
+}
+
+impl Display for BrilProgram {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for function in &self.functions {
+            write!(f, "{function}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Function {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "@{}", self.name)?;
+        if !self.args.is_empty() {
+            let args = self
+                .args
+                .iter()
+                .map(|a| format!("{}: {}", a.name, a.r#type))
+                .collect::<Vec<_>>();
+            write!(f, "({})", args.join(", "))?;
+        }
+        if let Some(ty) = self.r#type.as_ref() {
+            write!(f, ": {ty}")?;
+        }
+        writeln!(f, " {{")?;
+
+        for code in &self.instrs {
+            match code {
+                Code::Label { .. } => writeln!(f, "{code}")?,
+                Code::Instruction(_) => writeln!(f, "  {code}")?,
+            }
+        }
+
+        writeln!(f, "}}")
+    }
+}
+
+impl Display for Code {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Code::Label { label } => write!(f, ".{label}:"),
+            Code::Instruction(instruction) => write!(f, "{instruction}"),
+        }
+    }
+}
+
+/// Prints the instruction, including the terminating semicolon
+impl Display for Instruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(dest) = self.dest.as_ref() {
+            write!(f, "{dest}")?;
+            if let Some(ty) = self.r#type.as_ref() {
+                write!(f, ": {ty}")?;
+            }
+            write!(f, " = ")?;
+        }
+        write!(f, "{}", self.op)?;
+
+        for func in self.funcs.iter().flatten() {
+            write!(f, " @{func}")?;
+        }
+
+        if let Some(value) = self.value {
+            write!(f, " {value}")?;
+        }
+
+        for arg in self.args.iter() {
+            write!(f, " {arg}")?;
+        }
+
+        for label in self.labels.iter().flatten() {
+            write!(f, " .{label}")?;
+        }
+
+        write!(f, ";")?;
+
+        // The attributes are printed as a comment, so the output still parses
+        let attrs = self
+            .attrs
+            .iter()
+            .flatten()
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect::<Vec<_>>();
+        if !attrs.is_empty() {
+            write!(f, "  # {}", attrs.join(" "))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A token of the Bril text format
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Punct(char),
+}
+
+/// Characters which are always parsed as a single token
+const PUNCTUATION: &[char] = &['{', '}', '(', ')', ':', ';', '=', ','];
+
+/// Splits the input into tokens, keeping track of the line of each token.
+/// Comments start with a `#` and run until the end of the line. Character
+/// literals (`'a'`) are always kept as a single word.
+fn lex(input: &str) -> Vec<(usize, Token)> {
+    let mut tokens = Vec::new();
+
+    for (line, content) in input.lines().enumerate() {
+        let mut word = String::new();
+        let mut in_char = false;
+
+        for c in content.chars() {
+            if c == '\'' {
+                in_char = !in_char;
+                word.push(c);
+                continue;
+            }
+            if in_char {
+                word.push(c);
+                continue;
+            }
+            if c == '#' {
+                break;
+            }
+            if c.is_whitespace() || PUNCTUATION.contains(&c) {
+                if !word.is_empty() {
+                    tokens.push((line + 1, Token::Word(std::mem::take(&mut word))));
+                }
+                if !c.is_whitespace() {
+                    tokens.push((line + 1, Token::Punct(c)));
+                }
+                continue;
+            }
+            word.push(c);
+        }
+
+        if !word.is_empty() {
+            tokens.push((line + 1, Token::Word(word)));
+        }
+    }
+
+    tokens
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn new(tokens: Vec<(usize, Token)>) -> Self {
+        Self { tokens, pos: 0 }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.tokens.len()
+    }
+
+    /// Returns the line of the current token, or of the last
+    /// token if the end of the input was reached.
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .or(self.tokens.last())
+            .map(|(l, _)| *l)
+            .unwrap_or_default()
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, t)| t)
+    }
+
+    fn next(&mut self) -> eyre::Result<Token> {
+        let token = self
+            .peek()
+            .cloned()
+            .ok_or(eyre!("line {}: unexpected end of input", self.line()))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    /// Consumes the punctuation if it is the next token
+    fn eat(&mut self, punct: char) -> bool {
+        if self.peek() == Some(&Token::Punct(punct)) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, punct: char) -> eyre::Result<()> {
+        let line = self.line();
+        match self.next()? {
+            Token::Punct(c) if c == punct => Ok(()),
+            t => Err(eyre!("line {line}: expected '{punct}', got {t:?}")),
+        }
+    }
+
+    fn expect_word(&mut self) -> eyre::Result<String> {
+        let line = self.line();
+        match self.next()? {
+            Token::Word(w) => Ok(w),
+            t => Err(eyre!("line {line}: expected a word, got {t:?}")),
+        }
+    }
+
+    fn parse_type(&mut self) -> eyre::Result<Type> {
+        let line = self.line();
+        let ty = self.expect_word()?;
+        Type::from_str(&ty).map_err(|err| eyre!("line {line}: {err}"))
+    }
+
+    /// Parses a function of the form `@name[(arg: type, ...)][: type] { <code>* }`
+    fn parse_function(&mut self) -> eyre::Result<Function> {
+        let line = self.line();
+        let name = self.expect_word()?;
+        let name = name
+            .strip_prefix('@')
+            .ok_or(eyre!("line {line}: expected function name, got {name}"))?
+            .to_string();
+
+        let mut args = Vec::new();
+        if self.eat('(') {
+            while !self.eat(')') {
+                let name = self.expect_word()?;
+                self.expect(':')?;
+                let r#type = self.parse_type()?;
+                args.push(FunctionArg {
+                    name: name.into(),
+                    r#type,
+                });
+
+                // The last argument is not followed by a comma
+                if !self.eat(',') {
+                    self.expect(')')?;
+                    break;
+                }
+            }
+        }
+        let r#type = match self.eat(':') {
+            true => Some(self.parse_type()?),
+            false => None,
+        };
+        self.expect('{')?;
+
+        let mut instrs = Vec::new();
+        while !self.eat('}') {
+            instrs.push(self.parse_code()?);
+        }
+
+        Ok(Function {
+            name,
+            args,
+            r#type,
+            instrs,
+            extra: Default::default(),
+        })
+    }
+
+    /// Parses either a label (`.name:`) or an instruction
+    fn parse_code(&mut self) -> eyre::Result<Code> {
+        let line = self.line();
+        let word = self.expect_word()?;
+
+        if let Some(label) = word.strip_prefix('.') {
+            self.expect(':')?;
+            return Ok(Code::Label {
+                label: label.to_string(),
+            });
+        }
+
+        let mut instruction = Instruction::default();
+        let is_value_operation = matches!(self.peek(), Some(Token::Punct(':' | '=')));
+
+        let op = if is_value_operation {
+            // Value operation: `dest[: type] = op args;`
+            if self.eat(':') {
+                instruction.r#type = Some(self.parse_type()?);
+            }
+            self.expect('=')?;
+            instruction.dest = Some(word.into());
+            self.expect_word()?
+        } else {
+            // Effect operation: `op args;`
+            word
+        };
+        instruction.op = Operation::from_str(&op).map_err(|err| eyre!("line {line}: {err}"))?;
+
+        let mut args = Vec::new();
+        while !self.eat(';') {
+            args.push(self.expect_word()?);
+        }
+
+        if instruction.op == Operation::Const {
+            let [value] = args.as_slice() else {
+                return Err(eyre!("line {line}: expected a single value for const"));
+            };
+            let literal = match instruction.r#type {
+                Some(Type::Float) => value.parse().map(Literal::Float).ok(),
+                Some(Type::Bool) => value.parse().map(Literal::Bool).ok(),
+                Some(Type::Char) => parse_char(value),
+                Some(_) => value.parse().map(Literal::Int).ok(),
+                // Without a type, the literal is typed from its text
+                None => match value.as_str() {
+                    "true" | "false" => value.parse().map(Literal::Bool).ok(),
+                    v if v.starts_with('\'') => parse_char(v),
+                    v if v.contains('.') => v.parse().map(Literal::Float).ok(),
+                    v => v.parse().map(Literal::Int).ok(),
+                },
+            };
+            instruction.value =
+                Some(literal.ok_or(eyre!("line {line}: invalid constant {value}"))?);
+        } else {
+            // Functions are referenced with a leading `@` and labels
+            // with a leading dot in the text format
+            let (funcs, args): (Vec<_>, Vec<_>) =
+                args.into_iter().partition(|a| a.starts_with('@'));
+            if !funcs.is_empty() {
+                instruction.funcs = Some(funcs.into_iter().map(|f| f[1..].to_string()).collect());
+            }
+            let (labels, args): (Vec<_>, Vec<_>) =
+                args.into_iter().partition(|a| a.starts_with('.'));
+            if !labels.is_empty() {
+                instruction.labels = Some(labels.into_iter().map(|l| l[1..].to_string()).collect());
+            }
+            instruction.args = args.into_iter().map(Into::into).collect();
+        }
+
+        Ok(Code::Instruction(instruction))
+    }
+}
+
+/// Parses a character literal written as `'c'`
+fn parse_char(value: &str) -> Option<Literal> {
+    value
+        .strip_prefix('\'')
+        .and_then(|v| v.strip_suffix('\''))
+        .and_then(|v| v.parse().ok())
+        .map(Literal::Char)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{emit_program, parse_program};
+    use crate::types::{Code, Operation, Type};
+
+    #[test]
+    fn test_parse_program() {
+        let s = r#"
+            # Adds two constants and prints the result
+            @main {
+              v0: int = const 1;
+              v1: int = const 2;
+              v2: int = add v0 v1;
+              print v2;
+            }
+        "#;
+
+        let program = parse_program(s).unwrap();
+
+        assert_eq!(program.functions.len(), 1);
+        let main = &program.functions[0];
+        assert_eq!(main.name, "main");
+        assert_eq!(main.instrs.len(), 4);
+
+        let Code::Instruction(add) = &main.instrs[2] else {
+            panic!("expected instruction");
+        };
+        assert_eq!(add.op, Operation::Add);
+        assert_eq!(add.args, vec!["v0", "v1"]);
+        assert_eq!(add.dest.as_deref(), Some("v2"));
+        assert_eq!(add.r#type, Some(Type::Int));
+    }
+
+    #[test]
+    fn test_parse_labels() {
+        let s = r#"
+            @main {
+              jmp .end;
+            .end:
+              v: int = const 3;
+              print v;
+            }
+        "#;
+
+        let program = parse_program(s).unwrap();
+        let main = &program.functions[0];
+
+        assert_eq!(
+            main.instrs[1],
+            Code::Label {
+                label: "end".to_string()
+            }
+        );
+        let Code::Instruction(jmp) = &main.instrs[0] else {
+            panic!("expected instruction");
+        };
+        assert!(jmp.args.is_empty());
+        assert_eq!(jmp.labels, Some(vec!["end".to_string()]));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse_program("@main { v: int = unknown a; }").is_err());
+        assert!(parse_program("@main { v: int = const; }").is_err());
+        assert!(parse_program("@main { print a }").is_err());
+        assert!(parse_program("main { }").is_err());
+    }
+
+    #[test]
+    fn test_emit_program() {
+        let s = r#"@main(n: int, cond: bool) {
+  v0: int = const -1;
+  v1: int = add v0 n;
+  f: float = const 2.0;
+  g: float = fdiv f f;
+  c: bool = const true;
+  br c .then .else;
+.then:
+  print v1;
+  jmp .else;
+.else:
+  r: int = call @double v0;
+  print r;
+  ret;
+}
+@double(x: int): int {
+  r: int = add x x;
+  ret r;
+}
+"#;
+
+        let program = parse_program(s).unwrap();
+        let emitted = emit_program(&program);
+
+        assert_eq!(emitted, s);
+        assert_eq!(parse_program(&emitted).unwrap(), program);
+    }
+
+    #[test]
+    fn test_display_instruction() {
+        let program = parse_program("@main { v: int = add a b; print v; }").unwrap();
+        let Code::Instruction(add) = &program.functions[0].instrs[0] else {
+            panic!("expected instruction");
+        };
+
+        assert_eq!(add.to_string(), "v: int = add a b;");
+        assert_eq!(
+            program.functions[0].to_string(),
+            "@main {\n  v: int = add a b;\n  print v;\n}\n"
+        );
+    }
+}
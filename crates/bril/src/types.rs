@@ -1,81 +1,725 @@
+use crate::diagnostic::Diagnostic;
 use crate::{all_none, all_some};
 use eyre::eyre;
-use serde::Deserialize;
+use serde::de::{self, MapAccess, Visitor};
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{Display, Formatter};
 use std::str::FromStr;
+use std::sync::Mutex;
 
 /// A block of instruction in a function.
 /// A block doesn't contain any control flow
 /// instructions like `br` or `jmp`.
 pub type Block = Vec<Instruction>;
 
+pub use crate::symbol::Symbol;
+
 /// A variable in the program
-pub type Var = String;
+pub type Var = Symbol;
 
 /// The arguments to the operation
 pub type Args = Vec<Var>;
 
-#[derive(Debug, Deserialize)]
+/// The fields of a node unknown to this crate, such as the ones added
+/// by Bril extensions. They are kept as is so that they survive a
+/// round-trip through the IR.
+pub type Extra = BTreeMap<String, serde_json::Value>;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct BrilProgram {
+    /// The version of the IR the program was saved with. Programs without
+    /// a version, such as the ones produced by the Bril tools, are in the
+    /// current version, see [`crate::migrate`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u32>,
     pub functions: Vec<Function>,
+    #[serde(flatten, skip_serializing_if = "BTreeMap::is_empty")]
+    pub extra: Extra,
+    #[serde(skip)]
+    index: FunctionIndex,
+}
+
+/// Cache mapping the name of each function to its position in the
+/// program. The functions can be modified freely: the cache is checked
+/// on each lookup and only rebuilt when it is outdated.
+#[derive(Debug, Default)]
+struct FunctionIndex(Mutex<HashMap<String, usize>>);
+
+impl Clone for FunctionIndex {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+/// The cache doesn't take part in the comparison of programs
+impl PartialEq for FunctionIndex {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl BrilProgram {
+    pub fn new(functions: Vec<Function>) -> Self {
+        Self {
+            version: None,
+            functions,
+            extra: Default::default(),
+            index: Default::default(),
+        }
+    }
+
+    /// Returns the function with the given name
+    pub fn function(&self, name: &str) -> Option<&Function> {
+        let mut index = self.index.0.lock().expect("function index lock poisoned");
+        let lookup = |index: &HashMap<String, usize>| {
+            index
+                .get(name)
+                .and_then(|i| self.functions.get(*i))
+                .filter(|f| f.name == name)
+        };
+
+        if let Some(function) = lookup(&index) {
+            return Some(function);
+        }
+
+        // The function is missing if the cache holds the name of every
+        // function, otherwise the functions were modified since it was built
+        let outdated =
+            index.contains_key(name) || self.functions.iter().any(|f| !index.contains_key(&f.name));
+        if !outdated {
+            return None;
+        }
+        *index = self
+            .functions
+            .iter()
+            .enumerate()
+            .map(|(i, f)| (f.name.clone(), i))
+            .collect();
+        lookup(&index)
+    }
+
+    /// Verifies that the target of every call exists in the program,
+    /// returning a diagnostic for each unknown target
+    pub fn resolve_calls(&self) -> Result<(), Vec<Diagnostic>> {
+        let mut diagnostics = Vec::new();
+        let names = self
+            .functions
+            .iter()
+            .map(|f| f.name.as_str())
+            .collect::<HashSet<_>>();
+
+        for function in &self.functions {
+            for (index, code) in function.instrs.iter().enumerate() {
+                let Code::Instruction(i) = code else {
+                    continue;
+                };
+                if i.op != Operation::Call {
+                    continue;
+                }
+                for name in i.funcs.iter().flatten() {
+                    if !names.contains(name.as_str()) {
+                        diagnostics.push(
+                            Diagnostic::new(format!("call to unknown function {name}"))
+                                .in_function(&function.name)
+                                .at_instruction(index),
+                        );
+                    }
+                }
+            }
+        }
+
+        match diagnostics.is_empty() {
+            true => Ok(()),
+            false => Err(diagnostics),
+        }
+    }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
 pub struct Function {
     pub name: String,
-    pub instrs: Vec<Instruction>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<FunctionArg>,
+    /// The type of the returned value, none if the function
+    /// doesn't return a value
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub r#type: Option<Type>,
+    pub instrs: Vec<Code>,
+    #[serde(flatten, skip_serializing_if = "BTreeMap::is_empty")]
+    pub extra: Extra,
+}
+
+/// An argument of a function, along with its type
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct FunctionArg {
+    pub name: Var,
+    pub r#type: Type,
 }
 
-#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+/// An entry in the body of a function, either a label
+/// marking the start of a block or an instruction.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum Code {
+    Label { label: String },
+    Instruction(Instruction),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
 pub struct Instruction {
     pub op: Operation,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub args: Args,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub r#type: Option<Type>,
-    pub value: Option<u32>,
-    pub dest: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<Literal>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dest: Option<Var>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub funcs: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub labels: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pos: Option<Position>,
+    /// The results attached to the instruction by the analyses, see
+    /// [`Instruction::attr`]
+    #[serde(default, skip_serializing_if = "Instruction::has_no_attrs")]
+    pub attrs: Option<Attrs>,
+    #[serde(flatten, skip_serializing_if = "BTreeMap::is_empty")]
+    pub extra: Extra,
+}
+
+/// The attributes of an instruction, mapping a name to any JSON value
+pub type Attrs = BTreeMap<String, serde_json::Value>;
+
+/// The position of an instruction in the source file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Position {
+    pub row: u64,
+    pub col: u64,
 }
 
 impl Instruction {
+    /// Returns the attribute with the given name, if any
+    pub fn attr(&self, name: &str) -> Option<&serde_json::Value> {
+        self.attrs.as_ref()?.get(name)
+    }
+
+    /// Sets the attribute, returning its previous value
+    pub fn set_attr(
+        &mut self,
+        name: impl Into<String>,
+        value: impl Into<serde_json::Value>,
+    ) -> Option<serde_json::Value> {
+        self.attrs
+            .get_or_insert_with(Default::default)
+            .insert(name.into(), value.into())
+    }
+
+    /// Removes the attribute, returning its value
+    pub fn remove_attr(&mut self, name: &str) -> Option<serde_json::Value> {
+        let attrs = self.attrs.as_mut()?;
+        let value = attrs.remove(name);
+        if attrs.is_empty() {
+            self.attrs = None;
+        }
+        value
+    }
+
+    fn has_no_attrs(attrs: &Option<Attrs>) -> bool {
+        attrs.as_ref().is_none_or(BTreeMap::is_empty)
+    }
+
+    /// Returns the arguments of the instruction, empty if it has none
+    pub fn args(&self) -> &[Var] {
+        &self.args
+    }
+
+    /// Returns a mutable reference to the arguments of the instruction
+    pub fn args_mut(&mut self) -> &mut Args {
+        &mut self.args
+    }
+
+    /// Returns the variable defined by the instruction, if any
+    pub fn defs(&self) -> Option<&Var> {
+        self.dest.as_ref()
+    }
+
+    /// Returns the variables read by the instruction. Labels and function
+    /// names aren't variables, so only the arguments are uses: this includes
+    /// the condition of a branch, the arguments of a call and the pointer
+    /// and value of a store.
+    pub fn uses(&self) -> impl Iterator<Item = &Var> {
+        self.args.iter()
+    }
+
     /// Verifies if the instruction is a valid instruction
     pub fn is_valid(&self) -> bool {
         let count_args = self.args.len();
         let no_args = self.args.is_empty();
         let one_args = count_args == 1;
         let two_args = count_args == 2;
-        let three_args = count_args == 3;
+        let one_func = self.funcs.as_ref().is_some_and(|f| f.len() == 1);
+
+        // Only control flow, phi and guard operations refer to labels
+        if self.labels.is_some()
+            && !matches!(
+                self.op,
+                Operation::Br
+                    | Operation::Jmp
+                    | Operation::Phi
+                    | Operation::Guard
+                    | Operation::Other(_)
+            )
+        {
+            return false;
+        }
+
         match self.op {
+            // The type of a constant is optional but has to match the literal
             Operation::Const => {
-                all_some!(self.value, self.dest) && all_none!(self.r#type) && no_args
+                all_some!(self.value, self.dest)
+                    && all_none!(self.funcs)
+                    && no_args
+                    && self
+                        .value
+                        .zip(self.r#type.as_ref())
+                        .is_none_or(|(v, t)| v.matches_type(t))
+            }
+            Operation::Add | Operation::Mul | Operation::Sub | Operation::Div => {
+                all_some!(self.dest)
+                    && all_none!(self.value, self.funcs)
+                    && self.has_type_or_none(Type::Int)
+                    && two_args
+            }
+            Operation::Id => all_some!(self.dest) && all_none!(self.value, self.funcs) && one_args,
+            Operation::Print => {
+                all_none!(self.value, self.r#type, self.dest, self.funcs) && one_args
+            }
+            // A branch jumps to the first label if the condition is true
+            Operation::Br => {
+                all_none!(self.r#type, self.value, self.dest, self.funcs)
+                    && self.labels.as_ref().is_some_and(|l| l.len() == 2)
+                    && one_args
+            }
+            Operation::Jmp => {
+                all_none!(self.value, self.r#type, self.dest, self.funcs)
+                    && self.labels.as_ref().is_some_and(|l| l.len() == 1)
+                    && no_args
+            }
+            // The type of a call can only be set if the result is stored
+            Operation::Call => {
+                all_none!(self.value) && one_func && (self.dest.is_some() || self.r#type.is_none())
+            }
+            Operation::Ret => {
+                all_none!(self.value, self.r#type, self.dest, self.funcs) && count_args <= 1
+            }
+            Operation::Fadd | Operation::Fsub | Operation::Fmul | Operation::Fdiv => {
+                all_some!(self.dest)
+                    && all_none!(self.value, self.funcs)
+                    && self.has_type_or_none(Type::Float)
+                    && two_args
+            }
+            Operation::Feq | Operation::Flt | Operation::Fgt | Operation::Fle | Operation::Fge => {
+                all_some!(self.dest)
+                    && all_none!(self.value, self.funcs)
+                    && self.has_type_or_none(Type::Bool)
+                    && two_args
+            }
+            Operation::Eq | Operation::Lt | Operation::Gt | Operation::Le | Operation::Ge => {
+                all_some!(self.dest)
+                    && all_none!(self.value, self.funcs)
+                    && self.has_type_or_none(Type::Bool)
+                    && two_args
+            }
+            Operation::And | Operation::Or => {
+                all_some!(self.dest)
+                    && all_none!(self.value, self.funcs)
+                    && self.has_type_or_none(Type::Bool)
+                    && two_args
+            }
+            Operation::Not => {
+                all_some!(self.dest)
+                    && all_none!(self.value, self.funcs)
+                    && self.has_type_or_none(Type::Bool)
+                    && one_args
+            }
+            // Allocations return a pointer to the provided number of elements
+            Operation::Alloc => {
+                all_some!(self.dest)
+                    && all_none!(self.value, self.funcs)
+                    && self.r#type.as_ref().is_none_or(Type::is_ptr)
+                    && one_args
             }
-            Operation::Add => {
-                all_some!(self.dest) && all_none!(self.value, self.r#type) && two_args
+            Operation::Free => {
+                all_none!(self.value, self.r#type, self.dest, self.funcs) && one_args
             }
-            Operation::Mul => {
-                all_some!(self.dest) && all_none!(self.value, self.r#type) && two_args
+            Operation::Store => {
+                all_none!(self.value, self.r#type, self.dest, self.funcs) && two_args
             }
-            Operation::Id => all_some!(self.dest) && all_none!(self.value, self.r#type) && one_args,
-            Operation::Print => all_none!(self.value, self.r#type, self.dest) && one_args,
-            Operation::Br => all_none!(self.r#type, self.value, self.dest) && three_args,
-            Operation::Jmp => all_none!(self.value, self.r#type, self.dest),
+            Operation::Load => {
+                all_some!(self.dest) && all_none!(self.value, self.funcs) && one_args
+            }
+            Operation::PtrAdd => {
+                all_some!(self.dest)
+                    && all_none!(self.value, self.funcs)
+                    && self.r#type.as_ref().is_none_or(Type::is_ptr)
+                    && two_args
+            }
+            // Each argument of a phi is associated with the label it comes from
+            Operation::Phi => {
+                all_some!(self.dest)
+                    && all_none!(self.value, self.funcs)
+                    && self.labels.as_ref().is_some_and(|l| l.len() == count_args)
+            }
+            Operation::Set => all_none!(self.value, self.r#type, self.dest, self.funcs) && two_args,
+            Operation::Get => all_some!(self.dest) && all_none!(self.value, self.funcs) && no_args,
+            Operation::Ceq | Operation::Clt | Operation::Cgt | Operation::Cle | Operation::Cge => {
+                all_some!(self.dest)
+                    && all_none!(self.value, self.funcs)
+                    && self.has_type_or_none(Type::Bool)
+                    && two_args
+            }
+            Operation::Char2Int => {
+                all_some!(self.dest)
+                    && all_none!(self.value, self.funcs)
+                    && self.has_type_or_none(Type::Int)
+                    && one_args
+            }
+            Operation::Int2Char => {
+                all_some!(self.dest)
+                    && all_none!(self.value, self.funcs)
+                    && self.has_type_or_none(Type::Char)
+                    && one_args
+            }
+            Operation::Speculate | Operation::Commit => {
+                all_none!(self.value, self.r#type, self.dest, self.funcs) && no_args
+            }
+            // A guard checks the condition and aborts to the label if false
+            Operation::Guard => {
+                all_none!(self.value, self.r#type, self.dest, self.funcs)
+                    && self.labels.as_ref().is_some_and(|l| l.len() == 1)
+                    && one_args
+            }
+            // The shape of unknown operations can't be verified
+            Operation::Other(_) => true,
         }
     }
 
+    /// Returns true if the type of the instruction is not set or
+    /// is equal to the provided type
+    fn has_type_or_none(&self, ty: Type) -> bool {
+        self.r#type.as_ref().is_none_or(|t| *t == ty)
+    }
+
     /// Returns true if the instruction is a assignment (const operation)
     pub fn is_assignment(&self) -> bool {
         self.op == Operation::Const
     }
+
+    /// Returns true if the result of the instruction only depends on its
+    /// arguments, see [`Operation::is_pure`]
+    pub fn is_pure(&self) -> bool {
+        self.op.is_pure()
+    }
+
+    /// Returns true if the instruction can't be removed even if its
+    /// result is unused, see [`Operation::has_side_effects`]
+    pub fn has_side_effects(&self) -> bool {
+        self.op.has_side_effects()
+    }
+
+    /// Returns true if the instruction ends a block, see [`Operation::is_terminator`]
+    pub fn is_terminator(&self) -> bool {
+        self.op.is_terminator()
+    }
+
+    /// Returns true if the instruction can transfer control elsewhere than the
+    /// next instruction, see [`Operation::is_control_flow`]
+    pub fn is_control_flow(&self) -> bool {
+        self.op.is_control_flow()
+    }
+}
+
+/// A constant value in the program. Literals are serialized as the
+/// matching JSON value, characters being one character strings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Literal {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Char(char),
+}
+
+impl Literal {
+    /// Returns true if the literal can be used as a constant of the type.
+    /// Integer literals are accepted for floats, since round float values
+    /// are often serialized without a decimal point.
+    pub fn matches_type(&self, ty: &Type) -> bool {
+        matches!(
+            (self, ty),
+            (Literal::Bool(_), Type::Bool)
+                | (Literal::Int(_), Type::Int | Type::Float)
+                | (Literal::Float(_), Type::Float)
+                | (Literal::Char(_), Type::Char)
+        )
+    }
+}
+
+impl Serialize for Literal {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match *self {
+            Literal::Bool(v) => serializer.serialize_bool(v),
+            Literal::Int(v) => serializer.serialize_i64(v),
+            Literal::Float(v) => serializer.serialize_f64(v),
+            Literal::Char(v) => serializer.serialize_char(v),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Literal {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(LiteralVisitor)
+    }
+}
+
+struct LiteralVisitor;
+
+impl<'de> Visitor<'de> for LiteralVisitor {
+    type Value = Literal;
+
+    fn expecting(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("a boolean, a 64 bits integer, a float or a single character")
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Self::Value, E> {
+        Ok(Literal::Bool(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+        Ok(Literal::Int(v))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+        i64::try_from(v)
+            .map(Literal::Int)
+            .map_err(|_| E::invalid_value(de::Unexpected::Unsigned(v), &self))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Self::Value, E> {
+        Ok(Literal::Float(v))
+    }
+
+    fn visit_char<E: de::Error>(self, v: char) -> Result<Self::Value, E> {
+        Ok(Literal::Char(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        let mut chars = v.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Ok(Literal::Char(c)),
+            _ => Err(E::invalid_value(de::Unexpected::Str(v), &self)),
+        }
+    }
+}
+
+impl Display for Literal {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Literal::Bool(v) => write!(f, "{v}"),
+            Literal::Int(v) => write!(f, "{v}"),
+            // Debug formatting keeps the decimal point for round values
+            Literal::Float(v) => write!(f, "{v:?}"),
+            Literal::Char(v) => write!(f, "'{v}'"),
+        }
+    }
 }
 
-#[derive(Debug, Default, Hash, Clone, Eq, PartialEq, Deserialize)]
-#[serde(rename_all = "lowercase")]
+#[derive(Debug, Default, Hash, Clone, Eq, PartialEq)]
 pub enum Operation {
     #[default]
     Const,
     Add,
     Mul,
+    Sub,
+    Div,
+    Eq,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    And,
+    Or,
+    Not,
     Id,
     Print,
     Br,
     Jmp,
+    Call,
+    Ret,
+    Alloc,
+    Free,
+    Store,
+    Load,
+    PtrAdd,
+    Phi,
+    Set,
+    Get,
+    Speculate,
+    Commit,
+    Guard,
+    Fadd,
+    Fsub,
+    Fmul,
+    Fdiv,
+    Feq,
+    Flt,
+    Fgt,
+    Fle,
+    Fge,
+    Ceq,
+    Clt,
+    Cgt,
+    Cle,
+    Cge,
+    Char2Int,
+    Int2Char,
+    /// An operation unknown to this crate, such as the ones
+    /// added by extensions. Passes treat it as opaque.
+    Other(String),
+}
+
+impl Operation {
+    /// Returns true if the order of the arguments of the operation
+    /// doesn't change its result
+    pub fn is_commutative(&self) -> bool {
+        matches!(
+            self,
+            Operation::Add
+                | Operation::Mul
+                | Operation::Eq
+                | Operation::And
+                | Operation::Or
+                | Operation::Fadd
+                | Operation::Fmul
+                | Operation::Feq
+                | Operation::Ceq
+        )
+    }
+
+    /// Returns true if the grouping of a chain of the operation doesn't change
+    /// its result, such as `(a + b) + c` and `a + (b + c)`. The integers wrap
+    /// around, but the floats are rounded at each step, so they aren't.
+    pub fn is_associative(&self) -> bool {
+        matches!(
+            self,
+            Operation::Add | Operation::Mul | Operation::And | Operation::Or
+        )
+    }
+
+    /// Returns the comparison giving the same result once its two
+    /// arguments are swapped, such as `lt` for `gt`
+    pub fn swapped(&self) -> Option<Operation> {
+        Some(match self {
+            Operation::Lt => Operation::Gt,
+            Operation::Gt => Operation::Lt,
+            Operation::Le => Operation::Ge,
+            Operation::Ge => Operation::Le,
+            Operation::Flt => Operation::Fgt,
+            Operation::Fgt => Operation::Flt,
+            Operation::Fle => Operation::Fge,
+            Operation::Fge => Operation::Fle,
+            Operation::Clt => Operation::Cgt,
+            Operation::Cgt => Operation::Clt,
+            Operation::Cle => Operation::Cge,
+            Operation::Cge => Operation::Cle,
+            _ => return None,
+        })
+    }
+}
+
+/// The effect classification of the operations. Passes should rely on
+/// these instead of matching on the operations themselves.
+impl Operation {
+    /// Returns true if the result of the operation only depends on its
+    /// arguments, which means it can be reused or removed if unused
+    pub fn is_pure(&self) -> bool {
+        matches!(
+            self,
+            Operation::Const
+                | Operation::Add
+                | Operation::Mul
+                | Operation::Sub
+                | Operation::Div
+                | Operation::Eq
+                | Operation::Lt
+                | Operation::Gt
+                | Operation::Le
+                | Operation::Ge
+                | Operation::And
+                | Operation::Or
+                | Operation::Not
+                | Operation::Id
+                | Operation::PtrAdd
+                | Operation::Fadd
+                | Operation::Fsub
+                | Operation::Fmul
+                | Operation::Fdiv
+                | Operation::Feq
+                | Operation::Flt
+                | Operation::Fgt
+                | Operation::Fle
+                | Operation::Fge
+                | Operation::Ceq
+                | Operation::Clt
+                | Operation::Cgt
+                | Operation::Cle
+                | Operation::Cge
+                | Operation::Char2Int
+                | Operation::Int2Char
+        )
+    }
+
+    /// Returns true if the operation does more than computing its result:
+    /// printing, transferring control, calling a function, accessing memory
+    /// (which can fault) or speculating. Unknown operations are assumed to
+    /// have side effects.
+    ///
+    /// `phi` and `get` are neither pure nor effectful: they read state
+    /// outside of their arguments but can be removed if unused.
+    pub fn has_side_effects(&self) -> bool {
+        matches!(
+            self,
+            Operation::Print
+                | Operation::Br
+                | Operation::Jmp
+                | Operation::Call
+                | Operation::Ret
+                | Operation::Alloc
+                | Operation::Free
+                | Operation::Store
+                | Operation::Load
+                | Operation::Set
+                | Operation::Speculate
+                | Operation::Commit
+                | Operation::Guard
+                | Operation::Other(_)
+        )
+    }
+
+    /// Returns true if the operation always ends a block
+    pub fn is_terminator(&self) -> bool {
+        matches!(self, Operation::Br | Operation::Jmp | Operation::Ret)
+    }
+
+    /// Returns true if the operation can transfer control elsewhere than the
+    /// next instruction. Guards are included since they can abort to a label.
+    pub fn is_control_flow(&self) -> bool {
+        self.is_terminator() || *self == Operation::Guard
+    }
 }
 
 impl FromStr for Operation {
@@ -86,20 +730,142 @@ impl FromStr for Operation {
             "const" => Ok(Operation::Const),
             "add" => Ok(Operation::Add),
             "mul" => Ok(Operation::Mul),
+            "sub" => Ok(Operation::Sub),
+            "div" => Ok(Operation::Div),
+            "eq" => Ok(Operation::Eq),
+            "lt" => Ok(Operation::Lt),
+            "gt" => Ok(Operation::Gt),
+            "le" => Ok(Operation::Le),
+            "ge" => Ok(Operation::Ge),
+            "and" => Ok(Operation::And),
+            "or" => Ok(Operation::Or),
+            "not" => Ok(Operation::Not),
             "id" => Ok(Operation::Id),
             "print" => Ok(Operation::Print),
             "br" => Ok(Operation::Br),
             "jmp" => Ok(Operation::Jmp),
+            "call" => Ok(Operation::Call),
+            "ret" => Ok(Operation::Ret),
+            "alloc" => Ok(Operation::Alloc),
+            "free" => Ok(Operation::Free),
+            "store" => Ok(Operation::Store),
+            "load" => Ok(Operation::Load),
+            "ptradd" => Ok(Operation::PtrAdd),
+            "phi" => Ok(Operation::Phi),
+            "set" => Ok(Operation::Set),
+            "get" => Ok(Operation::Get),
+            "speculate" => Ok(Operation::Speculate),
+            "commit" => Ok(Operation::Commit),
+            "guard" => Ok(Operation::Guard),
+            "fadd" => Ok(Operation::Fadd),
+            "fsub" => Ok(Operation::Fsub),
+            "fmul" => Ok(Operation::Fmul),
+            "fdiv" => Ok(Operation::Fdiv),
+            "feq" => Ok(Operation::Feq),
+            "flt" => Ok(Operation::Flt),
+            "fgt" => Ok(Operation::Fgt),
+            "fle" => Ok(Operation::Fle),
+            "fge" => Ok(Operation::Fge),
+            "ceq" => Ok(Operation::Ceq),
+            "clt" => Ok(Operation::Clt),
+            "cgt" => Ok(Operation::Cgt),
+            "cle" => Ok(Operation::Cle),
+            "cge" => Ok(Operation::Cge),
+            "char2int" => Ok(Operation::Char2Int),
+            "int2char" => Ok(Operation::Int2Char),
             val => Err(eyre!("incorrect operation, got {val}")),
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Deserialize)]
-#[serde(rename_all = "lowercase")]
+impl Serialize for Operation {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Unknown operations are deserialized as [`Operation::Other`]
+/// instead of failing, which lets extension instructions go
+/// through the passes untouched.
+impl<'de> Deserialize<'de> for Operation {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let op = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        Ok(Operation::from_str(&op).unwrap_or_else(|_| Operation::Other(op.into_owned())))
+    }
+}
+
+impl Display for Operation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let op = match self {
+            Operation::Const => "const",
+            Operation::Add => "add",
+            Operation::Mul => "mul",
+            Operation::Sub => "sub",
+            Operation::Div => "div",
+            Operation::Eq => "eq",
+            Operation::Lt => "lt",
+            Operation::Gt => "gt",
+            Operation::Le => "le",
+            Operation::Ge => "ge",
+            Operation::And => "and",
+            Operation::Or => "or",
+            Operation::Not => "not",
+            Operation::Id => "id",
+            Operation::Print => "print",
+            Operation::Br => "br",
+            Operation::Jmp => "jmp",
+            Operation::Call => "call",
+            Operation::Ret => "ret",
+            Operation::Alloc => "alloc",
+            Operation::Free => "free",
+            Operation::Store => "store",
+            Operation::Load => "load",
+            Operation::PtrAdd => "ptradd",
+            Operation::Phi => "phi",
+            Operation::Set => "set",
+            Operation::Get => "get",
+            Operation::Speculate => "speculate",
+            Operation::Commit => "commit",
+            Operation::Guard => "guard",
+            Operation::Fadd => "fadd",
+            Operation::Fsub => "fsub",
+            Operation::Fmul => "fmul",
+            Operation::Fdiv => "fdiv",
+            Operation::Feq => "feq",
+            Operation::Flt => "flt",
+            Operation::Fgt => "fgt",
+            Operation::Fle => "fle",
+            Operation::Fge => "fge",
+            Operation::Ceq => "ceq",
+            Operation::Clt => "clt",
+            Operation::Cgt => "cgt",
+            Operation::Cle => "cle",
+            Operation::Cge => "cge",
+            Operation::Char2Int => "char2int",
+            Operation::Int2Char => "int2char",
+            Operation::Other(op) => op,
+        };
+        f.write_str(op)
+    }
+}
+
+/// The type of a value. Primitive types are serialized as strings and
+/// pointers as nested objects: `{"ptr": {"ptr": "int"}}` is a pointer
+/// to a pointer to an integer.
+#[derive(Debug, Clone, PartialEq)]
 pub enum Type {
     Int,
     Bool,
+    Float,
+    Char,
+    Ptr(Box<Type>),
+}
+
+impl Type {
+    /// Returns true if the type is a pointer
+    pub fn is_ptr(&self) -> bool {
+        matches!(self, Type::Ptr(_))
+    }
 }
 
 impl FromStr for Type {
@@ -109,14 +875,88 @@ impl FromStr for Type {
         match s {
             "int" => Ok(Type::Int),
             "bool" => Ok(Type::Bool),
-            val => Err(eyre!("incorrect type, got {val}")),
+            "float" => Ok(Type::Float),
+            "char" => Ok(Type::Char),
+            // Pointer types are written as `ptr<type>`
+            val => match val.strip_prefix("ptr<").and_then(|t| t.strip_suffix('>')) {
+                Some(t) => Ok(Type::Ptr(Box::new(Type::from_str(t)?))),
+                None => Err(eyre!("incorrect type, got {val}")),
+            },
+        }
+    }
+}
+
+impl Serialize for Type {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Type::Ptr(t) => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry("ptr", t)?;
+                map.end()
+            }
+            t => serializer.collect_str(t),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Type {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(TypeVisitor)
+    }
+}
+
+struct TypeVisitor;
+
+impl<'de> Visitor<'de> for TypeVisitor {
+    type Value = Type;
+
+    fn expecting(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("a primitive type or a {\"ptr\": <type>} object")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        match v {
+            "int" => Ok(Type::Int),
+            "bool" => Ok(Type::Bool),
+            "float" => Ok(Type::Float),
+            "char" => Ok(Type::Char),
+            v => Err(E::unknown_variant(v, &["int", "bool", "float", "char"])),
+        }
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let Some(key) = map.next_key::<std::borrow::Cow<'de, str>>()? else {
+            return Err(de::Error::invalid_length(0, &self));
+        };
+        if key != "ptr" {
+            return Err(de::Error::unknown_field(&key, &["ptr"]));
+        }
+        let ty = map.next_value::<Type>()?;
+        if map.next_key::<de::IgnoredAny>()?.is_some() {
+            return Err(de::Error::custom("expected a single key in pointer type"));
+        }
+        Ok(Type::Ptr(Box::new(ty)))
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Type::Int => f.write_str("int"),
+            Type::Bool => f.write_str("bool"),
+            Type::Float => f.write_str("float"),
+            Type::Char => f.write_str("char"),
+            Type::Ptr(t) => write!(f, "ptr<{t}>"),
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::BrilProgram;
+    use super::{
+        BrilProgram, Code, Function, FunctionArg, Instruction, Literal, Operation, Position, Type,
+    };
+    use std::str::FromStr;
 
     #[test]
     fn test_deserialize() {
@@ -165,4 +1005,514 @@ mod tests {
 
         assert_eq!(program.functions.len(), 1);
     }
+
+    #[test]
+    fn test_deserialize_function_args() {
+        let s = r#"
+            {
+              "functions": [
+                {
+                  "args": [
+                    {
+                      "name": "n",
+                      "type": "int"
+                    },
+                    {
+                      "name": "cond",
+                      "type": "bool"
+                    }
+                  ],
+                  "instrs": [
+                    {
+                      "args": [
+                        "n"
+                      ],
+                      "op": "print"
+                    }
+                  ],
+                  "name": "main"
+                }
+              ]
+            }
+        "#;
+
+        let program: BrilProgram = serde_json::from_str(s).unwrap();
+
+        let args = &program.functions[0].args;
+        assert_eq!(
+            args,
+            &vec![
+                FunctionArg {
+                    name: "n".into(),
+                    r#type: Type::Int
+                },
+                FunctionArg {
+                    name: "cond".into(),
+                    r#type: Type::Bool
+                }
+            ]
+        );
+        assert_eq!(
+            serde_json::to_value(&program).unwrap(),
+            serde_json::from_str::<serde_json::Value>(s).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_deserialize_float() {
+        let s = r#"
+            {
+              "functions": [
+                {
+                  "instrs": [
+                    {
+                      "dest": "v0",
+                      "op": "const",
+                      "type": "float",
+                      "value": 1.5
+                    },
+                    {
+                      "args": [
+                        "v0",
+                        "v0"
+                      ],
+                      "dest": "v1",
+                      "op": "fmul",
+                      "type": "float"
+                    }
+                  ],
+                  "name": "main"
+                }
+              ]
+            }
+        "#;
+
+        let program: BrilProgram = serde_json::from_str(s).unwrap();
+
+        let Code::Instruction(constant) = &program.functions[0].instrs[0] else {
+            panic!("expected instruction");
+        };
+        assert_eq!(constant.value, Some(Literal::Float(1.5)));
+        assert_eq!(constant.r#type, Some(Type::Float));
+
+        let Code::Instruction(mul) = &program.functions[0].instrs[1] else {
+            panic!("expected instruction");
+        };
+        assert_eq!(mul.op, Operation::Fmul);
+        assert!(mul.is_valid());
+    }
+
+    #[test]
+    fn test_deserialize_bool() {
+        let s = r#"
+            {
+              "dest": "cond",
+              "op": "const",
+              "type": "bool",
+              "value": true
+            }
+        "#;
+
+        let instruction: Instruction = serde_json::from_str(s).unwrap();
+
+        assert_eq!(instruction.value, Some(Literal::Bool(true)));
+        assert!(instruction.is_valid());
+        assert_eq!(
+            serde_json::to_value(&instruction).unwrap(),
+            serde_json::from_str::<serde_json::Value>(s).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_deserialize_signed_int() {
+        let s = r#"[
+            { "dest": "a", "op": "const", "type": "int", "value": -1 },
+            { "dest": "b", "op": "const", "type": "int", "value": 9223372036854775807 }
+        ]"#;
+
+        let instructions: Vec<Instruction> = serde_json::from_str(s).unwrap();
+
+        assert_eq!(instructions[0].value, Some(Literal::Int(-1)));
+        assert_eq!(instructions[1].value, Some(Literal::Int(i64::MAX)));
+    }
+
+    #[test]
+    fn test_deserialize_char() {
+        let s = r#"[
+            { "dest": "c", "op": "const", "type": "char", "value": "a" },
+            { "args": ["c"], "dest": "i", "op": "char2int", "type": "int" },
+            { "args": ["i"], "dest": "d", "op": "int2char", "type": "char" },
+            { "args": ["c", "d"], "dest": "eq", "op": "ceq", "type": "bool" }
+        ]"#;
+
+        let instructions: Vec<Instruction> = serde_json::from_str(s).unwrap();
+
+        assert_eq!(instructions[0].value, Some(Literal::Char('a')));
+        assert!(instructions.iter().all(Instruction::is_valid));
+        assert_eq!(
+            serde_json::to_value(&instructions).unwrap(),
+            serde_json::from_str::<serde_json::Value>(s).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_deserialize_position() {
+        let s = r#"{ "args": ["a"], "op": "print", "pos": { "col": 3, "row": 12 } }"#;
+
+        let instruction: Instruction = serde_json::from_str(s).unwrap();
+
+        assert_eq!(instruction.pos, Some(Position { row: 12, col: 3 }));
+        assert_eq!(
+            serde_json::to_value(&instruction).unwrap(),
+            serde_json::from_str::<serde_json::Value>(s).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_const_type_mismatch() {
+        let instruction = Instruction {
+            op: Operation::Const,
+            value: Some(Literal::Bool(false)),
+            dest: Some("a".into()),
+            r#type: Some(Type::Int),
+            ..Default::default()
+        };
+
+        assert!(!instruction.is_valid());
+    }
+
+    #[test]
+    fn test_deserialize_pointers() {
+        let s = r#"[
+            { "args": ["size"], "dest": "p", "op": "alloc", "type": { "ptr": "int" } },
+            { "args": ["p", "size"], "op": "store" },
+            { "args": ["p"], "dest": "v", "op": "load", "type": "int" },
+            { "args": ["p", "size"], "dest": "q", "op": "ptradd", "type": { "ptr": "int" } },
+            { "args": ["p"], "op": "free" }
+        ]"#;
+
+        let instructions: Vec<Instruction> = serde_json::from_str(s).unwrap();
+
+        assert_eq!(instructions[0].r#type, Some(Type::Ptr(Box::new(Type::Int))));
+        assert!(instructions.iter().all(Instruction::is_valid));
+        assert_eq!(
+            serde_json::to_value(&instructions).unwrap(),
+            serde_json::from_str::<serde_json::Value>(s).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_deserialize_ssa() {
+        let s = r#"[
+            { "args": ["a", "b"], "dest": "x", "labels": ["left", "right"], "op": "phi", "type": "int" },
+            { "args": ["shadow", "x"], "op": "set" },
+            { "dest": "shadow", "op": "get", "type": "int" }
+        ]"#;
+
+        let instructions: Vec<Instruction> = serde_json::from_str(s).unwrap();
+
+        assert_eq!(
+            instructions[0].labels,
+            Some(vec!["left".to_string(), "right".to_string()])
+        );
+        assert!(instructions.iter().all(Instruction::is_valid));
+        assert_eq!(
+            serde_json::to_value(&instructions).unwrap(),
+            serde_json::from_str::<serde_json::Value>(s).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_deserialize_speculation() {
+        let s = r#"[
+            { "op": "speculate" },
+            { "args": ["cond"], "labels": ["abort"], "op": "guard" },
+            { "op": "commit" }
+        ]"#;
+
+        let instructions: Vec<Instruction> = serde_json::from_str(s).unwrap();
+
+        assert_eq!(instructions[1].op, Operation::Guard);
+        assert!(instructions.iter().all(Instruction::is_valid));
+        assert_eq!(
+            serde_json::to_value(&instructions).unwrap(),
+            serde_json::from_str::<serde_json::Value>(s).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_deserialize_control_flow() {
+        let s = r#"[
+            { "args": ["cond"], "labels": ["then", "else"], "op": "br" },
+            { "labels": ["end"], "op": "jmp" }
+        ]"#;
+
+        let instructions: Vec<Instruction> = serde_json::from_str(s).unwrap();
+
+        assert_eq!(instructions[0].args, vec!["cond"]);
+        assert!(instructions.iter().all(Instruction::is_valid));
+        assert_eq!(
+            serde_json::to_value(&instructions).unwrap(),
+            serde_json::from_str::<serde_json::Value>(s).unwrap()
+        );
+
+        // Branch targets are not arguments of the operation
+        let jmp = Instruction {
+            op: Operation::Jmp,
+            args: vec!["end".into()],
+            ..Default::default()
+        };
+        assert!(!jmp.is_valid());
+    }
+
+    #[test]
+    fn test_invalid_phi() {
+        let mut phi = Instruction {
+            op: Operation::Phi,
+            args: vec!["a".into(), "b".into()],
+            dest: Some("x".into()),
+            labels: Some(vec!["left".to_string()]),
+            ..Default::default()
+        };
+        assert!(!phi.is_valid());
+
+        phi.op = Operation::Add;
+        assert!(!phi.is_valid());
+    }
+
+    #[test]
+    fn test_nested_pointer_type_serde() {
+        let s = r#"{"ptr": {"ptr": "int"}}"#;
+
+        let ty: Type = serde_json::from_str(s).unwrap();
+
+        assert_eq!(ty, Type::Ptr(Box::new(Type::Ptr(Box::new(Type::Int)))));
+        assert_eq!(
+            serde_json::to_string(&ty).unwrap(),
+            r#"{"ptr":{"ptr":"int"}}"#
+        );
+        assert!(serde_json::from_str::<Type>(r#""ptr""#).is_err());
+        assert!(serde_json::from_str::<Type>(r#"{"ref": "int"}"#).is_err());
+        assert!(serde_json::from_str::<Type>(r#"{"ptr": "int", "other": "int"}"#).is_err());
+
+        let alloc: Instruction = serde_json::from_str(
+            r#"{"op": "alloc", "args": ["n"], "dest": "p", "type": {"ptr": {"ptr": "bool"}}}"#,
+        )
+        .unwrap();
+        assert!(alloc.is_valid());
+        assert_eq!(alloc.r#type.unwrap().to_string(), "ptr<ptr<bool>>");
+    }
+
+    #[test]
+    fn test_pointer_type_from_str() {
+        let ty = Type::from_str("ptr<ptr<bool>>").unwrap();
+
+        assert_eq!(ty, Type::Ptr(Box::new(Type::Ptr(Box::new(Type::Bool)))));
+        assert_eq!(ty.to_string(), "ptr<ptr<bool>>");
+        assert!(Type::from_str("ptr<>").is_err());
+    }
+
+    #[test]
+    fn test_serialize_round_trip() {
+        let s = r#"
+            {
+              "functions": [
+                {
+                  "instrs": [
+                    {
+                      "dest": "v0",
+                      "op": "const",
+                      "type": "int",
+                      "value": 1
+                    },
+                    {
+                      "args": [
+                        "v0",
+                        "v0"
+                      ],
+                      "dest": "v1",
+                      "op": "add",
+                      "type": "int"
+                    },
+                    {
+                      "args": [
+                        "v1"
+                      ],
+                      "op": "print"
+                    }
+                  ],
+                  "name": "main"
+                }
+              ]
+            }
+        "#;
+
+        let program: BrilProgram = serde_json::from_str(s).unwrap();
+        let serialized = serde_json::to_value(&program).unwrap();
+
+        let expected: serde_json::Value = serde_json::from_str(s).unwrap();
+        assert_eq!(serialized, expected);
+
+        let round_trip: BrilProgram = serde_json::from_value(serialized).unwrap();
+        assert_eq!(round_trip, program);
+    }
+
+    #[test]
+    fn test_deserialize_unknown_operation() {
+        let s = r#"{"op": "vecadd", "args": ["a", "b"], "dest": "c", "labels": ["l"]}"#;
+
+        let instruction: Instruction = serde_json::from_str(s).unwrap();
+
+        assert_eq!(instruction.op, Operation::Other("vecadd".to_string()));
+        assert!(instruction.is_valid());
+        assert_eq!(instruction.op.to_string(), "vecadd");
+        assert_eq!(
+            serde_json::to_value(&instruction).unwrap(),
+            serde_json::from_str::<serde_json::Value>(s).unwrap()
+        );
+        assert!(Operation::from_str("vecadd").is_err());
+    }
+
+    #[test]
+    fn test_effect_classification() {
+        let add = Instruction::add("c", "a", "b");
+        let call = Instruction {
+            op: Operation::Call,
+            dest: Some("r".into()),
+            funcs: Some(vec!["f".to_string()]),
+            ..Default::default()
+        };
+        let guard = Instruction {
+            op: Operation::Guard,
+            ..Default::default()
+        };
+
+        assert!(add.is_pure() && !add.has_side_effects());
+        assert!(!call.is_pure() && call.has_side_effects());
+        assert!(!call.is_control_flow());
+        assert!(guard.is_control_flow() && !guard.is_terminator());
+        assert!(Instruction::jmp("l").is_terminator());
+        assert!(Instruction::ret(None).is_control_flow());
+        assert!(!Operation::Phi.is_pure() && !Operation::Phi.has_side_effects());
+        assert!(Operation::Other("vecadd".to_string()).has_side_effects());
+    }
+
+    #[test]
+    fn test_defs_uses() {
+        let call = Instruction {
+            op: Operation::Call,
+            args: vec!["a".into(), "b".into()],
+            dest: Some("r".into()),
+            funcs: Some(vec!["f".to_string()]),
+            ..Default::default()
+        };
+        let br = Instruction::br("cond", "then", "else");
+        let store = Instruction {
+            op: Operation::Store,
+            args: vec!["p".into(), "v".into()],
+            ..Default::default()
+        };
+
+        assert_eq!(call.defs().map(|d| d.as_str()), Some("r"));
+        assert_eq!(call.uses().collect::<Vec<_>>(), vec!["a", "b"]);
+        assert_eq!(br.defs(), None);
+        assert_eq!(br.uses().collect::<Vec<_>>(), vec!["cond"]);
+        assert_eq!(store.defs(), None);
+        assert_eq!(store.uses().collect::<Vec<_>>(), vec!["p", "v"]);
+    }
+
+    #[test]
+    fn test_literal_serde() {
+        let literals = serde_json::from_str::<Vec<Literal>>(r#"[true, -3, 0.5, "c"]"#).unwrap();
+
+        assert_eq!(
+            literals,
+            vec![
+                Literal::Bool(true),
+                Literal::Int(-3),
+                Literal::Float(0.5),
+                Literal::Char('c')
+            ]
+        );
+        assert_eq!(
+            serde_json::to_string(&literals).unwrap(),
+            r#"[true,-3,0.5,"c"]"#
+        );
+        assert!(serde_json::from_str::<Literal>(r#""abc""#).is_err());
+        assert!(serde_json::from_str::<Literal>("18446744073709551615").is_err());
+        assert!(serde_json::from_str::<Literal>("null").is_err());
+    }
+
+    #[test]
+    fn test_instruction_attrs() {
+        let mut add = Instruction::add("c", "a", "b");
+
+        add.set_attr("vn", 3);
+        add.set_attr("live", serde_json::json!(["a", "b"]));
+
+        assert_eq!(add.attr("vn"), Some(&serde_json::json!(3)));
+        assert_eq!(
+            serde_json::to_value(&add).unwrap()["attrs"],
+            serde_json::json!({ "live": ["a", "b"], "vn": 3 })
+        );
+        assert_eq!(add.to_string(), r#"c = add a b;  # live=["a","b"] vn=3"#);
+
+        add.remove_attr("vn");
+        add.remove_attr("live");
+
+        assert_eq!(add.attrs, None);
+        assert!(serde_json::to_value(&add).unwrap().get("attrs").is_none());
+        add.attrs = Some(Default::default());
+        assert!(serde_json::to_value(&add).unwrap().get("attrs").is_none());
+    }
+
+    #[test]
+    fn test_function_lookup() {
+        let call = |func: &str| {
+            Code::Instruction(Instruction {
+                op: Operation::Call,
+                funcs: Some(vec![func.to_string()]),
+                ..Default::default()
+            })
+        };
+        let function = |name: &str, instrs| Function {
+            name: name.to_string(),
+            instrs,
+            ..Default::default()
+        };
+        let mut program = BrilProgram::new(vec![
+            function("main", vec![call("helper"), call("missing")]),
+            function("helper", vec![]),
+        ]);
+
+        assert_eq!(program.function("helper").unwrap().name, "helper");
+        assert!(program.function("other").is_none());
+
+        // A missing function doesn't rebuild an index holding every function
+        let stale = || program.index.0.lock().unwrap().contains_key("stale");
+        program
+            .index
+            .0
+            .lock()
+            .unwrap()
+            .insert("stale".to_string(), 0);
+        assert!(program.function("other").is_none());
+        assert!(stale());
+
+        let diagnostics = program.resolve_calls().unwrap_err();
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec!["function main, instruction 1: call to unknown function missing"]
+        );
+
+        // The lookup stays correct after the functions are modified
+        program.functions.swap(0, 1);
+        program.functions[1].name = "missing".to_string();
+        assert_eq!(program.function("missing").unwrap().instrs.len(), 2);
+        assert!(program.function("main").is_none());
+        assert_eq!(program.resolve_calls(), Ok(()));
+    }
 }
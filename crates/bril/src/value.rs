@@ -0,0 +1,275 @@
+//! Contains the canonical representation of the expressions computed
+//! by the instructions, shared by the value-based passes.
+
+use crate::types::{Literal, Operation};
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, BuildHasherDefault, Hasher};
+
+/// The number assigned to a value by a value numbering pass
+pub type ValueNumber = usize;
+
+/// Hashable representation of a [`Literal`]. Floats are
+/// compared using their bit representation.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+enum LiteralKey {
+    Bool(bool),
+    Int(i64),
+    Float(u64),
+    Char(char),
+}
+
+impl From<Literal> for LiteralKey {
+    fn from(literal: Literal) -> Self {
+        match literal {
+            Literal::Bool(v) => Self::Bool(v),
+            Literal::Int(v) => Self::Int(v),
+            Literal::Float(v) => Self::Float(v.to_bits()),
+            Literal::Char(v) => Self::Char(v),
+        }
+    }
+}
+
+impl From<LiteralKey> for Literal {
+    fn from(key: LiteralKey) -> Self {
+        match key {
+            LiteralKey::Bool(v) => Self::Bool(v),
+            LiteralKey::Int(v) => Self::Int(v),
+            LiteralKey::Float(v) => Self::Float(f64::from_bits(v)),
+            LiteralKey::Char(v) => Self::Char(v),
+        }
+    }
+}
+
+/// An expression over value numbers, such as `(add, [1, 2])` or
+/// `(const, [], 42)`. The expressions built by [`ValueExpr::new`] are
+/// canonical: the arguments of commutative operations are sorted, so `add a b` and `add b a`
+/// are the same expression, and the greater than comparisons are
+/// swapped, so `gt a b` and `lt b a` are too.
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct ValueExpr {
+    op: Operation,
+    args: Vec<ValueNumber>,
+    value: Option<LiteralKey>,
+}
+
+impl ValueExpr {
+    pub fn new(mut op: Operation, mut args: Vec<ValueNumber>, value: Option<Literal>) -> Self {
+        if op.is_commutative() {
+            args.sort();
+        }
+        let greater = matches!(
+            op,
+            Operation::Gt
+                | Operation::Ge
+                | Operation::Fgt
+                | Operation::Fge
+                | Operation::Cgt
+                | Operation::Cge
+        );
+        if let Some(swapped) = op.swapped().filter(|_| greater) {
+            op = swapped;
+            args.reverse();
+        }
+        Self::as_written(op, args, value)
+    }
+
+    /// Returns the expression with its arguments in the given order, which
+    /// is only equal to the expressions written the same way
+    pub fn as_written(op: Operation, args: Vec<ValueNumber>, value: Option<Literal>) -> Self {
+        Self {
+            op,
+            args,
+            value: value.map(Into::into),
+        }
+    }
+
+    pub fn op(&self) -> &Operation {
+        &self.op
+    }
+
+    /// Returns the arguments, in canonical order
+    pub fn args(&self) -> &[ValueNumber] {
+        &self.args
+    }
+
+    pub fn value(&self) -> Option<Literal> {
+        self.value.map(Into::into)
+    }
+}
+
+/// The identifier of an interned [`ValueExpr`]. Identifiers are cheap
+/// to copy and compare, and two identifiers from the same table are
+/// equal if and only if their expressions are equal.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ExprId(u32);
+
+/// Hasher of the keys which already are hashes, which are used as is
+#[derive(Default)]
+struct HashedKey(u64);
+
+impl Hasher for HashedKey {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, _bytes: &[u8]) {
+        unreachable!("only hashes are used as keys")
+    }
+
+    fn write_u64(&mut self, hash: u64) {
+        self.0 = hash;
+    }
+}
+
+type ByHash<V> = HashMap<u64, V, BuildHasherDefault<HashedKey>>;
+
+/// Hash-consing table of the expressions, each expression
+/// is stored once and referred to by its [`ExprId`]
+///
+/// The expressions are looked up by their 64-bit hash, and compared to the
+/// interned expression with the same hash to rule out collisions. The rare
+/// expressions whose hash collides with another one are kept apart.
+#[derive(Debug, Default)]
+pub struct ExprTable {
+    exprs: Vec<ValueExpr>,
+    hasher: RandomState,
+    ids: ByHash<ExprId>,
+    collisions: ByHash<Vec<ExprId>>,
+}
+
+impl ExprTable {
+    /// Interns the expression, returning the existing identifier if
+    /// the expression was already interned
+    pub fn intern(&mut self, expr: ValueExpr) -> ExprId {
+        let hash = self.hasher.hash_one(&expr);
+        self.intern_hashed(expr, hash)
+    }
+
+    fn intern_hashed(&mut self, expr: ValueExpr, hash: u64) -> ExprId {
+        if let Some(id) = self.get_hashed(&expr, hash) {
+            return id;
+        }
+
+        let id = ExprId(self.exprs.len() as u32);
+        self.exprs.push(expr);
+        if let Some(first) = self.ids.insert(hash, id) {
+            self.ids.insert(hash, first);
+            self.collisions.entry(hash).or_default().push(id);
+        }
+        id
+    }
+
+    /// Returns the identifier of the expression if it was interned
+    pub fn get(&self, expr: &ValueExpr) -> Option<ExprId> {
+        self.get_hashed(expr, self.hasher.hash_one(expr))
+    }
+
+    fn get_hashed(&self, expr: &ValueExpr, hash: u64) -> Option<ExprId> {
+        let first = *self.ids.get(&hash)?;
+        if self.expr(first) == expr {
+            return Some(first);
+        }
+        self.collisions
+            .get(&hash)?
+            .iter()
+            .copied()
+            .find(|id| self.expr(*id) == expr)
+    }
+
+    /// Returns the expression of the identifier
+    pub fn expr(&self, id: ExprId) -> &ValueExpr {
+        &self.exprs[id.0 as usize]
+    }
+
+    pub fn len(&self) -> usize {
+        self.exprs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.exprs.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ExprTable, ValueExpr};
+    use crate::types::{Literal, Operation};
+
+    #[test]
+    fn test_canonical_expressions() {
+        // Given
+        let add = ValueExpr::new(Operation::Add, vec![2, 1], None);
+        let sub = ValueExpr::new(Operation::Sub, vec![2, 1], None);
+
+        // When / Then
+        assert_eq!(add, ValueExpr::new(Operation::Add, vec![1, 2], None));
+        assert_eq!(add.args(), &[1, 2]);
+        assert_ne!(sub, ValueExpr::new(Operation::Sub, vec![1, 2], None));
+        assert_eq!(
+            ValueExpr::new(Operation::Gt, vec![2, 1], None),
+            ValueExpr::new(Operation::Lt, vec![1, 2], None)
+        );
+        assert_eq!(
+            ValueExpr::new(Operation::Fge, vec![2, 1], None).op(),
+            &Operation::Fle
+        );
+        assert_ne!(
+            ValueExpr::new(Operation::Gt, vec![2, 1], None),
+            ValueExpr::new(Operation::Lt, vec![2, 1], None)
+        );
+        assert_eq!(
+            ValueExpr::new(Operation::Const, vec![], Some(Literal::Float(0.5))).value(),
+            Some(Literal::Float(0.5))
+        );
+        assert_ne!(
+            ValueExpr::as_written(Operation::Add, vec![2, 1], None),
+            ValueExpr::as_written(Operation::Add, vec![1, 2], None)
+        );
+        assert_eq!(
+            ValueExpr::as_written(Operation::Gt, vec![2, 1], None).op(),
+            &Operation::Gt
+        );
+    }
+
+    #[test]
+    fn test_hash_consing() {
+        // Given
+        let mut table = ExprTable::default();
+        let one = ValueExpr::new(Operation::Const, vec![], Some(Literal::Int(1)));
+
+        // When
+        let a = table.intern(one.clone());
+        let b = table.intern(ValueExpr::new(Operation::Add, vec![0, 0], None));
+        let c = table.intern(one.clone());
+
+        // Then
+        assert_eq!(a, c);
+        assert_ne!(a, b);
+        assert_eq!(table.len(), 2);
+        assert_eq!(table.get(&one), Some(a));
+        assert_eq!(table.expr(b).op(), &Operation::Add);
+    }
+
+    #[test]
+    fn test_hash_collisions() {
+        // Given
+        let mut table = ExprTable::default();
+        let exprs =
+            [1, 2, 3].map(|v| ValueExpr::new(Operation::Const, vec![], Some(Literal::Int(v))));
+
+        // When
+        let ids = exprs.clone().map(|expr| table.intern_hashed(expr, 42));
+        let again = exprs.clone().map(|expr| table.intern_hashed(expr, 42));
+
+        // Then
+        assert_eq!(ids, again);
+        assert_eq!(table.len(), 3);
+        for (expr, id) in exprs.iter().zip(ids) {
+            assert_eq!(table.get_hashed(expr, 42), Some(id));
+            assert_eq!(table.expr(id), expr);
+        }
+        let four = ValueExpr::new(Operation::Const, vec![], Some(Literal::Int(4)));
+        assert_eq!(table.get_hashed(&four, 42), None);
+    }
+}
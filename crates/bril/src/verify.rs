@@ -0,0 +1,442 @@
+//! Contains the validation of a whole program.
+
+pub use crate::diagnostic::Diagnostic;
+use crate::types::{BrilProgram, Code, Function, Instruction, Operation, Type, Var};
+use std::collections::{HashMap, HashSet};
+
+/// Verifies the program, returning all the issues found. The program
+/// is well-formed if no diagnostics are returned.
+///
+/// The following properties are checked:
+///     - every instruction is valid
+///     - every used variable is defined in the function
+///     - every branch target exists in the function
+///     - every called function exists and receives the correct arguments
+///     - every returned value matches the return type of the function
+///     - the operands of each instruction have the expected type
+pub fn verify_program(program: &BrilProgram) -> Vec<Diagnostic> {
+    program
+        .functions
+        .iter()
+        .flat_map(|f| verify_function(f, program))
+        .collect()
+}
+
+fn verify_function(function: &Function, program: &BrilProgram) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let locations = locations(function);
+    let mut report = |index: Option<usize>, message: String| {
+        let mut diagnostic = Diagnostic::new(message).in_function(&function.name);
+        if let Some((block, index)) = index.and_then(|i| locations[i]) {
+            diagnostic = diagnostic.in_block(block).at_instruction(index);
+        }
+        diagnostics.push(diagnostic)
+    };
+
+    let instructions = function
+        .instrs
+        .iter()
+        .enumerate()
+        .filter_map(|(index, code)| match code {
+            Code::Instruction(i) => Some((index, i)),
+            Code::Label { .. } => None,
+        })
+        .collect::<Vec<_>>();
+
+    let labels = function
+        .instrs
+        .iter()
+        .filter_map(|code| match code {
+            Code::Label { label } => Some(label.as_str()),
+            Code::Instruction(_) => None,
+        })
+        .collect::<HashSet<_>>();
+
+    // Collect the type of each variable. The definition is checked
+    // conservatively: a variable can be defined anywhere in the function.
+    let mut types = HashMap::<&Var, &Type>::new();
+    let mut defined = function
+        .args
+        .iter()
+        .map(|a| &a.name)
+        .collect::<HashSet<_>>();
+    for arg in &function.args {
+        types.insert(&arg.name, &arg.r#type);
+    }
+    for (index, i) in &instructions {
+        let Some(dest) = i.dest.as_ref() else {
+            continue;
+        };
+        defined.insert(dest);
+        if let Some(ty) = i.r#type.as_ref() {
+            match types.get(dest) {
+                Some(t) if *t != ty => report(
+                    Some(*index),
+                    format!("variable {dest} redefined with type {ty}, previously {t}"),
+                ),
+                _ => {
+                    types.insert(dest, ty);
+                }
+            }
+        }
+    }
+
+    for (index, i) in instructions {
+        let index = Some(index);
+
+        if !i.is_valid() {
+            report(index, format!("invalid {} instruction", i.op));
+        }
+
+        for arg in &i.args {
+            if !defined.contains(arg) {
+                report(index, format!("undefined variable {arg}"));
+            }
+        }
+
+        // Phi labels refer to the predecessors of the block, which can be
+        // the unlabeled entry block, so only jump targets are verified
+        if i.op != Operation::Phi {
+            for label in i.labels.iter().flatten() {
+                if !labels.contains(label.as_str()) {
+                    report(index, format!("unknown label {label}"));
+                }
+            }
+        }
+
+        if i.op == Operation::Ret {
+            match (function.r#type.as_ref(), i.args.first()) {
+                (None, Some(_)) => report(
+                    index,
+                    "returned a value from a function without return type".to_string(),
+                ),
+                (Some(t), None) => report(index, format!("expected a returned value of type {t}")),
+                (Some(t), Some(arg)) => match types.get(arg) {
+                    Some(arg_type) if *arg_type != t => report(
+                        index,
+                        format!("expected {arg} to be of type {t}, got {arg_type}"),
+                    ),
+                    _ => (),
+                },
+                (None, None) => (),
+            }
+        }
+
+        // A copy has the type of the copied variable
+        if let (Operation::Id, Some(t), Some(arg)) = (&i.op, i.r#type.as_ref(), i.args.first()) {
+            match types.get(arg) {
+                Some(arg_type) if *arg_type != t => report(
+                    index,
+                    format!("expected {arg} to be of type {t}, got {arg_type}"),
+                ),
+                _ => (),
+            }
+        }
+
+        if i.op == Operation::Call {
+            for message in verify_call(i, program, &types) {
+                report(index, message);
+            }
+        }
+
+        if let Some((arg_type, result_type)) = signature(&i.op) {
+            for arg in &i.args {
+                match types.get(arg) {
+                    Some(t) if **t != arg_type => report(
+                        index,
+                        format!("expected {arg} to be of type {arg_type}, got {t}"),
+                    ),
+                    _ => (),
+                }
+            }
+            match (result_type, i.r#type.as_ref()) {
+                (Some(expected), Some(t)) if expected != *t => report(
+                    index,
+                    format!("expected result of type {expected}, got {t}"),
+                ),
+                _ => (),
+            }
+        }
+    }
+
+    diagnostics
+}
+
+/// Returns the location of each entry of the function body as the index
+/// of its basic block and its index in the block, none for labels. The
+/// blocks are the ones of [`split_blocks`]: they start at labels and after
+/// terminators, a label directly following another one starting an empty block.
+///
+/// [`split_blocks`]: crate::cfg::split_blocks
+fn locations(function: &Function) -> Vec<Option<(usize, usize)>> {
+    let mut block = 0;
+    let mut index = 0;
+    // Whether the current block has a label or instructions
+    let mut started = false;
+
+    function
+        .instrs
+        .iter()
+        .map(|code| match code {
+            Code::Label { .. } => {
+                if started {
+                    block += 1;
+                    index = 0;
+                }
+                started = true;
+                None
+            }
+            Code::Instruction(i) => {
+                let location = (block, index);
+                index += 1;
+                started = true;
+                if i.is_terminator() {
+                    block += 1;
+                    index = 0;
+                    started = false;
+                }
+                Some(location)
+            }
+        })
+        .collect()
+}
+
+/// Verifies the call targets an existing function with the correct arguments
+fn verify_call(
+    call: &Instruction,
+    program: &BrilProgram,
+    types: &HashMap<&Var, &Type>,
+) -> Vec<String> {
+    let mut messages = Vec::new();
+
+    for name in call.funcs.iter().flatten() {
+        let Some(callee) = program.function(name) else {
+            messages.push(format!("call to unknown function {name}"));
+            continue;
+        };
+
+        if callee.args.len() != call.args.len() {
+            messages.push(format!(
+                "function {name} expects {} arguments, got {}",
+                callee.args.len(),
+                call.args.len()
+            ));
+            continue;
+        }
+
+        match (callee.r#type.as_ref(), call.r#type.as_ref()) {
+            (None, _) if call.dest.is_some() => {
+                messages.push(format!("function {name} doesn't return a value"))
+            }
+            (Some(expected), Some(t)) if expected != t => {
+                messages.push(format!("expected result of type {expected}, got {t}"))
+            }
+            _ => (),
+        }
+
+        for (param, arg) in callee.args.iter().zip(call.args.iter()) {
+            match types.get(arg) {
+                Some(t) if **t != param.r#type => messages.push(format!(
+                    "expected {arg} to be of type {}, got {t}",
+                    param.r#type
+                )),
+                _ => (),
+            }
+        }
+    }
+
+    messages
+}
+
+/// Returns the type expected for all the arguments of the
+/// operation and the type of its result, if fixed
+pub(crate) fn signature(op: &Operation) -> Option<(Type, Option<Type>)> {
+    let signature = match op {
+        Operation::Add | Operation::Mul | Operation::Sub | Operation::Div => {
+            (Type::Int, Some(Type::Int))
+        }
+        Operation::Eq | Operation::Lt | Operation::Gt | Operation::Le | Operation::Ge => {
+            (Type::Int, Some(Type::Bool))
+        }
+        Operation::And | Operation::Or | Operation::Not => (Type::Bool, Some(Type::Bool)),
+        Operation::Br | Operation::Guard => (Type::Bool, None),
+        Operation::Fadd | Operation::Fsub | Operation::Fmul | Operation::Fdiv => {
+            (Type::Float, Some(Type::Float))
+        }
+        Operation::Feq | Operation::Flt | Operation::Fgt | Operation::Fle | Operation::Fge => {
+            (Type::Float, Some(Type::Bool))
+        }
+        Operation::Ceq | Operation::Clt | Operation::Cgt | Operation::Cle | Operation::Cge => {
+            (Type::Char, Some(Type::Bool))
+        }
+        Operation::Char2Int => (Type::Char, Some(Type::Int)),
+        Operation::Int2Char => (Type::Int, Some(Type::Char)),
+        _ => return None,
+    };
+    Some(signature)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{verify_program, Diagnostic};
+    use crate::cfg::split_blocks;
+    use crate::text::parse_program;
+
+    #[test]
+    fn test_verify_valid_program() {
+        let program = parse_program(
+            r#"
+            @main(n: int) {
+              one: int = const 1;
+              cond: bool = lt one n;
+              br cond .then .end;
+            .then:
+              r: int = call @double n;
+              print r;
+            .end:
+              ret;
+            }
+            @double(x: int): int {
+              r: int = add x x;
+              ret r;
+            }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(verify_program(&program), vec![]);
+    }
+
+    #[test]
+    fn test_verify_invalid_program() {
+        let program = parse_program(
+            r#"
+            @main {
+              one: int = const 1;
+              t: bool = const true;
+              sum: int = add one t;
+              print missing;
+              jmp .nowhere;
+              r: int = call @double one t;
+              u: int = call @unknown;
+            }
+            @double(x: int): int {
+              r: int = add x x;
+              ret r;
+            }
+            "#,
+        )
+        .unwrap();
+
+        let diagnostic = |block, index, message: &str| {
+            Diagnostic::new(message)
+                .in_function("main")
+                .in_block(block)
+                .at_instruction(index)
+        };
+        assert_eq!(
+            verify_program(&program),
+            vec![
+                diagnostic(0, 2, "expected t to be of type int, got bool"),
+                diagnostic(0, 3, "undefined variable missing"),
+                diagnostic(0, 4, "unknown label nowhere"),
+                diagnostic(1, 0, "function double expects 1 arguments, got 2"),
+                diagnostic(1, 1, "call to unknown function unknown"),
+            ]
+        );
+        assert_eq!(
+            verify_program(&program)[0].to_string(),
+            "function main, block 0, instruction 2: expected t to be of type int, got bool"
+        );
+    }
+
+    #[test]
+    fn test_verify_copy_types() {
+        let program = parse_program(
+            r#"
+            @main {
+              a: float = const 1;
+              b: int = id a;
+              c: float = id a;
+            }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            verify_program(&program)
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec!["function main, block 0, instruction 1: expected a to be of type int, got float"]
+        );
+    }
+
+    #[test]
+    fn test_verify_return_types() {
+        let program = parse_program(
+            r#"
+            @main {
+              a: int = call @none;
+              b: bool = call @int;
+              ret a;
+            }
+            @none {
+              ret;
+            }
+            @int: int {
+              t: bool = const true;
+              ret t;
+              ret;
+            }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            verify_program(&program)
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec![
+                "function main, block 0, instruction 0: function none doesn't return a value",
+                "function main, block 0, instruction 1: expected result of type int, got bool",
+                "function main, block 0, instruction 2: returned a value from a function without return type",
+                "function int, block 0, instruction 1: expected t to be of type int, got bool",
+                "function int, block 1, instruction 0: expected a returned value of type int",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_verify_consecutive_labels() {
+        let program = parse_program(
+            r#"
+            @main {
+            .a:
+            .b:
+              print missing;
+              jmp .c;
+            .c:
+            .d:
+              print other;
+            }
+            "#,
+        )
+        .unwrap();
+
+        let blocks = split_blocks(program.functions[0].instrs.clone());
+        assert_eq!(blocks[1].label.as_deref(), Some("b"));
+        assert_eq!(blocks[3].label.as_deref(), Some("d"));
+        assert_eq!(
+            verify_program(&program)
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec![
+                "function main, block 1, instruction 0: undefined variable missing",
+                "function main, block 3, instruction 0: undefined variable other",
+            ]
+        );
+    }
+}
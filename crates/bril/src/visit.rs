@@ -0,0 +1,165 @@
+//! Contains the traits used to walk the IR.
+//!
+//! Each method has a default implementation which walks down
+//! the children of the node, so implementors only need to override
+//! the methods for the nodes they are interested in.
+
+use crate::types::{Block, BrilProgram, Code, Function, Instruction};
+
+/// Walks the IR by reference
+pub trait Visitor {
+    fn visit_program(&mut self, program: &BrilProgram) {
+        walk_program(self, program)
+    }
+
+    fn visit_function(&mut self, function: &Function) {
+        walk_function(self, function)
+    }
+
+    /// Called on standalone blocks, see [`walk_block`]
+    fn visit_block(&mut self, block: &Block) {
+        walk_block(self, block)
+    }
+
+    fn visit_label(&mut self, _label: &str) {}
+
+    fn visit_instruction(&mut self, _instruction: &Instruction) {}
+}
+
+pub fn walk_program<V: Visitor + ?Sized>(visitor: &mut V, program: &BrilProgram) {
+    for function in &program.functions {
+        visitor.visit_function(function);
+    }
+}
+
+pub fn walk_function<V: Visitor + ?Sized>(visitor: &mut V, function: &Function) {
+    for code in &function.instrs {
+        match code {
+            Code::Label { label } => visitor.visit_label(label),
+            Code::Instruction(instruction) => visitor.visit_instruction(instruction),
+        }
+    }
+}
+
+pub fn walk_block<V: Visitor + ?Sized>(visitor: &mut V, block: &Block) {
+    for instruction in block {
+        visitor.visit_instruction(instruction);
+    }
+}
+
+/// Walks the IR by mutable reference
+pub trait VisitorMut {
+    fn visit_program_mut(&mut self, program: &mut BrilProgram) {
+        walk_program_mut(self, program)
+    }
+
+    fn visit_function_mut(&mut self, function: &mut Function) {
+        walk_function_mut(self, function)
+    }
+
+    /// Called on standalone blocks, see [`walk_block_mut`]
+    fn visit_block_mut(&mut self, block: &mut Block) {
+        walk_block_mut(self, block)
+    }
+
+    fn visit_label_mut(&mut self, _label: &mut String) {}
+
+    fn visit_instruction_mut(&mut self, _instruction: &mut Instruction) {}
+}
+
+pub fn walk_program_mut<V: VisitorMut + ?Sized>(visitor: &mut V, program: &mut BrilProgram) {
+    for function in &mut program.functions {
+        visitor.visit_function_mut(function);
+    }
+}
+
+pub fn walk_function_mut<V: VisitorMut + ?Sized>(visitor: &mut V, function: &mut Function) {
+    for code in &mut function.instrs {
+        match code {
+            Code::Label { label } => visitor.visit_label_mut(label),
+            Code::Instruction(instruction) => visitor.visit_instruction_mut(instruction),
+        }
+    }
+}
+
+pub fn walk_block_mut<V: VisitorMut + ?Sized>(visitor: &mut V, block: &mut Block) {
+    for instruction in block {
+        visitor.visit_instruction_mut(instruction);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Visitor, VisitorMut};
+    use crate::text::parse_program;
+    use crate::types::{Instruction, Operation};
+
+    const PROGRAM: &str = r#"
+        @main {
+          a: int = const 1;
+          b: int = add a a;
+        .end:
+          print b;
+        }
+        @other {
+          c: int = const 2;
+        }
+    "#;
+
+    #[derive(Default)]
+    struct Counter {
+        labels: usize,
+        instructions: usize,
+    }
+
+    impl Visitor for Counter {
+        fn visit_label(&mut self, _label: &str) {
+            self.labels += 1;
+        }
+
+        fn visit_instruction(&mut self, _instruction: &Instruction) {
+            self.instructions += 1;
+        }
+    }
+
+    struct AddToMul;
+
+    impl VisitorMut for AddToMul {
+        fn visit_instruction_mut(&mut self, instruction: &mut Instruction) {
+            if instruction.op == Operation::Add {
+                instruction.op = Operation::Mul;
+            }
+        }
+    }
+
+    #[test]
+    fn test_visitor() {
+        let program = parse_program(PROGRAM).unwrap();
+
+        let mut counter = Counter::default();
+        counter.visit_program(&program);
+
+        assert_eq!(counter.labels, 1);
+        assert_eq!(counter.instructions, 4);
+    }
+
+    #[test]
+    fn test_visitor_mut() {
+        let mut program = parse_program(PROGRAM).unwrap();
+
+        AddToMul.visit_program_mut(&mut program);
+
+        let mut block = vec![Instruction::add("b", "a", "a")];
+        AddToMul.visit_block_mut(&mut block);
+
+        let mut counter = Counter::default();
+        counter.visit_block(&block);
+
+        assert_eq!(block[0].op, Operation::Mul);
+        assert_eq!(counter.instructions, 1);
+        assert_eq!(
+            crate::text::emit_program(&program),
+            "@main {\n  a: int = const 1;\n  b: int = mul a a;\n.end:\n  print b;\n}\n@other {\n  c: int = const 2;\n}\n"
+        );
+    }
+}
@@ -0,0 +1,30 @@
+//! Checks that programs in the reference Bril JSON format survive
+//! a round-trip through the IR.
+
+use bril::types::BrilProgram;
+use std::fs;
+use std::path::Path;
+
+#[test]
+fn test_round_trip_fixtures() {
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+
+    let mut count = 0;
+    for entry in fs::read_dir(fixtures).unwrap() {
+        // Given
+        let path = entry.unwrap().path();
+        let json = fs::read_to_string(&path).unwrap();
+        let expected: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        // When
+        let program: BrilProgram = serde_json::from_str(&json)
+            .unwrap_or_else(|err| panic!("failed to parse {}: {err}", path.display()));
+        let actual = serde_json::to_value(&program).unwrap();
+
+        // Then
+        assert_eq!(actual, expected, "round-trip of {}", path.display());
+        count += 1;
+    }
+
+    assert!(count > 0, "no fixtures found");
+}
@@ -1,84 +1,208 @@
-use bril::types::Block;
+use bril::cfg::Cfg;
+use bril::types::{Block, Function, Instruction, Operation, Var};
 use std::collections::{HashMap, HashSet};
 
 /// Returns optimisations on the block for a multi pass of Dead Code Elimination (DCE).
 pub fn multi_pass_dce(mut block: Block) -> Block {
+    run_in_place(&mut block);
+    block
+}
+
+/// Applies a multi pass of Dead Code Elimination (DCE) on the block in place.
+pub fn run_in_place(block: &mut Block) {
+    run_in_place_with_exits(block, &Exits::default());
+}
+
+/// Applies a multi pass of Dead Code Elimination (DCE) on the block in place,
+/// keeping the variables live when control leaves the block.
+fn run_in_place_with_exits(block: &mut Block, exits: &Exits) {
     let mut instr_len = 0;
 
     // Until a single pass of dce doesn't remove code, we keep looping
     while instr_len != block.len() {
         instr_len = block.len();
-        block = single_pass_dce(block);
+        single_pass_dce(block, exits);
     }
+}
 
-    block
+/// Applies a multi pass of Dead Code Elimination (DCE) in place on each basic
+/// block of the function. The definitions of the variables live when control
+/// leaves their block, at its end or at a guard, are kept for the blocks
+/// using them. The function is left as is if it jumps to an unknown label.
+pub fn run_function_in_place(function: &mut Function) {
+    let Ok(mut cfg) = Cfg::new(function) else {
+        return;
+    };
+    let labels = labels(&cfg);
+    let live_in = live_variables(&cfg, &labels);
+    for block in 0..cfg.blocks.len() {
+        let exits = exits(&cfg, &labels, &live_in, block);
+        run_in_place_with_exits(&mut cfg.blocks[block].instrs, &exits);
+    }
+    function.instrs = cfg.into_instrs();
 }
 
-/// Returns optimisations on the block for a single pass of Dead Code Elimination (DCE).
-/// Also removes assignment of variables which are not used before reassignment.
-fn single_pass_dce(mut block: Block) -> Block {
-    let mut used = HashMap::new();
-    let mut created = HashSet::new();
-    let mut remove = HashMap::new();
-    let mut prev_index = HashMap::<String, usize, _>::new();
+/// The variables live when control leaves a block
+#[derive(Debug, Default)]
+struct Exits {
+    /// At the end of the block
+    end: HashSet<Var>,
+    /// At the guards of the function, by label of the block they abort to
+    aborts: HashMap<String, HashSet<Var>>,
+}
 
-    // Each time a variable is used in an operation, add it to the mapping
-    for (index, instr) in block.iter().enumerate() {
-        let dest = instr.dest.clone().unwrap_or_default();
-
-        // If the destination is not newly inserted and the used doesn't contain
-        // the destination, the variable has been assigned but never used. We register
-        // it for deletion.
-        if !used.contains_key(&dest) && !created.insert(dest.clone()) {
-            let prev_index = prev_index.get(&dest).copied().unwrap_or_default();
-            remove.insert(prev_index, true);
-        }
+/// Returns the index of each labelled block of the graph
+fn labels(cfg: &Cfg) -> HashMap<String, usize> {
+    cfg.blocks
+        .iter()
+        .enumerate()
+        .filter_map(|(index, block)| Some((block.label.clone()?, index)))
+        .collect()
+}
 
-        // Insert the destination has being created
-        // Add has prev_index
-        // Remove from used
-        if let Some(d) = instr.dest.as_ref() {
-            created.insert(d.clone());
-            prev_index.insert(d.clone(), index);
-            used.remove(d);
-        }
+/// Returns the variables live when control leaves the block, from the
+/// variables live at the start of the other blocks. The guards of the whole
+/// function are included, as a speculation can start in the block and abort
+/// from a later one.
+fn exits(
+    cfg: &Cfg,
+    labels: &HashMap<String, usize>,
+    live_in: &[HashSet<Var>],
+    block: usize,
+) -> Exits {
+    let end = cfg
+        .successors(block)
+        .iter()
+        .flat_map(|s| &live_in[*s])
+        .copied()
+        .collect();
+    let aborts = cfg
+        .blocks
+        .iter()
+        .flat_map(|block| &block.instrs)
+        .filter(|i| i.op == Operation::Guard)
+        .flat_map(|i| i.labels.iter().flatten())
+        .filter_map(|label| Some((label.clone(), live_in[*labels.get(label)?].clone())))
+        .collect();
+    Exits { end, aborts }
+}
 
-        // Insert the args as being used
-        for arg in instr.args.iter() {
-            used.insert(arg.clone(), true);
+/// Returns the variables live at the start of each block of the graph. They
+/// are computed backward from the uses, until they no longer change.
+fn live_variables(cfg: &Cfg, labels: &HashMap<String, usize>) -> Vec<HashSet<Var>> {
+    let mut live_in = vec![HashSet::new(); cfg.blocks.len()];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for block in (0..cfg.blocks.len()).rev() {
+            let exits = exits(cfg, labels, &live_in, block);
+            let mut live = exits.end;
+            for i in cfg.blocks[block].instrs.iter().rev() {
+                if let Some(dest) = i.defs() {
+                    live.remove(dest);
+                }
+                live.extend(i.uses().copied());
+                live.extend(aborted(i, &exits.aborts));
+            }
+            if live != live_in[block] {
+                live_in[block] = live;
+                changed = true;
+            }
         }
     }
+    live_in
+}
+
+/// Returns the variables read by the instruction when a guard aborts: the
+/// variables live at the block it aborts to, which hold again the values
+/// they had at the start of the speculation. They are used by the guard and
+/// by the speculation, as the guard can be in a later block.
+fn aborted<'a>(
+    instr: &'a Instruction,
+    aborts: &'a HashMap<String, HashSet<Var>>,
+) -> impl Iterator<Item = Var> + 'a {
+    let live = match instr.op {
+        Operation::Guard => instr
+            .labels
+            .iter()
+            .flatten()
+            .filter_map(|label| aborts.get(label))
+            .collect(),
+        Operation::Speculate => aborts.values().collect(),
+        _ => Vec::new(),
+    };
+    live.into_iter().flatten().copied()
+}
 
-    // Iterate all the instructions, removing assignments to variables that are not used
+/// Removes the instructions at the indices, which another pass found dead,
+/// without analysing the block again. The indices must be in increasing order.
+pub fn remove_hinted(block: &mut Block, dead: &[usize]) {
+    let mut dead = dead.iter().peekable();
     let mut index = 0usize;
-    block.retain(move |i| {
-        if let Some(dest) = i.dest.as_ref() {
-            if !used.contains_key(dest) {
-                index += 1;
-                return false;
+    block.retain(|_| {
+        let keep = dead.next_if_eq(&&index).is_none();
+        index += 1;
+        keep
+    });
+}
+
+/// Applies a single pass of Dead Code Elimination (DCE) on the block in place.
+/// Each definition is removed if it isn't used before the variable is
+/// reassigned, or before the end of the block unless the variable is live
+/// when control leaves the block.
+fn single_pass_dce(block: &mut Block, exits: &Exits) {
+    // The index of the last definition of each variable, and whether it was used since
+    let mut pending = HashMap::<Var, (usize, bool)>::new();
+    let mut dead = HashSet::new();
+
+    for (index, instr) in block.iter().enumerate() {
+        // The args use the last definitions, along with the variables live at
+        // the block a guard aborts to. They are read before the destination
+        // is assigned, so (a = add a b) uses the previous definition of a.
+        for arg in instr.uses().copied().chain(aborted(instr, &exits.aborts)) {
+            if let Some((_, used)) = pending.get_mut(&arg) {
+                *used = true;
             }
         }
-        if remove.contains_key(&index) {
-            index += 1;
-            return false;
+
+        // The previous definition of the destination is dead if it wasn't used
+        if let Some(dest) = instr.defs() {
+            if let Some((previous, false)) = pending.insert(*dest, (index, false)) {
+                dead.insert(previous);
+            }
         }
-        index += 1;
+    }
 
-        true
-    });
+    // The last definitions are dead unless they are used in the block or
+    // the variable is live at its end
+    dead.extend(
+        pending
+            .into_iter()
+            .filter(|(var, (_, used))| !used && !exits.end.contains(var))
+            .map(|(_, (index, _))| index),
+    );
 
-    block
+    // Instructions with side effects, such as calls, memory accesses or
+    // speculation, are never removed even if their result is unused.
+    let mut index = 0usize;
+    block.retain(|i| {
+        let keep = i.has_side_effects() || !dead.contains(&index);
+        index += 1;
+        keep
+    });
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{multi_pass_dce, single_pass_dce};
+    use super::{multi_pass_dce, remove_hinted, run_function_in_place, single_pass_dce, Exits};
+    use bril::text::parse_program;
+    use bril::types::{Instruction, Operation};
     use bril_macros::instruction;
 
     #[test]
     fn test_single_pass_dce() {
         // Given
-        let block = vec![
+        let mut block = vec![
             instruction!(op = const, value = 1, dest = a),
             instruction!(op = const, value = 2, dest = b),
             instruction!(op = const, value = 2, dest = c),
@@ -87,7 +211,7 @@ mod tests {
         ];
 
         // When
-        let optimized_block = single_pass_dce(block);
+        single_pass_dce(&mut block, &Exits::default());
 
         // Then
         let expected_block = vec![
@@ -97,20 +221,20 @@ mod tests {
             instruction!(op = print, args = [sum]),
         ];
 
-        assert_eq!(optimized_block, expected_block);
+        assert_eq!(block, expected_block);
     }
 
     #[test]
     fn test_reassignment_single_pass_dce() {
         // Given
-        let block = vec![
+        let mut block = vec![
             instruction!(op = const, value = 1, dest = a),
             instruction!(op = const, value = 2, dest = a),
             instruction!(op = print, args = [a]),
         ];
 
         // When
-        let optimized_block = single_pass_dce(block);
+        single_pass_dce(&mut block, &Exits::default());
 
         // Then
         let expected_block = vec![
@@ -118,6 +242,33 @@ mod tests {
             instruction!(op = print, args = [a]),
         ];
 
+        assert_eq!(block, expected_block);
+    }
+
+    #[test]
+    fn test_used_then_reassigned_multi_pass_dce() {
+        // Given
+        let block = vec![
+            instruction!(op = const, value = 1, dest = a),
+            instruction!(op = print, args = [a]),
+            instruction!(op = const, value = 2, dest = a),
+            instruction!(op = const, value = 3, dest = b),
+            instruction!(op = add, args = [b, b], dest = b),
+            instruction!(op = print, args = [b]),
+        ];
+
+        // When
+        let optimized_block = multi_pass_dce(block);
+
+        // Then
+        let expected_block = vec![
+            instruction!(op = const, value = 1, dest = a),
+            instruction!(op = print, args = [a]),
+            instruction!(op = const, value = 3, dest = b),
+            instruction!(op = add, args = [b, b], dest = b),
+            instruction!(op = print, args = [b]),
+        ];
+
         assert_eq!(optimized_block, expected_block);
     }
 
@@ -173,4 +324,274 @@ mod tests {
 
         assert_eq!(optimized_block, expected_block);
     }
+
+    #[test]
+    fn test_calls_multi_pass_dce() {
+        // Given
+        let call = |dest: &str| Instruction {
+            op: Operation::Call,
+            args: vec!["a".into()],
+            dest: Some(dest.into()),
+            funcs: Some(vec!["f".to_string()]),
+            ..Default::default()
+        };
+        let block = vec![
+            instruction!(op = const, value = 1, dest = a),
+            call("r"),
+            call("r"),
+            instruction!(op = print, args = [a]),
+        ];
+
+        // When
+        let optimized_block = multi_pass_dce(block.clone());
+
+        // Then
+        assert_eq!(optimized_block, block);
+    }
+
+    #[test]
+    fn test_memory_multi_pass_dce() {
+        // Given
+        let block = vec![
+            instruction!(op = const, value = 1, dest = size),
+            instruction!(op = alloc, args = [size], dest = ptr),
+            instruction!(op = store, args = [ptr, size]),
+            instruction!(op = store, args = [ptr, size]),
+            instruction!(op = load, args = [ptr], dest = unused),
+            instruction!(op = ptradd, args = [ptr, size], dest = other),
+            instruction!(op = free, args = [ptr]),
+        ];
+
+        // When
+        let optimized_block = multi_pass_dce(block);
+
+        // Then
+        let expected_block = vec![
+            instruction!(op = const, value = 1, dest = size),
+            instruction!(op = alloc, args = [size], dest = ptr),
+            instruction!(op = store, args = [ptr, size]),
+            instruction!(op = store, args = [ptr, size]),
+            instruction!(op = load, args = [ptr], dest = unused),
+            instruction!(op = free, args = [ptr]),
+        ];
+
+        assert_eq!(optimized_block, expected_block);
+    }
+
+    #[test]
+    fn test_branch_labels_multi_pass_dce() {
+        // Given
+        let br = Instruction {
+            op: Operation::Br,
+            args: vec!["cond".into()],
+            labels: Some(vec!["end".to_string(), "other".to_string()]),
+            ..Default::default()
+        };
+        let block = vec![
+            instruction!(op = const, value = 1, dest = end),
+            instruction!(op = const, value = 1, dest = cond),
+            br.clone(),
+        ];
+
+        // When
+        let optimized_block = multi_pass_dce(block);
+
+        // Then
+        let expected_block = vec![instruction!(op = const, value = 1, dest = cond), br];
+
+        assert_eq!(optimized_block, expected_block);
+    }
+
+    #[test]
+    fn test_unknown_operation_multi_pass_dce() {
+        // Given
+        let block = vec![
+            instruction!(op = const, value = 1, dest = a),
+            Instruction {
+                op: Operation::Other("vecstore".to_string()),
+                args: vec!["a".into()],
+                dest: Some("unused".into()),
+                ..Default::default()
+            },
+        ];
+
+        // When
+        let optimized_block = multi_pass_dce(block.clone());
+
+        // Then
+        assert_eq!(optimized_block, block);
+    }
+
+    #[test]
+    fn test_run_function_in_place() {
+        // Given
+        let mut program = parse_program(
+            r#"
+            @main(c: bool) {
+              a: int = const 1;
+              a: int = const 2;
+              print a;
+              br c .then .end;
+            .then:
+              b: int = const 3;
+            .end:
+              b: int = const 4;
+              print b;
+            }
+            "#,
+        )
+        .unwrap();
+
+        // When
+        run_function_in_place(&mut program.functions[0]);
+
+        // Then
+        let expected = parse_program(
+            r#"
+            @main(c: bool) {
+              a: int = const 2;
+              print a;
+              br c .then .end;
+            .then:
+            .end:
+              b: int = const 4;
+              print b;
+            }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(program, expected);
+    }
+
+    #[test]
+    fn test_run_function_in_place_live_out() {
+        // Given
+        let mut program = parse_program(
+            r#"
+            @main(c: bool) {
+              a: int = const 1;
+              b: int = const 2;
+              b: int = const 3;
+              br c .left .right;
+            .left:
+              print a;
+              jmp .right;
+            .right:
+              d: int = const 4;
+              print b;
+            }
+            "#,
+        )
+        .unwrap();
+
+        // When
+        run_function_in_place(&mut program.functions[0]);
+
+        // Then
+        let expected = parse_program(
+            r#"
+            @main(c: bool) {
+              a: int = const 1;
+              b: int = const 3;
+              br c .left .right;
+            .left:
+              print a;
+              jmp .right;
+            .right:
+              print b;
+            }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(program, expected);
+    }
+
+    #[test]
+    fn test_run_function_in_place_used_then_reassigned() {
+        // Given
+        let mut program = parse_program(
+            r#"
+            @main {
+              a: int = const 1;
+              print a;
+              a: int = const 2;
+              jmp .next;
+            .next:
+              a: int = const 3;
+              print a;
+            }
+            "#,
+        )
+        .unwrap();
+
+        // When
+        run_function_in_place(&mut program.functions[0]);
+
+        // Then
+        let expected = parse_program(
+            r#"
+            @main {
+              a: int = const 1;
+              print a;
+              jmp .next;
+            .next:
+              a: int = const 3;
+              print a;
+            }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(program, expected);
+    }
+
+    #[test]
+    fn test_run_function_in_place_guard() {
+        // Given
+        let mut program = parse_program(
+            r#"
+            @main(c: bool) {
+              a: int = const 1;
+              speculate;
+              a: int = const 2;
+              guard c .abort;
+              commit;
+              print a;
+              ret;
+            .abort:
+              print a;
+            }
+            "#,
+        )
+        .unwrap();
+        let original = program.clone();
+
+        // When
+        run_function_in_place(&mut program.functions[0]);
+
+        // Then
+        assert_eq!(program, original);
+    }
+
+    #[test]
+    fn test_remove_hinted() {
+        // Given
+        let mut block = vec![
+            instruction!(op = const, value = 1, dest = a),
+            instruction!(op = id, args = [a], dest = b),
+            instruction!(op = id, args = [a], dest = c),
+            instruction!(op = id, args = [a], dest = d),
+            instruction!(op = print, args = [a]),
+        ];
+
+        // When
+        remove_hinted(&mut block, &[1, 3]);
+
+        // Then
+        let expected_block = vec![
+            instruction!(op = const, value = 1, dest = a),
+            instruction!(op = id, args = [a], dest = c),
+            instruction!(op = print, args = [a]),
+        ];
+        assert_eq!(block, expected_block);
+    }
 }
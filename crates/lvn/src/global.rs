@@ -0,0 +1,367 @@
+//! Contains the Global Value Numbering, which extends the numbering of a
+//! block to all the blocks it dominates.
+
+use crate::{guard_targets, number_block, Shared, Table};
+use bril::cfg::Cfg;
+use bril::diagnostic::Diagnostic;
+use bril::types::Function;
+use bril_macros::Pass;
+use std::collections::HashSet;
+
+/// The Global Value Numbering pass, run on each function in SSA form
+#[derive(Pass)]
+#[pass(name = "gvn", function = global_value_numbering_function)]
+pub struct GlobalValueNumbering;
+
+/// Applies [`global_value_numbering`] on the graph of the function
+pub fn global_value_numbering_function(function: &mut Function) -> eyre::Result<()> {
+    let mut cfg = Cfg::new(function)?;
+    global_value_numbering(&mut cfg).map_err(|error| match error.downcast::<Diagnostic>() {
+        Ok(diagnostic) => eyre::Report::new(diagnostic.in_function(&function.name)),
+        Err(error) => error,
+    })?;
+    function.instrs = cfg.into_instrs();
+    Ok(())
+}
+
+/// Applies the numbering on the dominator tree of a graph in SSA form. Each
+/// block starts with the values of its immediate dominator, so that a value
+/// computed by a block is reused by the blocks it dominates. As each variable
+/// is defined once, the variables holding the values are never reassigned.
+///
+/// The blocks a guard aborts to start from scratch instead, since the abort
+/// leaves the dominator before its end and restores the values from before
+/// the speculation. The blocks they dominate start from their values.
+/// The phis are given new numbers, and the unreachable blocks are kept as is.
+/// The loaded values are only inherited by the blocks whose single predecessor
+/// is their dominator, as the memory may be written on the other paths.
+/// Fails if a variable is defined more than once.
+pub fn global_value_numbering(cfg: &mut Cfg) -> eyre::Result<()> {
+    let mut defined = HashSet::new();
+    for (block, instrs) in cfg.blocks.iter().map(|b| &b.instrs).enumerate() {
+        for (index, instruction) in instrs.iter().enumerate() {
+            let Some(dest) = instruction.dest else {
+                continue;
+            };
+            if !defined.insert(dest) {
+                let message = format!("{dest} is defined more than once, which isn't SSA form");
+                let diagnostic = Diagnostic::new(message)
+                    .in_block(block)
+                    .at_instruction(index);
+                return Err(eyre::Report::new(diagnostic));
+            }
+        }
+    }
+
+    let mut children = vec![Vec::new(); cfg.blocks.len()];
+    for (block, idom) in cfg.immediate_dominators().into_iter().enumerate() {
+        if let Some(idom) = idom {
+            children[idom].push(block);
+        }
+    }
+
+    let mut shared = Shared::default();
+    for block in &cfg.blocks {
+        shared.extend(&block.instrs);
+    }
+
+    let aborted = guard_targets(cfg);
+    let mut pending = match cfg.blocks.is_empty() {
+        true => Vec::new(),
+        false => vec![(0, Table::default())],
+    };
+    while let Some((block, mut table)) = pending.pop() {
+        number_block(&mut cfg.blocks[block].instrs, &mut table, &mut shared)
+            .map_err(|diagnostic| eyre::Report::new(diagnostic.in_block(block)))?;
+        for &child in children[block].iter().rev() {
+            if aborted.contains(&child) {
+                pending.push((child, Table::default()));
+                continue;
+            }
+            let mut table = table.clone();
+            if cfg.predecessors(child) != [block] {
+                table.forget_memory();
+            }
+            pending.push((child, table));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{global_value_numbering, GlobalValueNumbering};
+    use bril::cfg::Cfg;
+    use bril::pass::Pass;
+    use bril::text::parse_program;
+
+    #[test]
+    fn test_global_value_numbering() {
+        // Given
+        let program = parse_program(
+            r#"
+            @main(x: int, c: bool) {
+              a: int = add x x;
+              br c .then .else;
+            .then:
+              b: int = mul x x;
+              jmp .end;
+            .else:
+              d: int = mul x x;
+            .end:
+              p: int = phi b d .then .else;
+              e: int = add x x;
+              f: int = mul x x;
+              print e f p;
+            }
+            "#,
+        )
+        .unwrap();
+        let mut cfg = Cfg::new(&program.functions[0]).unwrap();
+
+        // When
+        global_value_numbering(&mut cfg).expect("failed to apply gvn");
+
+        // Then
+        let expected = parse_program(
+            r#"
+            @main(x: int, c: bool) {
+              a: int = add x x;
+              br c .then .else;
+            .then:
+              b: int = mul x x;
+              jmp .end;
+            .else:
+              d: int = mul x x;
+            .end:
+              p: int = phi b d .then .else;
+              e: int = id a;
+              f: int = mul x x;
+              print a f p;
+            }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(cfg.into_instrs(), expected.functions[0].instrs);
+    }
+
+    #[test]
+    fn test_global_value_numbering_phis() {
+        // Given
+        let program = parse_program(
+            r#"
+            @main(x: int, y: int, c: bool) {
+              br c .left .right;
+            .left:
+              a: int = add x y;
+              jmp .join;
+            .right:
+              b: int = add y x;
+              jmp .join;
+            .join:
+              p: int = phi a b .left .right;
+              q: int = phi b a .right .left;
+              r: int = phi b a .left .right;
+              s: int = add p q;
+              t: int = add q p;
+              print s t r;
+            }
+            "#,
+        )
+        .unwrap();
+        let mut cfg = Cfg::new(&program.functions[0]).unwrap();
+
+        // When
+        global_value_numbering(&mut cfg).expect("failed to apply gvn");
+
+        // Then
+        let expected = parse_program(
+            r#"
+            @main(x: int, y: int, c: bool) {
+              br c .left .right;
+            .left:
+              a: int = add x y;
+              jmp .join;
+            .right:
+              b: int = add y x;
+              jmp .join;
+            .join:
+              p: int = phi a b .left .right;
+              q: int = id p;
+              r: int = phi b a .left .right;
+              s: int = add p p;
+              t: int = id s;
+              print s s r;
+            }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(cfg.into_instrs(), expected.functions[0].instrs);
+    }
+
+    #[test]
+    fn test_global_value_numbering_loop() {
+        // Given
+        let mut program = parse_program(
+            r#"
+            @main(n: int) {
+            .entry:
+              one: int = const 1;
+              zero: int = const 0;
+            .header:
+              i: int = phi zero next .entry .body;
+              cond: bool = lt i n;
+              br cond .body .exit;
+            .body:
+              step: int = const 1;
+              next: int = add i step;
+              jmp .header;
+            .exit:
+              print i;
+            }
+            "#,
+        )
+        .unwrap();
+
+        // When
+        GlobalValueNumbering
+            .run_on_function(&mut program.functions[0])
+            .expect("failed to apply gvn");
+
+        // Then
+        let expected = parse_program(
+            r#"
+            @main(n: int) {
+            .entry:
+              one: int = const 1;
+              zero: int = const 0;
+            .header:
+              i: int = phi zero next .entry .body;
+              cond: bool = lt i n;
+              br cond .body .exit;
+            .body:
+              step: int = id one;
+              next: int = add i one;
+              jmp .header;
+            .exit:
+              print i;
+            }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(program, expected);
+    }
+
+    #[test]
+    fn test_global_value_numbering_loads() {
+        // Given
+        let program = parse_program(
+            r#"
+            @main(p: ptr<int>, c: bool) {
+              a: int = load p;
+              br c .then .end;
+            .then:
+              b: int = load p;
+              store p b;
+            .end:
+              d: int = load p;
+              print a b d;
+            }
+            "#,
+        )
+        .unwrap();
+        let mut cfg = Cfg::new(&program.functions[0]).unwrap();
+
+        // When
+        global_value_numbering(&mut cfg).expect("failed to apply gvn");
+
+        // Then
+        let expected = parse_program(
+            r#"
+            @main(p: ptr<int>, c: bool) {
+              a: int = load p;
+              br c .then .end;
+            .then:
+              b: int = id a;
+              store p a;
+            .end:
+              d: int = load p;
+              print a b d;
+            }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(cfg.into_instrs(), expected.functions[0].instrs);
+    }
+
+    #[test]
+    fn test_global_value_numbering_guard() {
+        // Given
+        let program = parse_program(
+            r#"
+            @main(x: int, c: bool) {
+              speculate;
+              a: int = add x x;
+              guard c .abort;
+              commit;
+              print a;
+              ret;
+            .abort:
+              d: int = add x x;
+              e: int = add x x;
+              print d e;
+            }
+            "#,
+        )
+        .unwrap();
+        let mut cfg = Cfg::new(&program.functions[0]).unwrap();
+
+        // When
+        global_value_numbering(&mut cfg).expect("failed to apply gvn");
+
+        // Then
+        let expected = parse_program(
+            r#"
+            @main(x: int, c: bool) {
+              speculate;
+              a: int = add x x;
+              guard c .abort;
+              commit;
+              print a;
+              ret;
+            .abort:
+              d: int = add x x;
+              e: int = id d;
+              print d d;
+            }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(cfg.into_instrs(), expected.functions[0].instrs);
+    }
+
+    #[test]
+    fn test_global_value_numbering_not_ssa() {
+        // Given
+        let mut program = parse_program(
+            r#"
+            @main {
+              a: int = const 1;
+            .next:
+              a: int = const 2;
+            }
+            "#,
+        )
+        .unwrap();
+
+        // When
+        let result = GlobalValueNumbering.run_on_function(&mut program.functions[0]);
+
+        // Then
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "function main, block 1, instruction 0: a is defined more than once, which isn't SSA form"
+        );
+    }
+}
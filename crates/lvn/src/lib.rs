@@ -1,106 +1,876 @@
 //! Contains the implementation of the Local Value Numbering algorithm.
 
-use bril::types::{Block, Operation};
-use eyre::eyre;
-use std::collections::hash_map::Entry;
-use std::collections::HashMap;
-
-pub fn local_value_numbering(mut block: Block) -> eyre::Result<Block> {
-    let mut var2num = HashMap::new();
-    let mut num2var = Vec::new();
-    let mut lvn = HashMap::new();
-    let mut num = 0usize;
-
-    for i in block.iter_mut() {
+use bril::cfg::Cfg;
+use bril::diagnostic::Diagnostic;
+use bril::driver::for_each_block_parallel;
+use bril::pass::for_each_block;
+use bril::types::{
+    Block, BrilProgram, Function, FunctionArg, Instruction, Literal, Operation, Type, Var,
+};
+use bril::value::{ExprId, ExprTable, ValueExpr, ValueNumber};
+use bril_macros::Pass;
+use std::collections::{HashMap, HashSet};
+use tracing::{debug, debug_span, Level};
+
+pub mod global;
+pub mod pipeline;
+pub mod report;
+pub mod strength;
+pub mod trace;
+
+pub use report::{LvnReport, Reuse};
+
+/// Evaluates the operation if all of its arguments are known constants, either
+/// integers or booleans. The arithmetic wraps around like the 64-bit integers
+/// of Bril, and the divisions by zero are left to fail at runtime.
+fn fold(op: &Operation, args: &[usize], constants: &HashMap<usize, Literal>) -> Option<Literal> {
+    let args = args
+        .iter()
+        .map(|a| constants.get(a))
+        .collect::<Option<Vec<_>>>()?;
+    let (a, b) = match (op, args.as_slice()) {
+        (Operation::Not, [Literal::Bool(a)]) => return Some(Literal::Bool(!a)),
+        (Operation::And, [Literal::Bool(a), Literal::Bool(b)]) => {
+            return Some(Literal::Bool(*a && *b))
+        }
+        (Operation::Or, [Literal::Bool(a), Literal::Bool(b)]) => {
+            return Some(Literal::Bool(*a || *b))
+        }
+        (_, [Literal::Int(a), Literal::Int(b)]) => (a, b),
+        _ => return None,
+    };
+
+    let result = match op {
+        Operation::Add => Literal::Int(a.wrapping_add(*b)),
+        Operation::Sub => Literal::Int(a.wrapping_sub(*b)),
+        Operation::Mul => Literal::Int(a.wrapping_mul(*b)),
+        Operation::Div if *b != 0 => Literal::Int(a.wrapping_div(*b)),
+        Operation::Eq => Literal::Bool(a == b),
+        Operation::Lt => Literal::Bool(a < b),
+        Operation::Gt => Literal::Bool(a > b),
+        Operation::Le => Literal::Bool(a <= b),
+        Operation::Ge => Literal::Bool(a >= b),
+        _ => return None,
+    };
+
+    Some(result)
+}
+
+/// The Local Value Numbering pass, run on each basic block
+#[derive(Pass)]
+#[pass(name = "lvn", block = local_value_numbering)]
+pub struct LocalValueNumbering;
+
+/// The values known at a point of the numbering. The blocks of an extended
+/// basic block start from a copy of the table of their parent, so that they
+/// inherit its values without leaking their own to their siblings.
+///
+/// The maps are only used for lookups and never iterated while rewriting, so
+/// that the output doesn't depend on their order and is the same on each run.
+#[derive(Debug, Clone, Default)]
+struct Table {
+    var2num: HashMap<Var, usize>,
+    num2var: Vec<Var>,
+    lvn: HashMap<ExprId, usize>,
+    constants: HashMap<usize, Literal>,
+    negations: HashMap<usize, usize>,
+    /// The version of the memory, bumped each time it may be written
+    memory: usize,
+    /// The value loaded from each pointer number, at a version of the memory
+    loads: HashMap<(usize, usize), usize>,
+    /// The type of the values, when known
+    types: HashMap<usize, Type>,
+    /// The flattened operands of the values computed by a chain of an
+    /// associative operation, when reassociating
+    chains: HashMap<usize, (Operation, Vec<usize>)>,
+}
+
+impl Table {
+    /// Forgets the values loaded so far, as the memory may have been written
+    fn forget_memory(&mut self) {
+        self.memory += 1;
+    }
+
+    /// Returns the variable holding the value number, none if the variable the
+    /// value was assigned to has been reassigned since
+    fn holder(&self, num: usize) -> Option<Var> {
+        let var = *self.num2var.get(num)?;
+        (self.var2num.get(&var) == Some(&num)).then_some(var)
+    }
+
+    /// Returns the variable a use of the value number is rewritten to, which
+    /// is the variable holding the value if any, the used variable otherwise
+    fn canonical(&self, num: usize, var: Var) -> Var {
+        self.holder(num).unwrap_or(var)
+    }
+
+    /// Returns the number of the value held by the variable. A variable used
+    /// before being defined, such as an argument or a value computed by a
+    /// predecessor, holds an unknown value, so it is given a new number
+    /// which is only equal to itself.
+    fn number(&mut self, var: Var) -> usize {
+        match self.var2num.get(&var) {
+            Some(num) => *num,
+            None => self.push(var, None),
+        }
+    }
+
+    /// Gives a new number to the value held by the variable
+    fn push(&mut self, var: Var, ty: Option<&Type>) -> usize {
+        let num = self.num2var.len();
+        self.var2num.insert(var, num);
+        self.num2var.push(var);
+        if let Some(ty) = ty {
+            self.types.insert(num, ty.clone());
+        }
+        num
+    }
+
+    /// Returns whether the value number can be used as a value of the type,
+    /// which is the case unless both types are known and differ. Equal
+    /// constants can have different types, such as `int` and `float`.
+    fn has_type(&self, num: usize, ty: Option<&Type>) -> bool {
+        match (self.types.get(&num), ty) {
+            (Some(t), Some(ty)) => t == ty,
+            _ => true,
+        }
+    }
+
+    /// Rewrites the instruction into a copy of the variable holding the value
+    /// number, or into the constant itself if it is known and materialized.
+    /// The instruction keeps the type of its destination.
+    fn copy(&self, i: &mut Instruction, num: usize, var: Var, constants: Constants) {
+        match self.constants.get(&num) {
+            Some(value) if constants == Constants::Materialize => {
+                i.op = Operation::Const;
+                i.value = Some(*value);
+                i.args_mut().clear();
+            }
+            _ => {
+                i.op = Operation::Id;
+                *i.args_mut() = vec![var];
+                i.value = None;
+            }
+        }
+    }
+}
+
+/// How the copies of the values known to be constants are rewritten
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Constants {
+    /// The copies refer to the variable holding the constant
+    #[default]
+    Canonical,
+    /// The copies are replaced by the constant itself, so that they don't
+    /// depend on the variable holding it anymore
+    Materialize,
+}
+
+/// The state shared by all the blocks numbered together
+#[derive(Default)]
+struct Shared {
+    exprs: ExprTable,
+    /// The variables of the numbered code, which the fresh variables avoid
+    names: HashSet<Var>,
+    fresh: usize,
+    /// The number of blocks numbered so far, which scopes the phis to the
+    /// block they are in
+    blocks: usize,
+    /// The numbers given to the labels of the phis
+    labels: HashMap<String, usize>,
+    options: LvnOptions,
+}
+
+impl Shared {
+    /// Records the variables used and defined by the block
+    fn extend(&mut self, block: &Block) {
+        for i in block {
+            self.names.extend(i.args().iter().copied());
+            self.names.extend(i.dest);
+        }
+    }
+
+    /// Returns a variable which isn't used by the numbered code
+    fn fresh_name(&mut self) -> Var {
+        loop {
+            let name = Var::from(format!("lvn.{}", self.fresh));
+            self.fresh += 1;
+            if self.names.insert(name) {
+                return name;
+            }
+        }
+    }
+}
+
+/// Applies Local Value Numbering on a block. The variables used before
+/// being defined in the block hold values computed elsewhere, which are
+/// only known to be equal to themselves.
+pub fn local_value_numbering(block: Block) -> eyre::Result<Block> {
+    local_value_numbering_with_args(block, &[])
+}
+
+/// Applies Local Value Numbering on a block which uses the provided
+/// function arguments. Each argument is given its own number before
+/// the block is processed.
+pub fn local_value_numbering_with_args(
+    mut block: Block,
+    args: &[FunctionArg],
+) -> eyre::Result<Block> {
+    number_arguments_block(&mut block, args, LvnOptions::default())?;
+    Ok(block)
+}
+
+/// The refinements applied by the numbering on top of the reuse of the
+/// expressions, all enabled by default. Disabling one makes it possible
+/// to compare its effect on the output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LvnOptions {
+    /// Replace the arithmetic, the comparisons and the boolean operations of
+    /// known constants by their result
+    pub fold: bool,
+    /// Replace the negation of a negation, and the conjunction or the
+    /// disjunction of a value with itself, by a copy of the original value
+    pub simplify: bool,
+    /// Number the commutative operations and the opposite comparisons the
+    /// same whatever the order of their arguments
+    pub canonicalize: bool,
+    /// How the copies of the constants are rewritten. The copies include the
+    /// uses of the `id` chains starting from a constant.
+    /// Example: (a = const 1, b = id a, c = id b -> b = const 1, c = const 1)
+    pub constants: Constants,
+    /// Move the values assigned to a variable reassigned later in the block
+    /// to fresh variables, so that they can still be reused. Otherwise the
+    /// value is computed again once its variable is reassigned.
+    pub rename: bool,
+    /// Reuse the values loaded from the same pointer until the memory may
+    /// be written, by a store, a call or a free
+    pub loads: bool,
+    /// Number the chains of an associative operation by their flattened
+    /// operands, so that `(a + b) + c` and `a + (b + c)` are the same value.
+    /// Disabled by default, as the reused value may have been grouped
+    /// differently than the source, and the expressions of the analysis are
+    /// then the whole chains.
+    pub reassociate: bool,
+    /// Once the block is numbered, replace each use of a constant by a new
+    /// `const` instruction right before the use, so that the constants don't
+    /// stay live across the block. Disabled by default, as it adds
+    /// instructions, see [`rematerialize_constants`].
+    pub rematerialize_consts: bool,
+}
+
+impl Default for LvnOptions {
+    fn default() -> Self {
+        Self {
+            fold: true,
+            simplify: true,
+            canonicalize: true,
+            constants: Constants::default(),
+            rename: true,
+            loads: true,
+            reassociate: false,
+            rematerialize_consts: false,
+        }
+    }
+}
+
+/// Applies Local Value Numbering on a block with the given refinements
+pub fn local_value_numbering_with(mut block: Block, options: &LvnOptions) -> eyre::Result<Block> {
+    number_arguments_block(&mut block, &[], *options)?;
+    Ok(block)
+}
+
+/// The values discovered by the numbering of a block, as they are at the
+/// end of the block. Other passes can use it to find the equivalent
+/// variables without numbering the block again.
+#[derive(Debug)]
+pub struct LvnAnalysis {
+    table: Table,
+    exprs: ExprTable,
+    num2expr: HashMap<ValueNumber, ExprId>,
+}
+
+impl LvnAnalysis {
+    fn new(table: Table, exprs: ExprTable) -> Self {
+        let num2expr = table.lvn.iter().map(|(e, n)| (*n, *e)).collect();
+        Self {
+            table,
+            exprs,
+            num2expr,
+        }
+    }
+
+    /// Returns the value number of the variable, none if the block neither
+    /// uses nor defines it
+    pub fn value_number(&self, var: Var) -> Option<ValueNumber> {
+        self.table.var2num.get(&var).copied()
+    }
+
+    /// Returns the variable holding the value number, none if the variable the
+    /// value was assigned to has been reassigned since
+    pub fn holder(&self, num: ValueNumber) -> Option<Var> {
+        self.table.holder(num)
+    }
+
+    /// Returns the expression computing the value number, none for the values
+    /// which aren't computed by a pure operation of the block
+    pub fn expr(&self, num: ValueNumber) -> Option<&ValueExpr> {
+        self.num2expr.get(&num).map(|id| self.exprs.expr(*id))
+    }
+
+    /// Returns the constant of the value number, if it is known
+    pub fn constant(&self, num: ValueNumber) -> Option<Literal> {
+        self.table.constants.get(&num).copied()
+    }
+
+    /// Returns whether both variables hold the same value
+    pub fn equivalent(&self, a: Var, b: Var) -> bool {
+        self.value_number(a)
+            .is_some_and(|n| self.value_number(b) == Some(n))
+    }
+}
+
+/// Applies Local Value Numbering on a block with the given refinements, also
+/// returning the report of the rewrites
+pub fn local_value_numbering_with_report(
+    block: Block,
+    options: &LvnOptions,
+) -> eyre::Result<(Block, LvnReport)> {
+    // The report compares the instructions in place, before the constants
+    // are rematerialized
+    let numbering = LvnOptions {
+        rematerialize_consts: false,
+        ..*options
+    };
+    let mut rewritten = block.clone();
+    number_arguments_block(&mut rewritten, &[], numbering)?;
+    let report = LvnReport::new(&block, &rewritten);
+    if options.rematerialize_consts {
+        rematerialize_constants(&mut rewritten);
+    }
+    Ok((rewritten, report))
+}
+
+/// Applies Local Value Numbering on a block which uses the provided function
+/// arguments, also returning the values discovered by the numbering
+pub fn local_value_numbering_with_analysis(
+    mut block: Block,
+    args: &[FunctionArg],
+) -> eyre::Result<(Block, LvnAnalysis)> {
+    let (table, exprs) = number_arguments_block(&mut block, args, LvnOptions::default())?;
+    Ok((block, LvnAnalysis::new(table, exprs)))
+}
+
+/// Applies Local Value Numbering on a block, also returning the indices of
+/// the instructions turned into copies whose destination isn't used again in
+/// the block, in increasing order. The uses of a reused value are rewritten
+/// to the variable holding it, which often leaves its copies unused.
+///
+/// Like the Dead Code Elimination, the hints only look at the block, so a
+/// copy only used by a later block is flagged too.
+pub fn local_value_numbering_with_hints(mut block: Block) -> eyre::Result<(Block, Vec<usize>)> {
+    let copies = block
+        .iter()
+        .map(|i| i.op != Operation::Id)
+        .collect::<Vec<_>>();
+    number_arguments_block(&mut block, &[], LvnOptions::default())?;
+
+    // Walk the block backwards, tracking the variables read before being
+    // reassigned, which the dead copies don't read
+    let mut live = HashSet::new();
+    let mut dead = Vec::new();
+    for (index, i) in block.iter().enumerate().rev() {
+        let copy = copies[index] && i.op == Operation::Id;
+        if copy && i.dest.is_some_and(|dest| !live.contains(&dest)) {
+            dead.push(index);
+            continue;
+        }
+        if let Some(dest) = i.dest {
+            live.remove(&dest);
+        }
+        live.extend(i.args().iter().copied());
+    }
+    dead.reverse();
+
+    Ok((block, dead))
+}
+
+/// Applies Local Value Numbering on a block, then removes the copies it
+/// leaves unused, see [`local_value_numbering_with_hints`]
+pub fn lvn_dce(block: Block) -> eyre::Result<Block> {
+    let (mut block, dead) = local_value_numbering_with_hints(block)?;
+    dce::remove_hinted(&mut block, &dead);
+    Ok(block)
+}
+
+/// Applies Local Value Numbering on the block in place, for the pipelines
+/// which keep the blocks they run several passes on
+pub fn run_in_place(block: &mut Block) -> eyre::Result<()> {
+    number_arguments_block(block, &[], LvnOptions::default())?;
+    Ok(())
+}
+
+/// Applies Local Value Numbering in place on each basic block of the
+/// function, which uses the arguments of the function
+pub fn run_function_in_place(function: &mut Function) -> eyre::Result<()> {
+    let args = std::mem::take(&mut function.args);
+    let result = for_each_block(function, |block| {
+        number_arguments_block(block, &args, LvnOptions::default()).map(|_| ())
+    });
+    function.args = args;
+    result.map_err(|error| match error.downcast::<Diagnostic>() {
+        Ok(diagnostic) => eyre::Report::new(diagnostic.in_function(&function.name)),
+        Err(error) => error,
+    })
+}
+
+/// Applies Local Value Numbering on each basic block of the program, the
+/// blocks being numbered in parallel as they are independent. Each block
+/// uses the arguments of its function.
+pub fn run_program(program: &mut BrilProgram) -> eyre::Result<()> {
+    for_each_block_parallel(program, |args, block| {
+        number_arguments_block(block, args, LvnOptions::default()).map(|_| ())
+    })
+}
+
+/// Numbers the block, each argument being given its own number first. The
+/// values are returned for the analysis, which is only built on demand.
+fn number_arguments_block(
+    block: &mut Block,
+    args: &[FunctionArg],
+    options: LvnOptions,
+) -> eyre::Result<(Table, ExprTable)> {
+    let mut table = Table::default();
+    let mut shared = Shared {
+        options,
+        ..Default::default()
+    };
+    for arg in args {
+        table.push(arg.name, Some(&arg.r#type));
+        shared.names.insert(arg.name);
+    }
+    shared.extend(block);
+
+    number_block(block, &mut table, &mut shared)?;
+    if options.rematerialize_consts {
+        rematerialize_constants(block);
+    }
+    Ok((table, shared.exprs))
+}
+
+/// Replaces each use of a variable holding a constant of the block by a new
+/// variable, assigned the constant right before the use. A copy of the
+/// constant becomes the constant itself, and the phis are kept as they read
+/// their arguments at the end of the predecessors.
+/// Example: (a = const 4, b = add a a -> a = const 4, lvn.0 = const 4, b = add lvn.0 lvn.0)
+///
+/// The original constants are left for the Dead Code Elimination to remove
+/// once unused.
+pub fn rematerialize_constants(block: &mut Block) {
+    let mut shared = Shared::default();
+    shared.extend(block);
+
+    let mut constants = HashMap::<Var, Instruction>::new();
+    let mut rewritten = Block::with_capacity(block.len());
+    for mut i in std::mem::take(block) {
+        match i.op {
+            Operation::Const | Operation::Phi => {}
+            Operation::Id => {
+                let constant = i.args().first().and_then(|a| constants.get(a));
+                if let Some(value) = constant.and_then(|c| c.value) {
+                    i.op = Operation::Const;
+                    i.value = Some(value);
+                    i.args_mut().clear();
+                }
+            }
+            _ => {
+                // Each use gets its own constant, even within an instruction
+                for a in i.args_mut() {
+                    let Some(constant) = constants.get(a) else {
+                        continue;
+                    };
+                    let fresh = shared.fresh_name();
+                    rewritten.push(Instruction {
+                        dest: Some(fresh),
+                        ..constant.clone()
+                    });
+                    *a = fresh;
+                }
+            }
+        }
+
+        if let Some(dest) = i.dest {
+            match i.op {
+                Operation::Const => constants.insert(dest, i.clone()),
+                _ => constants.remove(&dest),
+            };
+        }
+        rewritten.push(i);
+    }
+
+    *block = rewritten;
+}
+
+/// Applies the numbering on the extended basic blocks of the graph, which
+/// are the trees of blocks with a single predecessor. Each block starts with
+/// the values of its predecessor, removing the redundancies along the paths
+/// of the tree. The roots, which are the entry, the blocks which can be
+/// reached from several blocks and the blocks a guard aborts to, start from
+/// scratch. An abort leaves its block before the end, restoring the values
+/// from before the speculation, so the values of the block don't hold there.
+///
+/// Variables used before being defined in the tree, such as the function
+/// arguments, are values computed elsewhere.
+pub fn ebb_value_numbering(cfg: &mut Cfg) -> eyre::Result<()> {
+    let mut shared = Shared::default();
+    for block in &cfg.blocks {
+        shared.extend(&block.instrs);
+    }
+
+    let aborted = guard_targets(cfg);
+    let inherits = (0..cfg.blocks.len())
+        .map(|block| block != 0 && cfg.predecessors(block).len() == 1 && !aborted.contains(&block))
+        .collect::<Vec<_>>();
+    let mut pending = (0..cfg.blocks.len())
+        .filter(|block| !inherits[*block])
+        .map(|block| (block, Table::default()))
+        .collect::<Vec<_>>();
+    while let Some((block, mut table)) = pending.pop() {
+        number_block(&mut cfg.blocks[block].instrs, &mut table, &mut shared)
+            .map_err(|diagnostic| eyre::Report::new(diagnostic.in_block(block)))?;
+        for &successor in cfg.successors(block) {
+            if inherits[successor] {
+                pending.push((successor, table.clone()));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the blocks of the graph which a guard can abort to
+fn guard_targets(cfg: &Cfg) -> HashSet<usize> {
+    cfg.blocks
+        .iter()
+        .flat_map(|block| &block.instrs)
+        .filter(|i| i.op == Operation::Guard)
+        .flat_map(|i| i.labels.iter().flatten())
+        .filter_map(|label| cfg.block_of(label))
+        .collect()
+}
+
+/// Numbers the instructions of the block, starting from the values of the table
+fn number_block(
+    block: &mut Block,
+    table: &mut Table,
+    shared: &mut Shared,
+) -> Result<(), Diagnostic> {
+    // A value assigned to a variable which is reassigned later in the block
+    // is moved to a fresh variable, so that the later uses of the value
+    // aren't rewritten to the variable once it holds something else
+    let mut last_defs = HashMap::new();
+    for (index, i) in block.iter().enumerate() {
+        if let Some(dest) = i.dest {
+            last_defs.insert(dest, index);
+        }
+    }
+    let options = shared.options;
+    let scope = shared.blocks;
+    shared.blocks += 1;
+    let _span = debug_span!("lvn_block", block = scope).entered();
+    let original = tracing::enabled!(Level::DEBUG).then(|| block.clone());
+    let home_of = |index: usize, dest: Var, shared: &mut Shared| {
+        if !options.rename || last_defs.get(&dest) == Some(&index) {
+            return dest;
+        }
+        shared.fresh_name()
+    };
+
+    for (index, i) in block.iter_mut().enumerate() {
+        let error = |message: String| Diagnostic::new(message).at_instruction(index);
+
         // Handle the id instruction in a special case
         if i.op == Operation::Id {
             // Take the argument of the operation, fetch the number
             // and point the destination to this number. Then, update
             // the args by taking the var corresponding to this number.
             // Example: (copy: int = id x -> var2num[copy] = var2num[x] and args = x)
-            let a = i.args.first().ok_or(eyre!("missing argument for Id"))?;
-            let num = var2num
-                .get(a)
-                .copied()
-                .ok_or(eyre!("missing {a} in var2num"))?;
-            var2num.insert(
-                i.dest.clone().ok_or(eyre!("missing destination for Id"))?,
+            let a = *i
+                .args()
+                .first()
+                .ok_or_else(|| error("missing argument for Id".to_string()))?;
+            let num = table.number(a);
+            let arg = table.canonical(num, a);
+            table.var2num.insert(
+                i.dest
+                    .ok_or_else(|| error("missing destination for Id".to_string()))?,
                 num,
             );
-            i.args = vec![num2var
-                .get(num)
-                .cloned()
-                .ok_or(eyre!("missing {num} in num2var"))?];
+            table.copy(i, num, arg, options.constants);
+            continue;
+        }
+
+        // The phis of the block picking the same values from the same predecessors
+        // are the same value, whatever the order of their arguments. They are
+        // numbered by their pairs of label and argument, in the order of the labels.
+        // Example: (p = phi a b .l .r, q = phi b a .r .l -> q = id p)
+        let labels = i.labels.as_deref().unwrap_or_default();
+        if let (Operation::Phi, Some(dest), true) = (&i.op, i.dest, labels.len() == i.args().len())
+        {
+            let mut edges = labels
+                .iter()
+                .zip(i.args())
+                .map(|(label, a)| {
+                    let count = shared.labels.len();
+                    let label = *shared.labels.entry(label.clone()).or_insert(count);
+                    (label, table.number(*a))
+                })
+                .collect::<Vec<_>>();
+            edges.sort();
+            let key = std::iter::once(scope)
+                .chain(edges.into_iter().flat_map(|(label, n)| [label, n]))
+                .collect();
+            let expression = shared
+                .exprs
+                .intern(ValueExpr::as_written(Operation::Phi, key, None));
+            debug!(index, %dest, expression = ?shared.exprs.expr(expression), "expression computed");
+
+            let known = table
+                .lvn
+                .get(&expression)
+                .copied()
+                .filter(|n| table.has_type(*n, i.r#type.as_ref()));
+            if let Some((n, var)) = known.and_then(|n| Some((n, table.holder(n)?))) {
+                debug!(index, value = n, holder = %var, "table hit");
+                table.var2num.insert(dest, n);
+                table.copy(i, n, var, options.constants);
+                i.labels = None;
+                continue;
+            }
+
+            let home = home_of(index, dest, shared);
+            i.dest = Some(home);
+            let n = table.push(home, i.r#type.as_ref());
+            debug!(index, value = n, holder = %home, "table miss");
+            table.lvn.insert(expression, n);
+            table.var2num.insert(dest, n);
             continue;
         }
 
-        // We convert the arguments and the value if any into their number in the var2num mapping.
-        // This converts the expression to something like (add, 1, 2) or (const 42).
-        let value_arr = i.value.iter().map(|x| *x as usize).collect::<Vec<_>>();
-        let args_num = i
-            .args
+        // Only the pure operations producing a value can be reused. The other ones,
+        // such as prints, calls or memory accesses, can have side effects or depend on
+        // state outside of the block, so they are never added to the lvn mapping. We
+        // only update the args and assign a new number to the destination if any.
+        // This covers the instructions without destination, such as the condition
+        // of a branch or the returned value.
+        let Some(dest) = i.dest.filter(|_| i.is_pure()) else {
+            let args_num = i
+                .args()
+                .iter()
+                .map(|a| table.number(*a))
+                .collect::<Vec<_>>();
+            let args = i
+                .args()
+                .iter()
+                .zip(&args_num)
+                .map(|(a, n)| table.canonical(*n, *a))
+                .collect();
+            *i.args_mut() = args;
+
+            // The loads from the same pointer number read the same value as long
+            // as the memory isn't written, which bumps its version.
+            // Example: (a = load p, b = load p -> b = id a)
+            let load = match (&i.op, args_num.as_slice()) {
+                (Operation::Load, [pointer]) if options.loads => Some((*pointer, table.memory)),
+                _ => None,
+            };
+            let loaded = load
+                .and_then(|key| table.loads.get(&key))
+                .copied()
+                .filter(|n| table.has_type(*n, i.r#type.as_ref()));
+            match (i.dest, loaded.and_then(|n| Some((n, table.holder(n)?)))) {
+                (Some(dest), Some((n, var))) => {
+                    table.var2num.insert(dest, n);
+                    table.copy(i, n, var, options.constants);
+                }
+                (Some(dest), None) => {
+                    let home = home_of(index, dest, shared);
+                    i.dest = Some(home);
+                    let n = table.push(home, i.r#type.as_ref());
+                    table.var2num.insert(dest, n);
+                    if let Some(key) = load {
+                        table.loads.insert(key, n);
+                    }
+                }
+                (None, _) => {}
+            }
+            if matches!(
+                i.op,
+                Operation::Store | Operation::Call | Operation::Free | Operation::Other(_)
+            ) {
+                table.forget_memory();
+            }
+            continue;
+        };
+
+        // We convert the arguments into their number in the var2num mapping and keep the value if any.
+        // This converts the expression to something like (add, [1, 2]) or (const, [], 42).
+        let mut args_num = i
+            .args()
             .iter()
-            .map(|a| {
-                var2num
-                    .get(a)
-                    .copied()
-                    .ok_or(eyre!("missing {a} in var2num"))
-            })
-            .collect::<eyre::Result<Vec<_>>>()?;
-        let mut args = [args_num.clone(), value_arr].concat();
-        args.sort();
-        let expression = (i.op.clone(), args);
-
-        let dest = i.dest.clone().unwrap_or_default();
-        let entry = lvn.entry(expression);
-
-        match entry {
-            // If vacant, update the var2num and num2var, increase num
-            // and insert the new expression in the mapping.
-            // Also retrieve the new arguments from the var2num
-            // mapping
-            Entry::Vacant(v) => {
-                var2num.insert(dest.clone(), num);
-                num2var.push(dest.clone());
-                v.insert((dest, num));
-                i.args = args_num
-                    .into_iter()
-                    .map(|arg| {
-                        num2var
-                            .get(arg)
-                            .cloned()
-                            .ok_or(eyre!("missing {arg} in num2var"))
-                    })
-                    .collect::<eyre::Result<Vec<_>>>()?;
-                num += 1;
-            }
-            // If occupied, retrieve the expression number from
-            // the lvn mapping and point the destination of the
-            // opcode towards this number. Also update the instruction
-            // to use [`bril::types::Operation::Id`]
-            Entry::Occupied(e) => {
-                let (var, n) = e.get();
-                var2num.insert(dest, *n);
-                i.op = Operation::Id;
-                i.args = vec![var.clone()];
+            .map(|a| table.number(*a))
+            .collect::<Vec<_>>();
+
+        // The negation of a negation is replaced by a copy of the original value.
+        // Example: (b = not a, c = not b -> c = id a)
+        // The original value must still be held by a variable.
+        let negated = args_num
+            .first()
+            .and_then(|a| table.negations.get(a))
+            .copied();
+        let original = negated
+            .filter(|_| options.simplify)
+            .and_then(|n| Some((n, table.holder(n)?)));
+        if let (Operation::Not, Some((n, var))) = (&i.op, original) {
+            table.var2num.insert(dest, n);
+            table.copy(i, n, var, options.constants);
+            continue;
+        }
+
+        // The conjunction or the disjunction of a value with itself is the value.
+        // Example: (b = and a a -> b = id a)
+        let idempotent = match (&i.op, args_num.as_slice()) {
+            (Operation::And | Operation::Or, [a, b]) if a == b && options.simplify => {
+                table.holder(*a).map(|var| (*a, var))
+            }
+            _ => None,
+        };
+        if let Some((n, var)) = idempotent {
+            table.var2num.insert(dest, n);
+            table.copy(i, n, var, options.constants);
+            continue;
+        }
+
+        // Operations on known constants are replaced by their result
+        let folded = fold(&i.op, &args_num, &table.constants);
+        if let Some(value) = folded.filter(|_| options.fold) {
+            i.op = Operation::Const;
+            i.value = Some(value);
+            args_num.clear();
+        }
+
+        // The operands of an associative operation which are chains of the same
+        // operation are replaced by their own operands
+        // Example: (d = add a b, e = add d c -> e is (add, [a, b, c]))
+        let chain = (options.reassociate && i.op.is_associative()).then(|| {
+            let mut operands = args_num
+                .iter()
+                .flat_map(|n| match table.chains.get(n) {
+                    Some((op, operands)) if *op == i.op => operands.clone(),
+                    _ => vec![*n],
+                })
+                .collect::<Vec<_>>();
+            operands.sort();
+            operands
+        });
+
+        // Only commutative operations have their arguments reordered in the expression
+        let expression = match (&chain, options.canonicalize) {
+            (Some(operands), _) => ValueExpr::new(i.op.clone(), operands.clone(), None),
+            (None, true) => ValueExpr::new(i.op.clone(), args_num.clone(), i.value),
+            (None, false) => ValueExpr::as_written(i.op.clone(), args_num.clone(), i.value),
+        };
+        let expression = shared.exprs.intern(expression);
+        debug!(index, %dest, expression = ?shared.exprs.expr(expression), "expression computed");
+
+        // If the expression is held by a variable, retrieve its number and point
+        // the destination of the opcode towards this number. Also turn the
+        // instruction into a copy of the value, see [`Table::copy`]
+        let known = table
+            .lvn
+            .get(&expression)
+            .copied()
+            .filter(|n| table.has_type(*n, i.r#type.as_ref()));
+        if let Some((n, var)) = known.and_then(|n| Some((n, table.holder(n)?))) {
+            debug!(index, value = n, holder = %var, "table hit");
+            table.var2num.insert(dest, n);
+            table.copy(i, n, var, options.constants);
+            continue;
+        }
+
+        // Otherwise the instruction is kept, with the arguments retrieved from the
+        // var2num mapping. Its destination holds the value, which is either new or
+        // was computed by a parent block into a variable reassigned since.
+        let args = i
+            .args()
+            .iter()
+            .zip(&args_num)
+            .map(|(a, n)| table.canonical(*n, *a))
+            .collect();
+        *i.args_mut() = args;
+        let home = home_of(index, dest, shared);
+        i.dest = Some(home);
+        let n = match known {
+            Some(n) => {
+                debug!(index, value = n, holder = %home, "table miss, value no longer held");
+                table.num2var[n] = home;
+                n
+            }
+            None => {
+                let n = table.push(home, i.r#type.as_ref());
+                debug!(index, value = n, holder = %home, "table miss");
+                if let (Operation::Const, Some(value)) = (&i.op, i.value) {
+                    table.constants.insert(n, value);
+                }
+                if let (Operation::Not, Some(a)) = (&i.op, args_num.first()) {
+                    table.negations.insert(n, *a);
+                }
+                if let Some(operands) = chain {
+                    table.chains.insert(n, (i.op.clone(), operands));
+                }
+                table.lvn.insert(expression, n);
+                n
             }
         };
+        table.var2num.insert(dest, n);
+        table.var2num.insert(home, n);
     }
 
-    Ok(block)
+    for (index, (before, after)) in original.iter().flatten().zip(block.iter()).enumerate() {
+        if before != after {
+            debug!(index, %before, %after, "rewrite performed");
+        }
+    }
+
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use super::local_value_numbering;
+    use super::{
+        ebb_value_numbering, local_value_numbering, local_value_numbering_with,
+        local_value_numbering_with_analysis, local_value_numbering_with_args,
+        local_value_numbering_with_hints, local_value_numbering_with_report, lvn_dce,
+        run_function_in_place, run_in_place, run_program, Constants, LvnOptions, Reuse,
+    };
+    use bril::cfg::Cfg;
+    use bril::diagnostic::Diagnostic;
+    use bril::generate::{generate, Config};
+    use bril::pass::PassRegistry;
+    use bril::text::parse_program;
+    use bril::types::{
+        BrilProgram, Code, FunctionArg, Instruction, Literal, Operation, Position, Type,
+    };
+    use bril::verify::verify_program;
     use bril_macros::instruction;
 
     #[test]
     fn test_local_value_numbering() {
         // Given
+        let args = ["a", "b"].map(|name| FunctionArg {
+            name: name.into(),
+            r#type: Type::Int,
+        });
         let block = vec![
-            instruction!(op = const, value = 1, dest = a),
-            instruction!(op = const, value = 2, dest = b),
             instruction!(op = add, args = [a, b], dest = sum1),
             instruction!(op = add, args = [a, b], dest = sum2),
             instruction!(op = mul, args = [sum1, sum2], dest = prod),
@@ -108,12 +878,11 @@ mod tests {
         ];
 
         // When
-        let optimized_block = local_value_numbering(block).expect("failed to apply lvn");
+        let optimized_block =
+            local_value_numbering_with_args(block, &args).expect("failed to apply lvn");
 
         // Then
         let expected_block = vec![
-            instruction!(op = const, value = 1, dest = a),
-            instruction!(op = const, value = 2, dest = b),
             instruction!(op = add, args = [a, b], dest = sum1),
             instruction!(op = id, args = [sum1], dest = sum2),
             instruction!(op = mul, args = [sum1, sum1], dest = prod),
@@ -126,9 +895,11 @@ mod tests {
     #[test]
     fn test_local_value_numbering_commutativity() {
         // Given
+        let args = ["a", "b"].map(|name| FunctionArg {
+            name: name.into(),
+            r#type: Type::Int,
+        });
         let block = vec![
-            instruction!(op = const, value = 1, dest = a),
-            instruction!(op = const, value = 2, dest = b),
             instruction!(op = add, args = [a, b], dest = sum1),
             instruction!(op = add, args = [b, a], dest = sum2),
             instruction!(op = mul, args = [sum1, sum2], dest = prod),
@@ -136,12 +907,11 @@ mod tests {
         ];
 
         // When
-        let optimized_block = local_value_numbering(block).expect("failed to apply lvn");
+        let optimized_block =
+            local_value_numbering_with_args(block, &args).expect("failed to apply lvn");
 
         // Then
         let expected_block = vec![
-            instruction!(op = const, value = 1, dest = a),
-            instruction!(op = const, value = 2, dest = b),
             instruction!(op = add, args = [a, b], dest = sum1),
             instruction!(op = id, args = [sum1], dest = sum2),
             instruction!(op = mul, args = [sum1, sum1], dest = prod),
@@ -151,6 +921,35 @@ mod tests {
         assert_eq!(optimized_block, expected_block);
     }
 
+    #[test]
+    fn test_local_value_numbering_opposite_comparisons() {
+        // Given
+        let args = ["x", "y"].map(|name| FunctionArg {
+            name: name.into(),
+            r#type: Type::Int,
+        });
+        let block = vec![
+            instruction!(op = gt, args = [x, y], dest = a),
+            instruction!(op = lt, args = [y, x], dest = b),
+            instruction!(op = lt, args = [x, y], dest = c),
+            instruction!(op = and, args = [b, c], dest = d),
+        ];
+
+        // When
+        let optimized_block =
+            local_value_numbering_with_args(block, &args).expect("failed to apply lvn");
+
+        // Then
+        let expected_block = vec![
+            instruction!(op = gt, args = [x, y], dest = a),
+            instruction!(op = id, args = [a], dest = b),
+            instruction!(op = lt, args = [x, y], dest = c),
+            instruction!(op = and, args = [a, c], dest = d),
+        ];
+
+        assert_eq!(optimized_block, expected_block);
+    }
+
     #[test]
     fn test_local_value_numbering_constant_propagation() {
         // Given
@@ -176,4 +975,1423 @@ mod tests {
 
         assert_eq!(optimized_block, expected_block);
     }
+
+    #[test]
+    fn test_local_value_numbering_with_args() {
+        // Given
+        let args = vec![
+            FunctionArg {
+                name: "a".into(),
+                r#type: Type::Int,
+            },
+            FunctionArg {
+                name: "b".into(),
+                r#type: Type::Int,
+            },
+        ];
+        let block = vec![
+            instruction!(op = add, args = [a, b], dest = sum1),
+            instruction!(op = add, args = [b, a], dest = sum2),
+            instruction!(op = print, args = [sum2]),
+        ];
+
+        // When
+        let optimized_block =
+            local_value_numbering_with_args(block, &args).expect("failed to apply lvn");
+
+        // Then
+        let expected_block = vec![
+            instruction!(op = add, args = [a, b], dest = sum1),
+            instruction!(op = id, args = [sum1], dest = sum2),
+            instruction!(op = print, args = [sum1]),
+        ];
+
+        assert_eq!(optimized_block, expected_block);
+    }
+
+    #[test]
+    fn test_local_value_numbering_calls() {
+        // Given
+        let call = |dest: &str| Instruction {
+            op: Operation::Call,
+            args: vec!["a".into()],
+            dest: Some(dest.into()),
+            funcs: Some(vec!["f".to_string()]),
+            ..Default::default()
+        };
+        let block = vec![
+            instruction!(op = const, value = 1, dest = a),
+            call("r1"),
+            call("r2"),
+            instruction!(op = add, args = [r1, r2], dest = sum),
+            instruction!(op = print, args = [sum]),
+        ];
+
+        // When
+        let optimized_block = local_value_numbering(block.clone()).expect("failed to apply lvn");
+
+        // Then
+        assert_eq!(optimized_block, block);
+    }
+
+    #[test]
+    fn test_local_value_numbering_literal_kinds() {
+        // Given
+        let mut condition = instruction!(op = const, value = 1, dest = c);
+        condition.value = Some(Literal::Bool(true));
+        let block = vec![
+            instruction!(op = const, value = 1, dest = a),
+            condition,
+            instruction!(op = print, args = [a]),
+            instruction!(op = print, args = [c]),
+        ];
+
+        // When
+        let optimized_block = local_value_numbering(block.clone()).expect("failed to apply lvn");
+
+        // Then
+        assert_eq!(optimized_block, block);
+    }
+
+    #[test]
+    fn test_local_value_numbering_negative_values() {
+        // Given
+        let constant = |dest: &str, value: i64| Instruction {
+            op: Operation::Const,
+            value: Some(Literal::Int(value)),
+            dest: Some(dest.into()),
+            ..Default::default()
+        };
+        let block = vec![
+            constant("a", -1),
+            constant("b", 1),
+            constant("c", -1),
+            instruction!(op = add, args = [a, b], dest = sum1),
+            instruction!(op = add, args = [c, b], dest = sum2),
+            instruction!(op = mul, args = [sum1, sum2], dest = prod),
+            instruction!(op = print, args = [prod]),
+        ];
+
+        // When
+        let optimized_block = local_value_numbering(block).expect("failed to apply lvn");
+
+        // Then
+        let expected_block = vec![
+            constant("a", -1),
+            constant("b", 1),
+            instruction!(op = id, args = [a], dest = c),
+            constant("sum1", 0),
+            instruction!(op = id, args = [sum1], dest = sum2),
+            instruction!(op = id, args = [sum1], dest = prod),
+            instruction!(op = print, args = [sum1]),
+        ];
+
+        assert_eq!(optimized_block, expected_block);
+    }
+
+    #[test]
+    fn test_local_value_numbering_non_commutative() {
+        // Given
+        let args = ["a", "b"].map(|name| FunctionArg {
+            name: name.into(),
+            r#type: Type::Int,
+        });
+        let block = vec![
+            instruction!(op = sub, args = [a, b], dest = diff1),
+            instruction!(op = sub, args = [b, a], dest = diff2),
+            instruction!(op = div, args = [a, b], dest = quot1),
+            instruction!(op = div, args = [a, b], dest = quot2),
+            instruction!(op = add, args = [diff1, diff2], dest = sum),
+            instruction!(op = add, args = [quot1, quot2], dest = sum2),
+        ];
+
+        // When
+        let optimized_block =
+            local_value_numbering_with_args(block, &args).expect("failed to apply lvn");
+
+        // Then
+        let expected_block = vec![
+            instruction!(op = sub, args = [a, b], dest = diff1),
+            instruction!(op = sub, args = [b, a], dest = diff2),
+            instruction!(op = div, args = [a, b], dest = quot1),
+            instruction!(op = id, args = [quot1], dest = quot2),
+            instruction!(op = add, args = [diff1, diff2], dest = sum),
+            instruction!(op = add, args = [quot1, quot1], dest = sum2),
+        ];
+
+        assert_eq!(optimized_block, expected_block);
+    }
+
+    #[test]
+    fn test_local_value_numbering_fold_comparisons() {
+        // Given
+        let block = vec![
+            instruction!(op = const, value = 1, dest = a),
+            instruction!(op = const, value = 2, dest = b),
+            instruction!(op = id, args = [b], dest = c),
+            instruction!(op = lt, args = [a, c], dest = lower),
+            instruction!(op = eq, args = [a, b], dest = equal),
+            instruction!(op = ge, args = [b, a], dest = greater),
+            instruction!(op = print, args = [equal]),
+            instruction!(op = print, args = [greater]),
+        ];
+
+        // When
+        let optimized_block = local_value_numbering(block).expect("failed to apply lvn");
+
+        // Then
+        let boolean = |dest: &str, value: bool| Instruction {
+            op: Operation::Const,
+            value: Some(Literal::Bool(value)),
+            dest: Some(dest.into()),
+            ..Default::default()
+        };
+        let expected_block = vec![
+            instruction!(op = const, value = 1, dest = a),
+            instruction!(op = const, value = 2, dest = b),
+            instruction!(op = id, args = [b], dest = c),
+            boolean("lower", true),
+            boolean("equal", false),
+            instruction!(op = id, args = [lower], dest = greater),
+            instruction!(op = print, args = [equal]),
+            instruction!(op = print, args = [lower]),
+        ];
+
+        assert_eq!(optimized_block, expected_block);
+    }
+
+    #[test]
+    fn test_local_value_numbering_fold_wrapping_arithmetic() {
+        // Given
+        let block = vec![
+            instruction!(op = const, value = i64::MAX, dest = max),
+            instruction!(op = const, value = i64::MIN, dest = min),
+            instruction!(op = const, value = 1, dest = one),
+            instruction!(op = const, value = -1, dest = minus_one),
+            instruction!(op = const, value = 0, dest = zero),
+            instruction!(op = const, value = -7, dest = minus_seven),
+            instruction!(op = const, value = 2, dest = two),
+            instruction!(op = add, args = [max, one], dest = a),
+            instruction!(op = sub, args = [min, one], dest = b),
+            instruction!(op = mul, args = [max, two], dest = c),
+            instruction!(op = div, args = [min, minus_one], dest = d),
+            instruction!(op = div, args = [minus_seven, two], dest = e),
+            instruction!(op = div, args = [one, zero], dest = f),
+        ];
+
+        // When
+        let optimized_block = local_value_numbering(block).expect("failed to apply lvn");
+
+        // Then
+        let expected_block = vec![
+            instruction!(op = const, value = i64::MAX, dest = max),
+            instruction!(op = const, value = i64::MIN, dest = min),
+            instruction!(op = const, value = 1, dest = one),
+            instruction!(op = const, value = -1, dest = minus_one),
+            instruction!(op = const, value = 0, dest = zero),
+            instruction!(op = const, value = -7, dest = minus_seven),
+            instruction!(op = const, value = 2, dest = two),
+            instruction!(op = id, args = [min], dest = a),
+            instruction!(op = id, args = [max], dest = b),
+            instruction!(op = const, value = -2, dest = c),
+            instruction!(op = id, args = [min], dest = d),
+            instruction!(op = const, value = -3, dest = e),
+            instruction!(op = div, args = [one, zero], dest = f),
+        ];
+
+        assert_eq!(optimized_block, expected_block);
+    }
+
+    #[test]
+    fn test_local_value_numbering_boolean_logic() {
+        // Given
+        let block = vec![
+            instruction!(op = lt, args = [x, y], dest = a),
+            instruction!(op = gt, args = [x, y], dest = b),
+            instruction!(op = and, args = [a, b], dest = and1),
+            instruction!(op = and, args = [b, a], dest = and2),
+            instruction!(op = not, args = [and1], dest = not1),
+            instruction!(op = not, args = [not1], dest = not2),
+            instruction!(op = or, args = [not2, and2], dest = or),
+            instruction!(op = print, args = [or]),
+        ];
+        let args = ["x", "y"].map(|name| FunctionArg {
+            name: name.into(),
+            r#type: Type::Int,
+        });
+
+        // When
+        let optimized_block =
+            local_value_numbering_with_args(block, &args).expect("failed to apply lvn");
+
+        // Then
+        let expected_block = vec![
+            instruction!(op = lt, args = [x, y], dest = a),
+            instruction!(op = gt, args = [x, y], dest = b),
+            instruction!(op = and, args = [a, b], dest = and1),
+            instruction!(op = id, args = [and1], dest = and2),
+            instruction!(op = not, args = [and1], dest = not1),
+            instruction!(op = id, args = [and1], dest = not2),
+            instruction!(op = id, args = [and1], dest = or),
+            instruction!(op = print, args = [and1]),
+        ];
+
+        assert_eq!(optimized_block, expected_block);
+    }
+
+    #[test]
+    fn test_local_value_numbering_memory() {
+        // Given
+        let block = vec![
+            instruction!(op = const, value = 1, dest = size),
+            instruction!(op = alloc, args = [size], dest = p1),
+            instruction!(op = alloc, args = [size], dest = p2),
+            instruction!(op = store, args = [p1, size]),
+            instruction!(op = load, args = [p1], dest = v1),
+            instruction!(op = store, args = [p1, v1]),
+            instruction!(op = load, args = [p1], dest = v2),
+            instruction!(op = ptradd, args = [p1, size], dest = q1),
+            instruction!(op = ptradd, args = [p1, size], dest = q2),
+            instruction!(op = free, args = [q2]),
+        ];
+
+        // When
+        let optimized_block = local_value_numbering(block).expect("failed to apply lvn");
+
+        // Then
+        let expected_block = vec![
+            instruction!(op = const, value = 1, dest = size),
+            instruction!(op = alloc, args = [size], dest = p1),
+            instruction!(op = alloc, args = [size], dest = p2),
+            instruction!(op = store, args = [p1, size]),
+            instruction!(op = load, args = [p1], dest = v1),
+            instruction!(op = store, args = [p1, v1]),
+            instruction!(op = load, args = [p1], dest = v2),
+            instruction!(op = ptradd, args = [p1, size], dest = q1),
+            instruction!(op = id, args = [q1], dest = q2),
+            instruction!(op = free, args = [q1]),
+        ];
+
+        assert_eq!(optimized_block, expected_block);
+    }
+
+    #[test]
+    fn test_local_value_numbering_memory_versions() {
+        // Given
+        let args = ["p", "size"].map(|name| FunctionArg {
+            name: name.into(),
+            r#type: Type::Int,
+        });
+        let block = vec![
+            instruction!(op = load, args = [p], dest = v1),
+            instruction!(op = load, args = [p], dest = v2),
+            instruction!(op = ptradd, args = [p, size], dest = q1),
+            instruction!(op = ptradd, args = [p, size], dest = q2),
+            instruction!(op = load, args = [q1], dest = w1),
+            instruction!(op = load, args = [q2], dest = w2),
+            instruction!(op = call, funcs = [f]),
+            instruction!(op = load, args = [p], dest = v3),
+            instruction!(op = load, args = [p], dest = v4),
+            instruction!(op = store, args = [q1, v4]),
+            instruction!(op = load, args = [p], dest = v5),
+            instruction!(op = print, args = [v5]),
+        ];
+
+        // When
+        let optimized_block =
+            local_value_numbering_with_args(block, &args).expect("failed to apply lvn");
+        let options = LvnOptions {
+            loads: false,
+            ..Default::default()
+        };
+        let loads = vec![
+            instruction!(op = const, value = 1, dest = size),
+            instruction!(op = alloc, args = [size], dest = p),
+            instruction!(op = load, args = [p], dest = v1),
+            instruction!(op = load, args = [p], dest = v2),
+        ];
+        let kept =
+            local_value_numbering_with(loads.clone(), &options).expect("failed to apply lvn");
+
+        // Then
+        let expected_block = vec![
+            instruction!(op = load, args = [p], dest = v1),
+            instruction!(op = id, args = [v1], dest = v2),
+            instruction!(op = ptradd, args = [p, size], dest = q1),
+            instruction!(op = id, args = [q1], dest = q2),
+            instruction!(op = load, args = [q1], dest = w1),
+            instruction!(op = id, args = [w1], dest = w2),
+            instruction!(op = call, funcs = [f]),
+            instruction!(op = load, args = [p], dest = v3),
+            instruction!(op = id, args = [v3], dest = v4),
+            instruction!(op = store, args = [q1, v3]),
+            instruction!(op = load, args = [p], dest = v5),
+            instruction!(op = print, args = [v5]),
+        ];
+        assert_eq!(optimized_block, expected_block);
+        assert_eq!(kept, loads);
+    }
+
+    #[test]
+    fn test_local_value_numbering_speculation() {
+        // Given
+        let guard = Instruction {
+            op: Operation::Guard,
+            args: vec!["c".into()],
+            labels: Some(vec!["abort".to_string()]),
+            ..Default::default()
+        };
+        let block = vec![
+            instruction!(op = speculate),
+            instruction!(op = lt, args = [a, b], dest = c),
+            guard.clone(),
+            guard.clone(),
+            instruction!(op = commit),
+        ];
+        let args = ["a", "b"].map(|name| FunctionArg {
+            name: name.into(),
+            r#type: Type::Int,
+        });
+
+        // When
+        let optimized_block =
+            local_value_numbering_with_args(block.clone(), &args).expect("failed to apply lvn");
+
+        // Then
+        assert_eq!(optimized_block, block);
+    }
+
+    #[test]
+    fn test_local_value_numbering_keeps_positions() {
+        // Given
+        let mut block = vec![
+            instruction!(op = const, value = 1, dest = a),
+            instruction!(op = const, value = 1, dest = b),
+            instruction!(op = add, args = [a, b], dest = sum),
+        ];
+        for (row, instruction) in block.iter_mut().enumerate() {
+            instruction.pos = Some(Position {
+                row: row as u64,
+                col: 2,
+            });
+        }
+
+        // When
+        let optimized_block = local_value_numbering(block).expect("failed to apply lvn");
+
+        // Then
+        let positions = optimized_block.iter().map(|i| i.pos).collect::<Vec<_>>();
+        assert_eq!(optimized_block[1].op, Operation::Id);
+        assert_eq!(
+            positions,
+            (0..3)
+                .map(|row| Some(Position { row, col: 2 }))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_local_value_numbering_unknown_operation() {
+        // Given
+        let unknown = Instruction {
+            op: Operation::Other("vecload".to_string()),
+            args: vec!["a".into()],
+            dest: Some("v".into()),
+            ..Default::default()
+        };
+        let block = vec![
+            instruction!(op = const, value = 1, dest = a),
+            unknown.clone(),
+            unknown.clone(),
+            instruction!(op = add, args = [v, v], dest = sum),
+        ];
+
+        // When
+        let optimized_block = local_value_numbering(block.clone()).expect("failed to apply lvn");
+
+        // Then
+        // The first load is kept, its destination being reassigned by the second one
+        let mut expected = block;
+        expected[1].dest = Some("lvn.0".into());
+        assert_eq!(optimized_block, expected);
+    }
+
+    #[test]
+    fn test_local_value_numbering_clobbered_destination() {
+        // Given
+        let args = ["a"].map(|name| FunctionArg {
+            name: name.into(),
+            r#type: Type::Int,
+        });
+        let block = vec![
+            instruction!(op = add, args = [a, a], dest = b),
+            instruction!(op = add, args = [a, a], dest = c),
+            instruction!(op = const, value = 5, dest = b),
+            instruction!(op = print, args = [c]),
+            instruction!(op = print, args = [b]),
+        ];
+
+        // When
+        let optimized_block =
+            local_value_numbering_with_args(block, &args).expect("failed to apply lvn");
+
+        // Then
+        let expected = vec![
+            instruction!(op = add, args = [a, a], dest = "lvn.0"),
+            instruction!(op = id, args = ["lvn.0"], dest = c),
+            instruction!(op = const, value = 5, dest = b),
+            instruction!(op = print, args = ["lvn.0"]),
+            instruction!(op = print, args = [b]),
+        ];
+        assert_eq!(optimized_block, expected);
+    }
+
+    #[test]
+    fn test_local_value_numbering_reassigned_in_place() {
+        // Given
+        let args = ["x"].map(|name| FunctionArg {
+            name: name.into(),
+            r#type: Type::Int,
+        });
+        let block = vec![
+            instruction!(op = add, args = [x, x], dest = a),
+            instruction!(op = add, args = [a, a], dest = a),
+            instruction!(op = add, args = [a, a], dest = a),
+            instruction!(op = print, args = [a]),
+        ];
+
+        // When
+        let optimized_block =
+            local_value_numbering_with_args(block, &args).expect("failed to apply lvn");
+
+        // Then
+        let expected = vec![
+            instruction!(op = add, args = [x, x], dest = "lvn.0"),
+            instruction!(op = add, args = ["lvn.0", "lvn.0"], dest = "lvn.1"),
+            instruction!(op = add, args = ["lvn.1", "lvn.1"], dest = a),
+            instruction!(op = print, args = [a]),
+        ];
+        assert_eq!(optimized_block, expected);
+    }
+
+    #[test]
+    fn test_local_value_numbering_clobbered_argument() {
+        // Given
+        let args = [FunctionArg {
+            name: "x".into(),
+            r#type: Type::Int,
+        }];
+        let block = vec![
+            instruction!(op = id, args = [x], dest = y),
+            instruction!(op = const, value = 1, dest = x),
+            instruction!(op = add, args = [y, y], dest = z),
+            instruction!(op = print, args = [z]),
+        ];
+
+        // When
+        let optimized_block =
+            local_value_numbering_with_args(block.clone(), &args).expect("failed to apply lvn");
+
+        // Then
+        // The argument can't be renamed, so its copy is used once it is reassigned
+        assert_eq!(optimized_block, block);
+    }
+
+    #[test]
+    fn test_local_value_numbering_destless_uses() {
+        // Given
+        let args = [FunctionArg {
+            name: "x".into(),
+            r#type: Type::Int,
+        }];
+        let branch = vec![
+            instruction!(op = eq, args = [x, x], dest = c),
+            instruction!(op = eq, args = [x, x], dest = d),
+            instruction!(op = const, value = false, dest = c),
+            instruction!(op = print, args = [c]),
+            instruction!(op = br, args = [d], labels = [left, right]),
+        ];
+        let ret = vec![
+            instruction!(op = add, args = [x, x], dest = y),
+            instruction!(op = id, args = [y], dest = z),
+            instruction!(op = const, value = 0, dest = y),
+            instruction!(op = ret, args = [z]),
+        ];
+
+        // When
+        let branch = local_value_numbering_with_args(branch, &args).expect("failed to apply lvn");
+        let ret = local_value_numbering_with_args(ret, &args).expect("failed to apply lvn");
+
+        // Then
+        let expected_branch = vec![
+            instruction!(op = eq, args = [x, x], dest = "lvn.0"),
+            instruction!(op = id, args = ["lvn.0"], dest = d),
+            instruction!(op = const, value = false, dest = c),
+            instruction!(op = print, args = [c]),
+            instruction!(op = br, args = ["lvn.0"], labels = [left, right]),
+        ];
+        assert_eq!(branch, expected_branch);
+        let expected_ret = vec![
+            instruction!(op = add, args = [x, x], dest = "lvn.0"),
+            instruction!(op = id, args = ["lvn.0"], dest = z),
+            instruction!(op = const, value = 0, dest = y),
+            instruction!(op = ret, args = ["lvn.0"]),
+        ];
+        assert_eq!(ret, expected_ret);
+    }
+
+    #[test]
+    fn test_local_value_numbering_fresh_names_are_unused() {
+        // Given
+        let block = vec![
+            instruction!(op = const, value = 1, dest = "lvn.0"),
+            instruction!(op = const, value = 2, dest = a),
+            instruction!(op = print, args = ["lvn.0"]),
+            instruction!(op = const, value = 3, dest = a),
+            instruction!(op = print, args = [a]),
+        ];
+
+        // When
+        let optimized_block = local_value_numbering(block).expect("failed to apply lvn");
+
+        // Then
+        assert_eq!(optimized_block[1].dest, Some("lvn.1".into()));
+    }
+
+    #[test]
+    fn test_local_value_numbering_effects_are_kept() {
+        // Given
+        let block = vec![
+            instruction!(op = const, value = 1, dest = a),
+            instruction!(op = id, args = [a], dest = b),
+            instruction!(op = print, args = [b]),
+            instruction!(op = print, args = [b]),
+            instruction!(op = call, args = [b], funcs = [f]),
+            instruction!(op = call, args = [b], funcs = [f]),
+            instruction!(op = store, args = [p, b]),
+            instruction!(op = store, args = [p, b]),
+        ];
+
+        // When
+        let optimized_block = local_value_numbering_with_args(
+            block.clone(),
+            &[FunctionArg {
+                name: "p".into(),
+                r#type: Type::Ptr(Box::new(Type::Int)),
+            }],
+        )
+        .expect("failed to apply lvn");
+
+        // Then
+        let mut expected = block;
+        for instruction in &mut expected[2..8] {
+            *instruction.args_mut() = instruction
+                .args()
+                .iter()
+                .map(|a| if a == "b" { "a".into() } else { *a })
+                .collect();
+        }
+        assert_eq!(optimized_block, expected);
+    }
+
+    #[test]
+    fn test_local_value_numbering_generated_programs() {
+        for seed in 0..50 {
+            // Given
+            let program = generate(&Config {
+                seed,
+                functions: 2,
+                ..Default::default()
+            });
+
+            for function in &program.functions {
+                let blocks = function
+                    .instrs
+                    .split(|c| matches!(c, Code::Label { .. }))
+                    .map(|codes| {
+                        codes
+                            .iter()
+                            .filter_map(|c| match c {
+                                Code::Instruction(i) => Some(i.clone()),
+                                Code::Label { .. } => None,
+                            })
+                            .collect::<Vec<_>>()
+                    });
+
+                for block in blocks {
+                    // When
+                    let optimized_block =
+                        local_value_numbering_with_args(block.clone(), &function.args);
+
+                    // Then
+                    let optimized_block = optimized_block.expect("failed to apply lvn");
+                    assert_eq!(optimized_block.len(), block.len(), "seed {seed}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_local_value_numbering_typed_constants() {
+        // Given
+        let block = vec![
+            instruction!(op = const, value = 1, dest = x, ty = float),
+            instruction!(op = const, value = 1, dest = y, ty = int),
+            instruction!(op = const, value = 1, dest = z, ty = int),
+            instruction!(op = id, args = [x], dest = w, ty = float),
+        ];
+
+        // When
+        let optimized_block = local_value_numbering(block).expect("failed to apply lvn");
+
+        // Then
+        let expected_block = vec![
+            instruction!(op = const, value = 1, dest = x, ty = float),
+            instruction!(op = const, value = 1, dest = y, ty = int),
+            instruction!(op = id, args = [y], dest = z, ty = int),
+            instruction!(op = id, args = [x], dest = w, ty = float),
+        ];
+        assert_eq!(optimized_block, expected_block);
+    }
+
+    #[test]
+    fn test_local_value_numbering_with_hints() {
+        // Given
+        let block = vec![
+            instruction!(op = add, args = [x, y], dest = a),
+            instruction!(op = add, args = [x, y], dest = b),
+            instruction!(op = add, args = [x, y], dest = c),
+            instruction!(op = id, args = [a], dest = d),
+            instruction!(op = add, args = [x, y], dest = e),
+            instruction!(op = print, args = [c]),
+            instruction!(op = print, args = [d]),
+            instruction!(op = add, args = [x, y], dest = e),
+        ];
+
+        // When
+        let (optimized_block, dead) =
+            local_value_numbering_with_hints(block).expect("failed to apply lvn");
+
+        // Then
+        let expected_block = vec![
+            instruction!(op = add, args = [x, y], dest = a),
+            instruction!(op = id, args = [a], dest = b),
+            instruction!(op = id, args = [a], dest = c),
+            instruction!(op = id, args = [a], dest = d),
+            instruction!(op = id, args = [a], dest = e),
+            instruction!(op = print, args = [a]),
+            instruction!(op = print, args = [a]),
+            instruction!(op = id, args = [a], dest = e),
+        ];
+        assert_eq!(optimized_block, expected_block);
+        assert_eq!(dead, [1, 2, 4, 7]);
+    }
+
+    #[test]
+    fn test_lvn_dce() {
+        // Given
+        let block = vec![
+            instruction!(op = mul, args = [x, y], dest = a),
+            instruction!(op = mul, args = [y, x], dest = b),
+            instruction!(op = add, args = [a, b], dest = c),
+            instruction!(op = print, args = [c]),
+        ];
+
+        // When
+        let optimized_block = lvn_dce(block).expect("failed to apply lvn");
+
+        // Then
+        let expected_block = vec![
+            instruction!(op = mul, args = [x, y], dest = a),
+            instruction!(op = add, args = [a, a], dest = c),
+            instruction!(op = print, args = [c]),
+        ];
+        assert_eq!(optimized_block, expected_block);
+    }
+
+    #[test]
+    fn test_run_in_place() {
+        // Given
+        let mut block = vec![
+            instruction!(op = const, value = 4, dest = a, ty = int),
+            instruction!(op = add, args = [a, a], dest = b, ty = int),
+            instruction!(op = add, args = [a, a], dest = c, ty = int),
+            instruction!(op = print, args = [c]),
+        ];
+        let expected = local_value_numbering(block.clone()).unwrap();
+
+        // When
+        run_in_place(&mut block).expect("failed to apply lvn");
+
+        // Then
+        assert_eq!(block, expected);
+    }
+
+    #[test]
+    fn test_run_function_in_place() {
+        // Given
+        let mut program = parse_program(
+            r#"
+            @main(x: int, c: bool) {
+              a: int = add x x;
+              b: int = add x x;
+              br c .then .end;
+            .then:
+              d: int = add x x;
+            .end:
+              e: int = id a;
+              f: int = add e e;
+              g: int = add a a;
+              print b f g;
+            }
+            "#,
+        )
+        .unwrap();
+
+        // When
+        run_function_in_place(&mut program.functions[0]).expect("failed to apply lvn");
+
+        // Then
+        let expected = parse_program(
+            r#"
+            @main(x: int, c: bool) {
+              a: int = add x x;
+              b: int = id a;
+              br c .then .end;
+            .then:
+              d: int = add x x;
+            .end:
+              e: int = id a;
+              f: int = add a a;
+              g: int = id f;
+              print b f f;
+            }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(program, expected);
+    }
+
+    #[test]
+    fn test_run_function_in_place_error() {
+        // Given
+        let mut program = parse_program("@main { b: int = id; }").unwrap();
+
+        // When
+        let result = run_function_in_place(&mut program.functions[0]);
+
+        // Then
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "function main, instruction 0: missing argument for Id"
+        );
+    }
+
+    #[test]
+    fn test_run_program() {
+        // Given
+        let mut program = generate(&Config {
+            seed: 3,
+            functions: 20,
+            ..Default::default()
+        });
+        let mut expected = program.clone();
+        number_program(&mut expected);
+
+        // When
+        run_program(&mut program).expect("failed to apply lvn");
+
+        // Then
+        assert_eq!(program, expected);
+    }
+
+    /// Applies the numbering on each basic block of the program
+    fn number_program(program: &mut BrilProgram) {
+        for function in &mut program.functions {
+            let mut cfg = Cfg::new(function).unwrap();
+            for block in &mut cfg.blocks {
+                let instrs = std::mem::take(&mut block.instrs);
+                block.instrs = local_value_numbering_with_args(instrs, &function.args)
+                    .expect("failed to apply lvn");
+            }
+            function.instrs = cfg.into_instrs();
+        }
+    }
+
+    #[test]
+    fn test_local_value_numbering_is_deterministic() {
+        for seed in 0..20 {
+            // Given
+            let program = generate(&Config {
+                seed,
+                functions: 4,
+                ..Default::default()
+            });
+
+            // When
+            // Each run uses new maps, which are seeded differently
+            let outputs = (0..4)
+                .map(|_| {
+                    let mut program = program.clone();
+                    number_program(&mut program);
+                    let mut cfg = Cfg::new(&program.functions[0]).unwrap();
+                    ebb_value_numbering(&mut cfg).expect("failed to apply lvn");
+                    program.functions[0].instrs = cfg.into_instrs();
+                    serde_json::to_string(&program).unwrap()
+                })
+                .collect::<Vec<_>>();
+
+            // Then
+            assert!(outputs.iter().all(|o| *o == outputs[0]), "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn test_local_value_numbering_output_verifies() {
+        for seed in 0..50 {
+            // Given
+            let mut program = generate(&Config {
+                seed,
+                functions: 2,
+                ..Default::default()
+            });
+            assert!(verify_program(&program).is_empty(), "seed {seed}");
+
+            // When
+            number_program(&mut program);
+            let json = serde_json::to_string(&program).unwrap();
+
+            // Then
+            let program: BrilProgram = serde_json::from_str(&json).unwrap();
+            let diagnostics = verify_program(&program);
+            assert!(diagnostics.is_empty(), "seed {seed}: {diagnostics:?}");
+        }
+    }
+
+    #[test]
+    fn test_local_value_numbering_reports_location() {
+        // Given
+        let block = vec![
+            instruction!(op = const, value = 1, dest = a),
+            Instruction {
+                op: Operation::Id,
+                dest: Some("b".into()),
+                ..Default::default()
+            },
+        ];
+
+        // When
+        let error = local_value_numbering(block).unwrap_err();
+
+        // Then
+        let diagnostic = error.downcast_ref::<Diagnostic>().unwrap();
+        assert_eq!(diagnostic.index, Some(1));
+        assert_eq!(error.to_string(), "instruction 1: missing argument for Id");
+    }
+
+    #[test]
+    fn test_local_value_numbering_live_in() {
+        // Given
+        let block = vec![
+            instruction!(op = add, args = [x, y], dest = a),
+            instruction!(op = add, args = [y, x], dest = b),
+            instruction!(op = add, args = [x, z], dest = c),
+            instruction!(op = id, args = [y], dest = d),
+            instruction!(op = mul, args = [d, x], dest = e),
+            instruction!(op = mul, args = [x, y], dest = f),
+            instruction!(op = print, args = [f]),
+        ];
+
+        // When
+        let optimized_block = local_value_numbering(block).expect("failed to apply lvn");
+
+        // Then
+        let expected_block = vec![
+            instruction!(op = add, args = [x, y], dest = a),
+            instruction!(op = id, args = [a], dest = b),
+            instruction!(op = add, args = [x, z], dest = c),
+            instruction!(op = id, args = [y], dest = d),
+            instruction!(op = mul, args = [y, x], dest = e),
+            instruction!(op = id, args = [e], dest = f),
+            instruction!(op = print, args = [e]),
+        ];
+        assert_eq!(optimized_block, expected_block);
+    }
+
+    #[test]
+    fn test_local_value_numbering_id_chains() {
+        // Given
+        let block = vec![
+            instruction!(op = const, value = 4, dest = a),
+            instruction!(op = id, args = [a], dest = b, ty = int),
+            instruction!(op = id, args = [b], dest = c, ty = int),
+            instruction!(op = const, value = 4, dest = d),
+            instruction!(op = mul, args = [c, c], dest = e),
+            instruction!(op = print, args = [e]),
+        ];
+        let options = LvnOptions {
+            constants: Constants::Materialize,
+            ..Default::default()
+        };
+
+        // When
+        let canonical = local_value_numbering(block.clone()).expect("failed to apply lvn");
+        let materialized =
+            local_value_numbering_with(block, &options).expect("failed to apply lvn");
+
+        // Then
+        let expected_canonical = vec![
+            instruction!(op = const, value = 4, dest = a),
+            instruction!(op = id, args = [a], dest = b, ty = int),
+            instruction!(op = id, args = [a], dest = c, ty = int),
+            instruction!(op = id, args = [a], dest = d),
+            instruction!(op = const, value = 16, dest = e),
+            instruction!(op = print, args = [e]),
+        ];
+        assert_eq!(canonical, expected_canonical);
+        let expected_materialized = vec![
+            instruction!(op = const, value = 4, dest = a),
+            instruction!(op = const, value = 4, dest = b, ty = int),
+            instruction!(op = const, value = 4, dest = c, ty = int),
+            instruction!(op = const, value = 4, dest = d),
+            instruction!(op = const, value = 16, dest = e),
+            instruction!(op = print, args = [e]),
+        ];
+        assert_eq!(materialized, expected_materialized);
+    }
+
+    #[test]
+    fn test_local_value_numbering_phis() {
+        // Given
+        let program = parse_program(
+            r#"
+            @main {
+              p: int = phi a b .l .r;
+              s: int = phi b a .r .l;
+              t: int = phi a b .r .l;
+              q: float = phi b a .r .l;
+              u: int = add s p;
+              print u t q;
+            }
+            "#,
+        )
+        .unwrap();
+        let block = Cfg::new(&program.functions[0]).unwrap().blocks[0]
+            .instrs
+            .clone();
+
+        // When
+        let optimized_block = local_value_numbering(block).expect("failed to apply lvn");
+
+        // Then
+        let expected = parse_program(
+            r#"
+            @main {
+              p: int = phi a b .l .r;
+              s: int = id p;
+              t: int = phi a b .r .l;
+              q: float = phi b a .r .l;
+              u: int = add p p;
+              print u t q;
+            }
+            "#,
+        )
+        .unwrap();
+        let expected_block = &Cfg::new(&expected.functions[0]).unwrap().blocks[0].instrs;
+        assert_eq!(&optimized_block, expected_block);
+    }
+
+    #[test]
+    fn test_local_value_numbering_booleans() {
+        // Given
+        let block = vec![
+            instruction!(op = const, value = true, dest = t),
+            instruction!(op = const, value = false, dest = f),
+            instruction!(op = and, args = [t, f], dest = a),
+            instruction!(op = or, args = [f, t], dest = b),
+            instruction!(op = not, args = [a], dest = c),
+            instruction!(op = const, value = 3, dest = x),
+            instruction!(op = lt, args = [x, x], dest = d),
+            instruction!(op = eq, args = [x, x], dest = e),
+            instruction!(op = and, args = [p, p], dest = g),
+            instruction!(op = or, args = [g, p], dest = h),
+            instruction!(op = not, args = [h], dest = i),
+            instruction!(op = not, args = [i], dest = j),
+            instruction!(op = br, args = [j], labels = [yes, no]),
+        ];
+
+        // When
+        let optimized_block = local_value_numbering(block).expect("failed to apply lvn");
+
+        // Then
+        let expected_block = vec![
+            instruction!(op = const, value = true, dest = t),
+            instruction!(op = const, value = false, dest = f),
+            instruction!(op = id, args = [f], dest = a),
+            instruction!(op = id, args = [t], dest = b),
+            instruction!(op = id, args = [t], dest = c),
+            instruction!(op = const, value = 3, dest = x),
+            instruction!(op = id, args = [f], dest = d),
+            instruction!(op = id, args = [t], dest = e),
+            instruction!(op = id, args = [p], dest = g),
+            instruction!(op = id, args = [p], dest = h),
+            instruction!(op = not, args = [p], dest = i),
+            instruction!(op = id, args = [p], dest = j),
+            instruction!(op = br, args = [p], labels = [yes, no]),
+        ];
+        assert_eq!(optimized_block, expected_block);
+    }
+
+    #[test]
+    fn test_local_value_numbering_rematerialize_consts() {
+        // Given
+        let block = vec![
+            instruction!(op = const, value = 4, dest = a, ty = int),
+            instruction!(op = add, args = [x, a], dest = b, ty = int),
+            instruction!(op = id, args = [a], dest = c, ty = int),
+            instruction!(op = mul, args = [a, a], dest = d, ty = int),
+            instruction!(op = lt, args = [x, d], dest = e, ty = bool),
+            instruction!(op = print, args = [a]),
+            instruction!(op = br, args = [e], labels = [yes, no]),
+        ];
+        let options = LvnOptions {
+            rematerialize_consts: true,
+            ..Default::default()
+        };
+
+        // When
+        let optimized_block =
+            local_value_numbering_with(block.clone(), &options).expect("failed to apply lvn");
+        let (reported, report) =
+            local_value_numbering_with_report(block, &options).expect("failed to apply lvn");
+
+        // Then
+        let expected_block = vec![
+            instruction!(op = const, value = 4, dest = a, ty = int),
+            instruction!(op = const, value = 4, dest = "lvn.0", ty = int),
+            instruction!(op = add, args = [x, "lvn.0"], dest = b, ty = int),
+            instruction!(op = const, value = 4, dest = c, ty = int),
+            instruction!(op = const, value = 16, dest = d, ty = int),
+            instruction!(op = const, value = 16, dest = "lvn.1", ty = int),
+            instruction!(op = lt, args = [x, "lvn.1"], dest = e, ty = bool),
+            instruction!(op = const, value = 4, dest = "lvn.2", ty = int),
+            instruction!(op = print, args = ["lvn.2"]),
+            instruction!(op = br, args = [e], labels = [yes, no]),
+        ];
+        assert_eq!(optimized_block, expected_block);
+        assert_eq!(reported, expected_block);
+        assert_eq!(report.copies, 0);
+        assert_eq!(report.constants, 1);
+    }
+
+    #[test]
+    fn test_local_value_numbering_reassociate() {
+        // Given
+        let block = vec![
+            instruction!(op = add, args = [x, y], dest = a),
+            instruction!(op = add, args = [a, z], dest = b),
+            instruction!(op = add, args = [z, y], dest = c),
+            instruction!(op = add, args = [x, c], dest = d),
+            instruction!(op = mul, args = [x, y], dest = e),
+            instruction!(op = add, args = [e, z], dest = f),
+            instruction!(op = mul, args = [y, z], dest = g),
+            instruction!(op = add, args = [x, g], dest = h),
+            instruction!(op = sub, args = [x, y], dest = i),
+            instruction!(op = sub, args = [i, z], dest = j),
+            instruction!(op = sub, args = [y, z], dest = k),
+            instruction!(op = sub, args = [x, k], dest = l),
+            instruction!(op = print, args = [d]),
+        ];
+        let options = LvnOptions {
+            reassociate: true,
+            ..Default::default()
+        };
+
+        // When
+        let grouped = local_value_numbering(block.clone()).expect("failed to apply lvn");
+        let reassociated =
+            local_value_numbering_with(block.clone(), &options).expect("failed to apply lvn");
+
+        // Then
+        assert_eq!(grouped, block);
+        let mut expected = block;
+        expected[3] = instruction!(op = id, args = [b], dest = d);
+        expected[12] = instruction!(op = print, args = [b]);
+        assert_eq!(reassociated, expected);
+    }
+
+    #[test]
+    fn test_local_value_numbering_with_options() {
+        // Given
+        let block = vec![
+            instruction!(op = const, value = 1, dest = a),
+            instruction!(op = const, value = 2, dest = b),
+            instruction!(op = lt, args = [a, b], dest = c),
+            instruction!(op = not, args = [c], dest = d),
+            instruction!(op = not, args = [d], dest = e),
+            instruction!(op = add, args = [a, b], dest = f),
+            instruction!(op = add, args = [b, a], dest = g),
+            instruction!(op = const, value = 5, dest = f),
+            instruction!(op = add, args = [a, b], dest = h),
+            instruction!(op = print, args = [e]),
+        ];
+        let disabled = LvnOptions {
+            fold: false,
+            simplify: false,
+            canonicalize: false,
+            rename: false,
+            ..Default::default()
+        };
+
+        // When
+        let enabled = local_value_numbering(block.clone()).expect("failed to apply lvn");
+        let disabled = local_value_numbering_with(block, &disabled).expect("failed to apply lvn");
+
+        // Then
+        let expected_enabled = vec![
+            instruction!(op = const, value = 1, dest = a),
+            instruction!(op = const, value = 2, dest = b),
+            instruction!(op = const, value = true, dest = c),
+            instruction!(op = const, value = false, dest = d),
+            instruction!(op = id, args = [c], dest = e),
+            instruction!(op = const, value = 3, dest = "lvn.0"),
+            instruction!(op = id, args = ["lvn.0"], dest = g),
+            instruction!(op = const, value = 5, dest = f),
+            instruction!(op = id, args = ["lvn.0"], dest = h),
+            instruction!(op = print, args = [c]),
+        ];
+        assert_eq!(enabled, expected_enabled);
+        let expected_disabled = vec![
+            instruction!(op = const, value = 1, dest = a),
+            instruction!(op = const, value = 2, dest = b),
+            instruction!(op = lt, args = [a, b], dest = c),
+            instruction!(op = not, args = [c], dest = d),
+            instruction!(op = not, args = [d], dest = e),
+            instruction!(op = add, args = [a, b], dest = f),
+            instruction!(op = add, args = [b, a], dest = g),
+            instruction!(op = const, value = 5, dest = f),
+            instruction!(op = add, args = [a, b], dest = h),
+            instruction!(op = print, args = [e]),
+        ];
+        assert_eq!(disabled, expected_disabled);
+    }
+
+    #[test]
+    fn test_local_value_numbering_with_report() {
+        // Given
+        let block = vec![
+            instruction!(op = const, value = 1, dest = a),
+            instruction!(op = const, value = 2, dest = b),
+            instruction!(op = lt, args = [a, b], dest = c),
+            instruction!(op = add, args = [a, b], dest = d),
+            instruction!(op = add, args = [b, a], dest = e),
+            instruction!(op = const, value = 1, dest = f),
+            instruction!(op = print, args = [e]),
+        ];
+
+        // When
+        let (_, report) = local_value_numbering_with_report(block, &LvnOptions::default())
+            .expect("failed to apply lvn");
+
+        // Then
+        assert_eq!(report.copies, 2);
+        assert_eq!(report.constants, 2);
+        assert_eq!(
+            report.reuses,
+            [
+                Reuse {
+                    index: 4,
+                    op: Operation::Add,
+                    from: "d".into(),
+                },
+                Reuse {
+                    index: 5,
+                    op: Operation::Const,
+                    from: "a".into(),
+                },
+            ]
+        );
+        assert_eq!(
+            report.to_string(),
+            "copies: 2, constants: 2\n  instruction 4: add reused from d\n  instruction 5: const reused from a\n"
+        );
+    }
+
+    #[test]
+    fn test_local_value_numbering_with_analysis() {
+        // Given
+        let block = vec![
+            instruction!(op = const, value = 2, dest = a),
+            instruction!(op = add, args = [x, a], dest = b),
+            instruction!(op = add, args = [a, x], dest = c),
+            instruction!(op = const, value = 3, dest = a),
+        ];
+        let args = [FunctionArg {
+            name: "x".into(),
+            r#type: Type::Int,
+        }];
+
+        // When
+        let (_, analysis) =
+            local_value_numbering_with_analysis(block, &args).expect("failed to apply lvn");
+
+        // Then
+        assert!(analysis.equivalent("b".into(), "c".into()));
+        assert!(!analysis.equivalent("a".into(), "lvn.0".into()));
+        assert!(!analysis.equivalent("b".into(), "unknown".into()));
+        let sum = analysis.value_number("b".into()).unwrap();
+        assert_eq!(analysis.holder(sum), Some("b".into()));
+        assert_eq!(analysis.expr(sum).unwrap().op(), &Operation::Add);
+        let two = analysis.value_number("lvn.0".into()).unwrap();
+        assert_eq!(analysis.constant(two), Some(Literal::Int(2)));
+        assert_eq!(analysis.holder(two), Some("lvn.0".into()));
+        let x = analysis.value_number("x".into()).unwrap();
+        assert_eq!(analysis.expr(x), None);
+        assert_eq!(analysis.constant(x), None);
+    }
+
+    #[test]
+    fn test_ebb_value_numbering() {
+        // Given
+        let program = parse_program(
+            r#"
+            @main(x: int, c: bool) {
+              a: int = add x x;
+              br c .then .else;
+            .then:
+              b: int = add x x;
+              m: int = mul x x;
+              print b m;
+              jmp .end;
+            .else:
+              d: int = add x x;
+              n: int = mul x x;
+              print d n;
+            .end:
+              e: int = add x x;
+              print e;
+            }
+            "#,
+        )
+        .unwrap();
+        let mut cfg = Cfg::new(&program.functions[0]).unwrap();
+
+        // When
+        ebb_value_numbering(&mut cfg).expect("failed to apply lvn");
+
+        // Then
+        let expected = parse_program(
+            r#"
+            @main(x: int, c: bool) {
+              a: int = add x x;
+              br c .then .else;
+            .then:
+              b: int = id a;
+              m: int = mul x x;
+              print a m;
+              jmp .end;
+            .else:
+              d: int = id a;
+              n: int = mul x x;
+              print a n;
+            .end:
+              e: int = add x x;
+              print e;
+            }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(cfg.into_instrs(), expected.functions[0].instrs);
+    }
+
+    #[test]
+    fn test_ebb_value_numbering_clobbered_in_child() {
+        // Given
+        let program = parse_program(
+            r#"
+            @main(x: int, c: bool) {
+              a: int = add x x;
+              br c .then .end;
+            .then:
+              a: int = const 1;
+              b: int = add x x;
+              d: int = add x x;
+              print a d;
+            .end:
+              ret;
+            }
+            "#,
+        )
+        .unwrap();
+        let mut cfg = Cfg::new(&program.functions[0]).unwrap();
+
+        // When
+        ebb_value_numbering(&mut cfg).expect("failed to apply lvn");
+
+        // Then
+        let expected = parse_program(
+            r#"
+            @main(x: int, c: bool) {
+              a: int = add x x;
+              br c .then .end;
+            .then:
+              a: int = const 1;
+              b: int = add x x;
+              d: int = id b;
+              print a b;
+            .end:
+              ret;
+            }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(cfg.into_instrs(), expected.functions[0].instrs);
+    }
+
+    #[test]
+    fn test_ebb_value_numbering_guard() {
+        // Given
+        let program = parse_program(
+            r#"
+            @main(x: int, c: bool) {
+              speculate;
+              a: int = add x x;
+              guard c .abort;
+              commit;
+              print a;
+              ret;
+            .abort:
+              d: int = add x x;
+              print d;
+            }
+            "#,
+        )
+        .unwrap();
+        let mut cfg = Cfg::new(&program.functions[0]).unwrap();
+
+        // When
+        ebb_value_numbering(&mut cfg).expect("failed to apply lvn");
+
+        // Then
+        assert_eq!(cfg.into_instrs(), program.functions[0].instrs);
+    }
+
+    #[test]
+    fn test_builtin_passes() {
+        // Given
+        let mut program =
+            parse_program("@main(x: int) { a: int = add x x; b: int = add x x; print b; }")
+                .unwrap();
+
+        // When
+        let registry = PassRegistry::with_builtin().unwrap();
+        registry.run(&["lvn"], &mut program).unwrap();
+
+        // Then
+        assert_eq!(
+            registry.names().collect::<Vec<_>>(),
+            ["gvn", "lvn", "strength"]
+        );
+        assert!(program.to_string().contains("b: int = id a;"));
+    }
 }
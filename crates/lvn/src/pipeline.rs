@@ -0,0 +1,134 @@
+//! Contains the pipelines combining the numbering with the other passes.
+
+use crate::local_value_numbering;
+use bril::types::Block;
+use std::fmt::{Display, Formatter};
+
+/// The number of rounds after which [`lvn_dce_fixpoint`] stops
+pub const DEFAULT_MAX_ITERATIONS: usize = 16;
+
+/// The changes made by the rounds of [`lvn_dce_fixpoint_with`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FixpointReport {
+    /// The number of rounds run, including the last one which changed nothing
+    /// if the fixpoint was reached
+    pub iterations: usize,
+    /// The number of instructions rewritten by the numbering
+    pub rewritten: usize,
+    /// The number of instructions removed by the Dead Code Elimination
+    pub removed: usize,
+    /// Whether a round left the block unchanged before the limit
+    pub converged: bool,
+}
+
+impl Display for FixpointReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "iterations: {}, rewritten: {}, removed: {}, converged: {}",
+            self.iterations, self.rewritten, self.removed, self.converged
+        )
+    }
+}
+
+/// Applies [`lvn_dce_fixpoint_with`] with [`DEFAULT_MAX_ITERATIONS`]
+pub fn lvn_dce_fixpoint(block: Block) -> eyre::Result<(Block, FixpointReport)> {
+    lvn_dce_fixpoint_with(block, DEFAULT_MAX_ITERATIONS)
+}
+
+/// Alternates Local Value Numbering and Dead Code Elimination on the block
+/// until a round changes neither, or until the given number of rounds ran.
+/// Each pass can expose new opportunities to the other one: the copies left
+/// by the numbering are removed, which can make other values unused.
+pub fn lvn_dce_fixpoint_with(
+    mut block: Block,
+    max_iterations: usize,
+) -> eyre::Result<(Block, FixpointReport)> {
+    let mut report = FixpointReport::default();
+    while report.iterations < max_iterations {
+        report.iterations += 1;
+
+        let numbered = local_value_numbering(block.clone())?;
+        let rewritten = block.iter().zip(&numbered).filter(|(a, b)| a != b).count();
+        let mut eliminated = numbered;
+        dce::run_in_place(&mut eliminated);
+        let removed = block.len() - eliminated.len();
+
+        block = eliminated;
+        report.rewritten += rewritten;
+        report.removed += removed;
+        if rewritten == 0 && removed == 0 {
+            report.converged = true;
+            break;
+        }
+    }
+
+    Ok((block, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{lvn_dce_fixpoint, lvn_dce_fixpoint_with, FixpointReport};
+    use bril::types::Operation;
+    use bril_macros::instruction;
+
+    #[test]
+    fn test_lvn_dce_fixpoint() {
+        // Given
+        let block = vec![
+            instruction!(op = add, args = [x, y], dest = a),
+            instruction!(op = add, args = [y, x], dest = b),
+            instruction!(op = mul, args = [a, b], dest = c),
+            instruction!(op = mul, args = [b, a], dest = d),
+            instruction!(op = sub, args = [c, d], dest = e),
+            instruction!(op = print, args = [e]),
+        ];
+
+        // When
+        let (optimized_block, report) = lvn_dce_fixpoint(block).expect("failed to apply lvn");
+
+        // Then
+        let expected_block = vec![
+            instruction!(op = add, args = [x, y], dest = a),
+            instruction!(op = mul, args = [a, a], dest = c),
+            instruction!(op = sub, args = [c, c], dest = e),
+            instruction!(op = print, args = [e]),
+        ];
+        assert_eq!(optimized_block, expected_block);
+        assert_eq!(
+            report,
+            FixpointReport {
+                iterations: 2,
+                rewritten: 4,
+                removed: 2,
+                converged: true,
+            }
+        );
+        assert_eq!(
+            report.to_string(),
+            "iterations: 2, rewritten: 4, removed: 2, converged: true"
+        );
+    }
+
+    #[test]
+    fn test_lvn_dce_fixpoint_limit() {
+        // Given
+        let block = vec![
+            instruction!(op = add, args = [x, y], dest = a),
+            instruction!(op = add, args = [x, y], dest = b),
+            instruction!(op = print, args = [b]),
+        ];
+
+        // When
+        let (optimized_block, report) =
+            lvn_dce_fixpoint_with(block.clone(), 1).expect("failed to apply lvn");
+        let (_, no_rounds) = lvn_dce_fixpoint_with(block, 0).expect("failed to apply lvn");
+
+        // Then
+        assert_eq!(optimized_block.len(), 2);
+        assert!(optimized_block.iter().all(|i| i.op != Operation::Id));
+        assert!(!report.converged);
+        assert_eq!(report.iterations, 1);
+        assert_eq!(no_rounds, FixpointReport::default());
+    }
+}
@@ -0,0 +1,69 @@
+//! Contains the report of the rewrites made by the numbering of a block.
+
+use bril::types::{Instruction, Operation, Var};
+use std::fmt::{Display, Formatter};
+
+/// The rewrites made by the numbering of a block, used to evaluate the pass
+/// without comparing its outputs by hand
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LvnReport {
+    /// The number of instructions turned into copies of another variable
+    pub copies: usize,
+    /// The number of instructions turned into constants, either folded or
+    /// materialized
+    pub constants: usize,
+    /// The values reused instead of being computed again, in the order of
+    /// the block
+    pub reuses: Vec<Reuse>,
+}
+
+/// A value reused by an instruction instead of being computed again
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reuse {
+    /// The index of the instruction in the block
+    pub index: usize,
+    /// The operation the instruction computed originally
+    pub op: Operation,
+    /// The variable the value is copied from
+    pub from: Var,
+}
+
+impl LvnReport {
+    /// Builds the report from the block before and after the numbering,
+    /// which only rewrites the instructions in place
+    pub fn new(before: &[Instruction], after: &[Instruction]) -> Self {
+        let mut report = Self::default();
+        for (index, (before, after)) in before.iter().zip(after).enumerate() {
+            if before.op == after.op {
+                continue;
+            }
+            match after.op {
+                Operation::Id => {
+                    report.copies += 1;
+                    report.reuses.extend(after.args().first().map(|from| Reuse {
+                        index,
+                        op: before.op.clone(),
+                        from: *from,
+                    }));
+                }
+                Operation::Const => report.constants += 1,
+                _ => {}
+            }
+        }
+        report
+    }
+}
+
+impl Display for LvnReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "copies: {}, constants: {}", self.copies, self.constants)?;
+        for reuse in &self.reuses {
+            writeln!(
+                f,
+                "  instruction {}: {} reused from {}",
+                reuse.index, reuse.op, reuse.from
+            )?;
+        }
+        Ok(())
+    }
+}
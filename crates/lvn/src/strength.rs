@@ -0,0 +1,209 @@
+//! Contains the strength reduction of the multiplications by a known power
+//! of two, which are rewritten into additions when they are cheaper.
+
+use bril::cost::{CostModel, DefaultCostModel};
+use bril::types::{Block, Instruction, Literal, Operation, Var};
+use bril_macros::Pass;
+use std::collections::HashMap;
+
+/// The strength reduction pass, run on each basic block with the default
+/// cost model
+#[derive(Pass)]
+#[pass(name = "strength", block = reduce_strength)]
+pub struct StrengthReduction;
+
+/// Applies [`reduce_strength_with`] with the [`DefaultCostModel`]
+pub fn reduce_strength(block: Block) -> Block {
+    reduce_strength_with(block, &DefaultCostModel)
+}
+
+/// Rewrites the multiplications of the block by a constant power of two
+/// `2^k` into `k` additions doubling the value, when the cost model finds
+/// them cheaper than the multiplication. The additions accumulate into the
+/// destination, and the multiplication by one becomes a copy.
+/// Example: (c = const 4, d = mul x c -> d = add x x, d = add d d)
+///
+/// The additions wrap around like the multiplication, so the result is the
+/// same for all the values. Only the constants defined in the block are known.
+pub fn reduce_strength_with(block: Block, model: &impl CostModel) -> Block {
+    let mut constants = HashMap::new();
+    let mut reduced = Block::with_capacity(block.len());
+    for i in block {
+        let doubled = match (&i.op, i.args()) {
+            (Operation::Mul, [a, b]) => match (power(&constants, a), power(&constants, b)) {
+                (_, Some(k)) => Some((*a, k)),
+                (Some(k), _) => Some((*b, k)),
+                _ => None,
+            },
+            _ => None,
+        };
+        let additions = doubled
+            .and_then(|(x, k)| Some((i.dest?, x, k)))
+            .map(|(dest, x, k)| doubling(&i, dest, x, k))
+            .filter(|additions| model.block_cost(additions) < model.cost(&i));
+
+        if let Some(dest) = i.dest {
+            match (&i.op, i.value) {
+                (Operation::Const, Some(Literal::Int(value))) => constants.insert(dest, value),
+                _ => constants.remove(&dest),
+            };
+        }
+        match additions {
+            Some(additions) => reduced.extend(additions),
+            None => reduced.push(i),
+        }
+    }
+
+    reduced
+}
+
+/// Returns `k` if the variable holds the constant `2^k`
+fn power(constants: &HashMap<Var, i64>, var: &Var) -> Option<u32> {
+    let value = u64::try_from(*constants.get(var)?).ok()?;
+    value.is_power_of_two().then(|| value.trailing_zeros())
+}
+
+/// Returns the instructions doubling `x` `k` times into the destination of
+/// the multiplication, keeping its type and position
+fn doubling(mul: &Instruction, dest: Var, x: Var, k: u32) -> Block {
+    if k == 0 {
+        return vec![Instruction {
+            op: Operation::Id,
+            args: vec![x],
+            ..mul.clone()
+        }];
+    }
+
+    (0..k)
+        .map(|step| {
+            let source = if step == 0 { x } else { dest };
+            Instruction {
+                op: Operation::Add,
+                args: vec![source, source],
+                ..mul.clone()
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{reduce_strength, reduce_strength_with};
+    use bril::cost::CostModel;
+    use bril::types::{Instruction, Literal, Operation, Var};
+    use bril_macros::instruction;
+    use std::collections::HashMap;
+
+    /// Makes the multiplications expensive enough to be replaced by any chain
+    struct SlowMul;
+
+    impl CostModel for SlowMul {
+        fn cost(&self, instruction: &Instruction) -> u64 {
+            match instruction.op {
+                Operation::Mul => 100,
+                _ => 1,
+            }
+        }
+    }
+
+    /// Evaluates a block of integer arithmetic, returning the printed values
+    fn evaluate(block: &[Instruction], x: i64) -> Vec<i64> {
+        let mut values = HashMap::<Var, i64>::from([("x".into(), x)]);
+        let mut printed = Vec::new();
+        for i in block {
+            let args = i.args().iter().map(|a| values[a]).collect::<Vec<_>>();
+            let value = match (&i.op, args.as_slice(), i.value) {
+                (Operation::Const, [], Some(Literal::Int(value))) => value,
+                (Operation::Id, [a], _) => *a,
+                (Operation::Add, [a, b], _) => a.wrapping_add(*b),
+                (Operation::Mul, [a, b], _) => a.wrapping_mul(*b),
+                (Operation::Print, [a], _) => {
+                    printed.push(*a);
+                    continue;
+                }
+                _ => panic!("unexpected instruction {i}"),
+            };
+            values.insert(i.dest.unwrap(), value);
+        }
+        printed
+    }
+
+    #[test]
+    fn test_reduce_strength() {
+        // Given
+        let block = vec![
+            instruction!(op = const, value = 1, dest = one, ty = int),
+            instruction!(op = const, value = 2, dest = two, ty = int),
+            instruction!(op = const, value = 4, dest = four, ty = int),
+            instruction!(op = const, value = 8, dest = eight, ty = int),
+            instruction!(op = mul, args = [x, one], dest = a, ty = int),
+            instruction!(op = mul, args = [two, x], dest = b, ty = int),
+            instruction!(op = mul, args = [x, four], dest = c, ty = int),
+            instruction!(op = mul, args = [x, eight], dest = d, ty = int),
+            instruction!(op = const, value = 3, dest = two, ty = int),
+            instruction!(op = mul, args = [x, two], dest = e, ty = int),
+        ];
+
+        // When
+        let reduced = reduce_strength(block);
+
+        // Then
+        let expected = vec![
+            instruction!(op = const, value = 1, dest = one, ty = int),
+            instruction!(op = const, value = 2, dest = two, ty = int),
+            instruction!(op = const, value = 4, dest = four, ty = int),
+            instruction!(op = const, value = 8, dest = eight, ty = int),
+            instruction!(op = id, args = [x], dest = a, ty = int),
+            instruction!(op = add, args = [x, x], dest = b, ty = int),
+            instruction!(op = add, args = [x, x], dest = c, ty = int),
+            instruction!(op = add, args = [c, c], dest = c, ty = int),
+            instruction!(op = mul, args = [x, eight], dest = d, ty = int),
+            instruction!(op = const, value = 3, dest = two, ty = int),
+            instruction!(op = mul, args = [x, two], dest = e, ty = int),
+        ];
+        assert_eq!(reduced, expected);
+    }
+
+    #[test]
+    fn test_reduce_strength_cost_model() {
+        // Given
+        let block = vec![
+            instruction!(op = const, value = 1024, dest = c),
+            instruction!(op = mul, args = [x, c], dest = x),
+            instruction!(op = print, args = [x]),
+        ];
+
+        // When
+        let reduced = reduce_strength_with(block, &SlowMul);
+
+        // Then
+        assert_eq!(reduced.len(), 12);
+        assert_eq!(reduced[1], instruction!(op = add, args = [x, x], dest = x));
+        assert!(reduced[1..11].iter().all(|i| i.op == Operation::Add));
+    }
+
+    #[test]
+    fn test_reduce_strength_semantics() {
+        // Given
+        let mut block = Vec::new();
+        for k in 0..63 {
+            let c = format!("c{k}");
+            let d = format!("d{k}");
+            block.push(Instruction::constant(c.as_str(), 1i64 << k));
+            block.push(Instruction::mul(d.as_str(), "x", c.as_str()));
+            block.push(Instruction::print(d.as_str()));
+        }
+        block.push(Instruction::constant("m", 12i64));
+        block.push(Instruction::mul("x", "x", "m"));
+        block.push(Instruction::print("x"));
+
+        // When
+        let reduced = reduce_strength_with(block.clone(), &SlowMul);
+
+        // Then
+        assert!(reduced.len() > block.len());
+        for x in [0, 1, -1, 7, -12345, i64::MAX, i64::MIN, 1 << 40] {
+            assert_eq!(evaluate(&reduced, x), evaluate(&block, x), "x = {x}");
+        }
+    }
+}
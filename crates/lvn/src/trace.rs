@@ -0,0 +1,94 @@
+//! Contains the installation of a subscriber printing the debug events of
+//! the numbering. Each numbered block is a `lvn_block` span, in which debug
+//! events are recorded for the expression computed by an instruction, the
+//! hits and misses of the table and the rewritten instructions. The events
+//! are filtered by the `RUST_LOG` environment variable:
+//!
+//! ```text
+//! RUST_LOG=lvn=debug cargo test
+//! ```
+
+use tracing_subscriber::EnvFilter;
+
+/// Installs a subscriber printing the events to the standard error, filtered
+/// by the `RUST_LOG` environment variable. Fails if a subscriber is already
+/// installed.
+pub fn init() -> eyre::Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .with_writer(std::io::stderr)
+        .try_init()
+        .map_err(|error| eyre::eyre!(error))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::local_value_numbering;
+    use bril_macros::instruction;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::EnvFilter;
+
+    /// Collects the formatted events
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Returns the events of the numbering of the block, with the filter
+    fn events(filter: &str) -> String {
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_env_filter(EnvFilter::new(filter))
+            .with_writer(move || writer.clone())
+            .with_ansi(false)
+            .without_time()
+            .finish();
+        let block = vec![
+            instruction!(op = add, args = [a, b], dest = sum1),
+            instruction!(op = add, args = [a, b], dest = sum2),
+            instruction!(op = print, args = [sum2]),
+        ];
+
+        tracing::subscriber::with_default(subscriber, || {
+            local_value_numbering(block).expect("failed to apply lvn");
+        });
+        let events = buffer.0.lock().unwrap().clone();
+        String::from_utf8(events).unwrap()
+    }
+
+    #[test]
+    fn test_events() {
+        // When
+        let events = events("lvn=debug");
+
+        // Then
+        let lines = events.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 6, "{events}");
+        assert!(lines.iter().all(|l| l.contains("lvn_block{block=0}")));
+        assert!(lines[0].contains("expression computed index=0 dest=sum1"));
+        assert!(lines[1].contains("table miss index=0 value=2 holder=sum1"));
+        assert!(lines[3].contains("table hit index=1 value=2 holder=sum1"));
+        assert!(lines[4].contains("rewrite performed index=1"));
+        assert!(lines[4].contains("after=sum2 = id sum1;"));
+        assert!(lines[5].contains("rewrite performed index=2"));
+    }
+
+    #[test]
+    fn test_events_filtered() {
+        // When
+        let events = events("lvn=info");
+
+        // Then
+        assert_eq!(events, "");
+    }
+}